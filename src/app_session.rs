@@ -0,0 +1,146 @@
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use log::{info, warn};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::hexterm::TaskId;
+use crate::tasks::{App, DEFAULT_SHELL};
+use crate::views::CharDims;
+
+/***
+AppSession: One of the [[apps]], running in a pseudo-terminal of its own.
+    Everything it prints arrives on `output`, and `send` types into it. `on_output` is called
+    each time there's more, and once more when the app exits.
+    Dropping the session kills the app if it's still running.
+ */
+pub struct AppSession {
+    pub id: TaskId,
+    pub output: Receiver<String>,
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+impl AppSession {
+    /// Start `app` (through the default shell, in its `path`) in a terminal of `size`.
+    pub fn start(app: &App, size: CharDims, on_output: impl Fn() + Send + 'static) -> anyhow::Result<AppSession> {
+        let pair = native_pty_system().openpty(PtySize {
+            rows: size.height as u16,
+            cols: size.width as u16,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+
+        let mut cmd = CommandBuilder::from_argv(shell_words::split(DEFAULT_SHELL)?.into_iter().map(|a| a.into()).collect());
+        cmd.arg(&app.command);
+        if !app.path.is_empty() {
+            cmd.cwd(&app.path);
+        }
+
+        info!("Opening {} ({}) in a {}x{} pty", app.id, app.command, size.width, size.height);
+        let child = pair.slave.spawn_command(cmd)?;
+        let reader = pair.master.try_clone_reader()?;
+
+        let (tx, output) = channel();
+        thread::Builder::new().name(format!("{} pty", app.id)).spawn(move || {
+            let mut reader = reader;
+            let mut chunk = [0u8; 4096];
+            let mut pending = Vec::new();
+            // Reading fails (EIO) rather than hitting EOF once the app exits and its pty closes.
+            while let Ok(n) = reader.read(&mut chunk) {
+                if n == 0 { break; }
+                pending.extend_from_slice(&chunk[..n]);
+                if tx.send(take_utf8(&mut pending)).is_err() { break; }
+                on_output();
+            }
+            drop(tx);
+            on_output();
+        })?;
+
+        Ok(AppSession { id: app.id.clone(), output, master: pair.master, child })
+    }
+
+    /// Type into the app.
+    pub fn send(&mut self, input: &[u8]) -> io::Result<()> {
+        self.master.write_all(input)?;
+        self.master.flush()
+    }
+
+    /// Tell the app its terminal's changed size. It gets a SIGWINCH.
+    pub fn resize(&mut self, size: CharDims) -> anyhow::Result<()> {
+        self.master.resize(PtySize {
+            rows: size.height as u16,
+            cols: size.width as u16,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+    }
+
+    pub fn exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for AppSession {
+    fn drop(&mut self) {
+        if !self.exited() {
+            info!("Killing {}", self.id);
+            if let Err(e) = self.child.kill() {
+                warn!("Couldn't kill {}: {}", self.id, e);
+            }
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/***
+ * Take everything from `bytes` that's complete UTF-8, leaving behind a trailing
+ * partial character for the next read to finish. Invalid bytes become U+FFFD.
+ ***/
+fn take_utf8(bytes: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        // error_len() is None when the input just stops partway through a character.
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => bytes.len(),
+    };
+
+    let rest = bytes.split_off(complete);
+    let text = String::from_utf8_lossy(bytes).into_owned();
+    *bytes = rest;
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn partial_characters_wait_for_the_rest() {
+        let mut bytes = "héllo".as_bytes()[..2].to_vec();
+        assert_eq!(take_utf8(&mut bytes), "h");
+        bytes.extend_from_slice(&"héllo".as_bytes()[2..]);
+        assert_eq!(take_utf8(&mut bytes), "éllo");
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn runs_apps_in_a_pty() {
+        let app: App = toml::from_str("id = 'echo'\nname = ''\ndescription = ''\npath = '/'\ncommand = 'read line; echo \"got $line\"; stty size'").unwrap();
+        let mut session = AppSession::start(&app, CharDims::new(40, 12), || {}).unwrap();
+        session.send(b"hello\r").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut output = String::new();
+        while !output.contains("12 40") && Instant::now() < deadline {
+            if let Ok(chunk) = session.output.recv_timeout(Duration::from_millis(100)) {
+                output.push_str(&chunk);
+            }
+        }
+
+        assert!(output.contains("got hello"), "{:?}", output);
+        assert!(output.contains("12 40"), "{:?}", output);
+    }
+}
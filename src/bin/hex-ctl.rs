@@ -0,0 +1,85 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Parser, Subcommand};
+use hex::ipc::{default_socket_path, Request};
+
+/***
+Args: Command-line arguments for hex-ctl, which sends a single request to a running hex.
+    e.g. `hex-ctl set todo "buy milk"` or `journalctl -n 5 | hex-ctl set log`
+ */
+#[derive(Parser, Debug)]
+#[command(name = "hex-ctl", version, about = "Update a running hex's widgets from scripts")]
+struct Args {
+    /// hex's control socket. Defaults to $XDG_RUNTIME_DIR/hex.sock
+    #[arg(short, long, value_name = "FILE")]
+    socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Replace a widget's content. Reads it from stdin if no TEXT (or "-") is given
+    Set { id: String, text: Vec<String> },
+    /// Add a line (or lines) to a widget's content. Reads stdin if no TEXT (or "-") is given
+    Append { id: String, text: Vec<String> },
+    /// Empty a widget
+    Clear { id: String },
+    /// Run a prompt command, as if it were typed at hex's prompt, e.g. `hex-ctl run status`
+    Run {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+    let socket = args.socket.unwrap_or_else(default_socket_path);
+
+    let request = match args.action {
+        Action::Set { id, text } => Request::Set(id, text_or_stdin(text)),
+        Action::Append { id, text } => Request::Append(id, text_or_stdin(text)),
+        Action::Clear { id } => Request::Clear(id),
+        Action::Run { command } => Request::Run(command.join(" ")),
+    };
+
+    match send(&socket, &request) {
+        Ok(reply) if reply == "ok" => {},
+        Ok(reply) => {
+            eprintln!("hex-ctl: {}", reply.trim_start_matches("error: "));
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("hex-ctl: couldn't talk to hex on {}: {}", socket.display(), e);
+            process::exit(2);
+        }
+    }
+}
+
+/// The text given on the command line, or everything on stdin (minus its last newline).
+fn text_or_stdin(text: Vec<String>) -> String {
+    if !text.is_empty() && text != ["-"] {
+        return text.join(" ");
+    }
+
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        eprintln!("hex-ctl: couldn't read stdin: {}", e);
+        process::exit(2);
+    }
+    input.strip_suffix('\n').unwrap_or(&input).to_string()
+}
+
+/// Send the request and wait for hex's one-line reply.
+fn send(socket: &Path, request: &Request) -> io::Result<String> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", request.to_line())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
@@ -0,0 +1,139 @@
+use std::env;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use hex::ipc::default_socket_path;
+use simplelog::LevelFilter;
+
+const CONFIG_FILE: &str = "tasks.toml";
+const LOG_FILE: &str = "hex.log";
+
+/***
+Cli: Command-line arguments for hex.
+    Everything is optional - with no arguments hex runs the HUD using the first
+    tasks.toml it can find (see `Cli::config_path`).
+ */
+#[derive(Parser, Debug)]
+#[command(name = "hex", version, about = "An extensible, terminal-based HUD for head-mounted-computers")]
+pub struct Cli {
+    /// Path to the tasks.toml to use. Searches the XDG config dirs, then ./config, if omitted.
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Where to write the log. Defaults to $XDG_STATE_HOME/hex/hex.log
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Minimum level to log: off, error, warn, info, debug or trace
+    #[arg(long, global = true, value_name = "LEVEL", default_value = "info")]
+    pub log_level: LevelFilter,
+
+    /// Control socket to listen on. Defaults to $XDG_RUNTIME_DIR/hex.sock
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub mode: Option<Mode>,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    /// Run the HUD (the default)
+    Run,
+    /// Load and validate the config, then exit
+    CheckConfig,
+    /// Print the configured layout tree, then exit
+    PrintLayout,
+}
+
+impl Cli {
+    pub fn mode(&self) -> Mode {
+        self.mode.unwrap_or(Mode::Run)
+    }
+
+    /***
+     * The config file to load. In order of preference:
+     *   1. --config
+     *   2. $XDG_CONFIG_HOME/hex/tasks.toml (~/.config/hex/tasks.toml)
+     *   3. <each of $XDG_CONFIG_DIRS>/hex/tasks.toml (/etc/xdg/hex/tasks.toml)
+     *   4. config/tasks.toml - for running out of a checkout.
+     ***/
+    pub fn config_path(&self) -> PathBuf {
+        if let Some(path) = &self.config {
+            return path.clone();
+        }
+
+        let fallback = PathBuf::from("config").join(CONFIG_FILE);
+        config_dirs().into_iter()
+            .map(|dir| dir.join("hex").join(CONFIG_FILE))
+            .find(|path| path.is_file())
+            .unwrap_or(fallback)
+    }
+
+    /***
+     * The log file to write to: --log-file, or $XDG_STATE_HOME/hex/hex.log (~/.local/state/hex/hex.log).
+     * Falls back to log/hex.log if there's no home directory to speak of.
+     ***/
+    pub fn log_path(&self) -> PathBuf {
+        if let Some(path) = &self.log_file {
+            return path.clone();
+        }
+
+        match xdg_dir("XDG_STATE_HOME", ".local/state") {
+            Some(dir) => dir.join("hex").join(LOG_FILE),
+            None => PathBuf::from("log").join(LOG_FILE)
+        }
+    }
+
+    /// The control socket to listen on for hex-ctl: --socket, or $XDG_RUNTIME_DIR/hex.sock.
+    pub fn socket_path(&self) -> PathBuf {
+        self.socket.clone().unwrap_or_else(default_socket_path)
+    }
+}
+
+fn config_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = xdg_dir("XDG_CONFIG_HOME", ".config").into_iter().collect();
+
+    let system_dirs = env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+    dirs.extend(env::split_paths(&system_dirs).filter(|d| d.is_absolute()));
+
+    dirs
+}
+
+/// Resolve an XDG base directory, falling back to $HOME/<default> as the spec requires.
+fn xdg_dir(var: &str, home_default: &str) -> Option<PathBuf> {
+    match env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(home_default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_run_mode() {
+        let cli = Cli::parse_from(vec!["hex"]);
+        assert_eq!(cli.mode(), Mode::Run);
+        assert_eq!(cli.log_level, LevelFilter::Info);
+    }
+
+    #[test]
+    fn parses_subcommands_with_global_flags() {
+        let cli = Cli::parse_from(vec!["hex", "check-config", "--config", "my.toml", "--log-level", "debug"]);
+        assert_eq!(cli.mode(), Mode::CheckConfig);
+        assert_eq!(cli.config_path(), PathBuf::from("my.toml"));
+        assert_eq!(cli.log_level, LevelFilter::Debug);
+    }
+
+    #[test]
+    fn explicit_log_file_wins() {
+        let cli = Cli::parse_from(vec!["hex", "--log-file", "/tmp/hex.log", "print-layout"]);
+        assert_eq!(cli.mode(), Mode::PrintLayout);
+        assert_eq!(cli.log_path(), PathBuf::from("/tmp/hex.log"));
+    }
+}
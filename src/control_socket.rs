@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fs::{self, DirBuilder};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use hex::ipc::{parse_request, Request};
+use log::{info, warn};
+
+use crate::hexterm::TaskId;
+use crate::task_result::{TaskResult, DEFAULT_MAX_LINES};
+use crate::tasks::Widget;
+
+const HEX_IS_GONE: &str = "hex is shutting down";
+
+/***
+ControlSocket: Listens on a Unix socket for Requests from other programs - usually hex-ctl.
+    Widget content is sent on as TaskResults, exactly like the output of a widget's own command.
+    `run` requests are passed to hex as prompt commands.
+    The socket file is removed again when the ControlSocket is dropped.
+ */
+pub struct ControlSocket {
+    path: PathBuf,
+    content: Arc<Mutex<PushedContent>>,
+}
+
+// What's been pushed into each widget so far, so there's something to append to.
+#[derive(Default)]
+struct PushedContent {
+    max_lines: HashMap<TaskId, usize>,
+    text: HashMap<TaskId, String>,
+}
+
+impl ControlSocket {
+    pub fn new(path: &Path,
+               widgets: &[Widget],
+               output_tx: Sender<HashMap<TaskId, TaskResult>>,
+               command_tx: Sender<String>) -> io::Result<ControlSocket> {
+        let listener = bind(path)?;
+        let socket = ControlSocket { path: path.to_path_buf(), content: Arc::new(Mutex::new(PushedContent::default())) };
+        socket.set_widgets(widgets);
+        info!("Listening for requests on {}", path.display());
+
+        let content = socket.content.clone();
+        thread::Builder::new().name("control socket".to_string()).spawn(move || {
+            for client in listener.incoming() {
+                match client {
+                    Ok(client) => serve_client(client, content.clone(), output_tx.clone(), command_tx.clone()),
+                    Err(e) => warn!("Couldn't accept a control socket connection: {}", e),
+                }
+            }
+        })?;
+
+        Ok(socket)
+    }
+
+    /// The widgets that can be pushed to. Anything pushed to a widget that's gone is dropped.
+    pub fn set_widgets(&self, widgets: &[Widget]) {
+        let mut content = self.content.lock().unwrap();
+        content.max_lines = widgets.iter().map(|w| (w.id.clone(), w.max_lines.unwrap_or(DEFAULT_MAX_LINES))).collect();
+
+        let PushedContent { max_lines, text } = &mut *content;
+        text.retain(|id, _| max_lines.contains_key(id));
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/***
+ * Bind to `path`, clearing away a socket left behind by a hex that didn't exit cleanly.
+ * Refuses if another hex is still listening there. Only we get to connect.
+ ***/
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, "another hex is already listening there"));
+        }
+        fs::remove_file(path)?;
+    }
+
+    // The socket's created with whatever the umask allows, so it's made inside a directory only we
+    // can get into, and only moved into place once its own permissions are narrowed.
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let private_dir = path.with_file_name(format!(".{}.{}", name, process::id()));
+    DirBuilder::new().mode(0o700).create(&private_dir)?;
+    let private_path = private_dir.join("socket");
+    let listener = UnixListener::bind(&private_path).and_then(|listener| {
+        fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&private_path, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&private_path);
+    fs::remove_dir(&private_dir)?;
+    listener
+}
+
+/// Answer each request a client sends, on its own thread, until it hangs up.
+fn serve_client(client: UnixStream,
+                content: Arc<Mutex<PushedContent>>,
+                output_tx: Sender<HashMap<TaskId, TaskResult>>,
+                command_tx: Sender<String>) {
+    thread::spawn(move || {
+        let mut replies = match client.try_clone() {
+            Ok(replies) => replies,
+            Err(e) => return warn!("Couldn't reply on the control socket: {}", e),
+        };
+
+        for line in BufReader::new(client).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            let reply = match parse_request(&line).and_then(|request| handle(request, &content, &output_tx, &command_tx)) {
+                Ok(()) => "ok".to_string(),
+                Err(e) => {
+                    warn!("Rejected control request '{}': {}", line, e);
+                    format!("error: {}", e)
+                }
+            };
+            if writeln!(replies, "{}", reply).is_err() { break; }
+        }
+    });
+}
+
+fn handle(request: Request,
+          content: &Mutex<PushedContent>,
+          output_tx: &Sender<HashMap<TaskId, TaskResult>>,
+          command_tx: &Sender<String>) -> Result<(), String> {
+    let (task_id, text) = match request {
+        Request::Run(command) => return command_tx.send(command).map_err(|_| HEX_IS_GONE.to_string()),
+        Request::Set(id, new_text) => {
+            let text = edit(content, &id, |text, _| *text = new_text)?;
+            (id, text)
+        }
+        Request::Append(id, new_text) => {
+            let text = edit(content, &id, |text, max_lines| {
+                if !text.is_empty() { text.push('\n'); }
+                text.push_str(&new_text);
+                *text = last_lines(text, max_lines);
+            })?;
+            (id, text)
+        }
+        Request::Clear(id) => {
+            let text = edit(content, &id, |text, _| text.clear())?;
+            (id, text)
+        }
+    };
+
+    let mut h = HashMap::new();
+    h.insert(task_id, TaskResult::from_text(text, SystemTime::now()));
+    output_tx.send(h).map_err(|_| HEX_IS_GONE.to_string())
+}
+
+/// Change a widget's pushed content (given its max_lines too), returning the result.
+fn edit<F: FnOnce(&mut String, usize)>(content: &Mutex<PushedContent>, task_id: &TaskId, change: F) -> Result<String, String> {
+    let mut content = content.lock().unwrap();
+    let max_lines = *content.max_lines.get(task_id).ok_or_else(|| format!("no widget has the id '{}'", task_id))?;
+    let text = content.text.entry(task_id.clone()).or_default();
+    change(text, max_lines);
+    Ok(text.clone())
+}
+
+fn last_lines(text: &str, max_lines: usize) -> String {
+    let lines = text.split('\n').collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(max_lines)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    fn push_widget(id: &str, max_lines: usize) -> Widget {
+        toml::from_str(&format!("id = '{}'\nname = ''\ndescription = ''\nmode = 'push'\nmax_lines = {}", id, max_lines)).unwrap()
+    }
+
+    fn request(client: &mut UnixStream, line: &str) -> String {
+        writeln!(client, "{}", line).unwrap();
+        let mut reply = String::new();
+        BufReader::new(client.try_clone().unwrap()).read_line(&mut reply).unwrap();
+        reply
+    }
+
+    #[test]
+    fn pushes_content_into_widgets() {
+        let path = std::env::temp_dir().join(format!("hex-test-{}.sock", std::process::id()));
+        let (output_tx, output_rx) = channel();
+        let (command_tx, command_rx) = channel();
+        let socket = ControlSocket::new(&path, &[push_widget("log", 2)], output_tx, command_tx).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        let mut client = UnixStream::connect(&path).unwrap();
+        let next_text = || output_rx.recv_timeout(Duration::from_secs(5)).unwrap()["log"].stdout.clone();
+
+        assert_eq!(request(&mut client, "set log one"), "ok\n");
+        assert_eq!(next_text(), "one");
+        assert_eq!(request(&mut client, "append log two\\nthree"), "ok\n");
+        assert_eq!(next_text(), "two\nthree");
+        assert_eq!(request(&mut client, "clear log"), "ok\n");
+        assert_eq!(next_text(), "");
+
+        assert_eq!(request(&mut client, "run status"), "ok\n");
+        assert_eq!(command_rx.recv_timeout(Duration::from_secs(5)), Ok("status".to_string()));
+
+        assert_eq!(request(&mut client, "set nope hi"), "error: no widget has the id 'nope'\n");
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
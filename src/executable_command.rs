@@ -1,64 +1,250 @@
 use std::str;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Local, Offset, TimeZone};
 use regex::Regex;
+use crate::task_result::DEFAULT_MAX_LINES;
+use crate::tasks::CommandLine;
 
+// How long a stream widget's command waits to be restarted after it exits (before any backoff).
+const RESTART_MILLIS: u64 = 1000;
+// Stand-in for "never" (e.g. a cron expression with no future matches), short enough not to overflow a SystemTime.
+const NEVER_MILLIS: u64 = 365 * 24 * 3600 * 1000;
+
+#[derive(Clone)]
 pub struct ExecutableCommand {
     pub id: String,
-    pub command: String,
+    pub command: CommandLine,
     pub working_dir: String,
-    pub period: String,
-    pub time_between_runs: u64
+    pub shell: Vec<String>,
+    pub timeout: Option<u64>,
+    pub schedule: Schedule,
+    // Only used by Continuous commands.
+    pub max_lines: usize,
+    // Only used by the others: how many lines of earlier runs' output to keep above the latest.
+    pub scrollback: Option<usize>,
+}
+
+/***
+Schedule: When a widget's command runs.
+    Every   -> Every `period` millis, measured from the start of the previous run. If `align`ed,
+               runs snap to wall-clock multiples of the period instead - so "1m" ticks on the minute.
+    Cron(s) -> Whenever the cron expression next matches, in local time.
+    Continuous -> All the time - a stream widget. Restarted shortly after it exits.
+ */
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Every { period: u64, align: bool },
+    Cron(Box<cron::Schedule>),
+    Continuous,
 }
 
 impl ExecutableCommand {
-    pub fn new(id: String, command: String, working_dir: String, period: String) -> ExecutableCommand {
+    pub fn new(id: String, command: CommandLine, working_dir: String, shell: Vec<String>, timeout: Option<u64>, schedule: Schedule) -> ExecutableCommand {
         ExecutableCommand {
             id,
             command,
             working_dir,
-            period: period.clone(),
-            time_between_runs: calc_time_between_runs(period.as_str()),
+            shell,
+            timeout,
+            schedule,
+            max_lines: DEFAULT_MAX_LINES,
+            scrollback: None,
         }
     }
 
-    pub fn millis_until_next_run(&self, elapsed: u64) -> u64 {
-        match elapsed > self.time_between_runs
-        {
-            true => 0,
-            false => self.time_between_runs - elapsed
+    /***
+     * The full argv to execute: either the shell plus our command line, or our argv as-is.
+     ***/
+    pub fn argv(&self) -> Vec<String> {
+        match &self.command {
+            CommandLine::Shell(line) => {
+                let mut argv = self.shell.clone();
+                argv.push(line.clone());
+                argv
+            }
+            CommandLine::Argv(argv) => argv.clone()
         }
+    }
+
+    /***
+     * A copy of this command with extra arguments tacked on the end.
+     * Shell commands get them verbatim - the shell will split them. Argv commands
+     * have them split shell-style first.
+     ***/
+    pub fn with_args(&self, args: &str) -> ExecutableCommand {
+        let mut cmd = self.clone();
+        if args.trim().is_empty() { return cmd; }
 
+        cmd.command = match &self.command {
+            CommandLine::Shell(line) => CommandLine::Shell(format!("{} {}", line, args)),
+            CommandLine::Argv(argv) => {
+                let extra = shell_words::split(args)
+                    .unwrap_or_else(|_| args.split_whitespace().map(|a| a.to_string()).collect());
+                CommandLine::Argv(argv.iter().cloned().chain(extra).collect())
+            }
+        };
+        cmd
     }
+
 }
 
-impl Clone for ExecutableCommand {
-    fn clone(&self) -> ExecutableCommand {
-        ExecutableCommand::new(
-            self.id.clone(),
-            self.command.clone(),
-            self.working_dir.clone(),
-            self.period.clone()
-        )
+impl Schedule {
+    pub fn every(period: &str, align: bool) -> Schedule {
+        Schedule::Every { period: calc_time_between_runs(period), align }
+    }
+
+    pub fn cron(expression: &str) -> Result<Schedule, String> {
+        parse_cron(expression).map(|s| Schedule::Cron(Box::new(s)))
+    }
+
+    /***
+     * How long to wait, from `now`, before running again given the last run started at `last_run`.
+     ***/
+    pub fn millis_until_next_run(&self, last_run: SystemTime, now: SystemTime) -> u64 {
+        let elapsed = millis_between(last_run, now);
+        match self {
+            Schedule::Every { period, align: false } => period.saturating_sub(elapsed),
+            Schedule::Every { period, align: true } => {
+                // Waking a touch early mustn't mean running twice for the same boundary.
+                let earliest = std::cmp::min(period / 2, 1000).saturating_sub(elapsed);
+                earliest + millis_to_boundary(now + Duration::from_millis(earliest), *period)
+            }
+            Schedule::Cron(schedule) => {
+                // Cron is only accurate to the second - don't match the second we just ran in again.
+                let earliest = std::cmp::max(now, last_run + Duration::from_secs(1));
+                schedule.after(&DateTime::<Local>::from(earliest)).next()
+                    .map(|next| millis_between(now, next.into()))
+                    .unwrap_or(NEVER_MILLIS)
+            }
+            Schedule::Continuous => RESTART_MILLIS,
+        }
+    }
+
+    /// The usual gap between runs. Failing widgets back off in multiples of this.
+    pub fn nominal_millis(&self, now: SystemTime) -> u64 {
+        match self {
+            Schedule::Every { period, .. } => *period,
+            Schedule::Cron(schedule) => {
+                let mut upcoming = schedule.after(&DateTime::<Local>::from(now));
+                match (upcoming.next(), upcoming.next()) {
+                    (Some(first), Some(second)) => (second - first).num_milliseconds().max(1000) as u64,
+                    _ => NEVER_MILLIS
+                }
+            }
+            Schedule::Continuous => RESTART_MILLIS,
+        }
     }
 }
 
+fn millis_between(earlier: SystemTime, later: SystemTime) -> u64 {
+    later.duration_since(earlier).unwrap_or_default().as_millis() as u64
+}
+
+/// Millis from `now` until the next local wall-clock multiple of `period`.
+fn millis_to_boundary(now: SystemTime, period: u64) -> u64 {
+    let utc_millis = now.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+    let offset_millis = Local.offset_from_utc_datetime(&DateTime::<Local>::from(now).naive_utc()).fix().local_minus_utc() as i64 * 1000;
+    let local_millis = utc_millis + offset_millis;
+
+    let period = period.max(1) as i64;
+    (period - local_millis.rem_euclid(period)) as u64
+}
+
 fn calc_time_between_runs(period: &str) -> u64 {
-    let matcher = Regex::new(r"(\d+)([smh]?)").unwrap();
+    match parse_period(period) {
+        Some(millis) => millis,
+        None => panic!("Couldn't calculate the time between runs from '{}'", period)
+    }
+}
+
+/***
+ * Parse a period like "10s", "5m", "1h30m" or "500ms" into milliseconds.
+ * A bare number is taken as seconds. Returns None if the period can't be understood, or is zero.
+ ***/
+pub fn parse_period(period: &str) -> Option<u64> {
+    let period = period.trim();
+    if let Ok(secs) = period.parse::<u64>() {
+        return secs.checked_mul(1000).filter(|millis| *millis > 0);
+    }
+
+    let matcher = Regex::new(r"(\d+)(ms|h|m|s)").unwrap();
+    let mut total: u64 = 0;
+    let mut parsed_up_to = 0;
 
     for c in matcher.captures_iter(period) {
-        // Should only be one, but whatever.
-        let time = &c[1].parse::<u64>().unwrap();
-        let unit = &c[2];
+        let term = c.get(0).unwrap();
+        if term.start() != parsed_up_to { return None; } // junk between terms
 
-        let mult = match unit {
+        let time = c[1].parse::<u64>().ok()?;
+        let mult = match &c[2] {
             "h" => 3600000,
             "m" => 60000,
-            _ => 1000 // default to milliseconds
+            "s" => 1000,
+            _ => 1 // milliseconds
         };
 
-        return time * mult;
+        total = total.checked_add(time.checked_mul(mult)?)?;
+        parsed_up_to = term.end();
+    }
+
+    match parsed_up_to == period.len() && total > 0 {
+        true => Some(total),
+        false => None
     }
+}
+
+/***
+ * Parse a cron expression. Takes the classic 5-field form ("30 9 * * Mon-Fri"), or the
+ * 6/7-field form with seconds (and years) that the cron crate understands. The 5-field form
+ * numbers the days of the week like crontab does, from 0 (or 7) for Sunday.
+ ***/
+pub fn parse_cron(expression: &str) -> Result<cron::Schedule, String> {
+    let fields = expression.split_whitespace().collect::<Vec<&str>>();
+    let expression = match fields.as_slice() {
+        [minute, hour, day, month, weekday] => format!("0 {} {} {} {} {}", minute, hour, day, month, crontab_weekdays(weekday)?),
+        _ => fields.join(" ")
+    };
 
-    panic!("Couldn't calculate the time between runs from '{}'", period);
+    cron::Schedule::from_str(&expression).map_err(|e| e.to_string())
+}
+
+/***
+ * A crontab day-of-week field (0-7, Sunday being both 0 and 7) in the cron crate's numbering
+ * (1-7 from Sunday). Numbers, ranges and steps become a list of days; names and `*` are left alone.
+ ***/
+fn crontab_weekdays(field: &str) -> Result<String, String> {
+    let day = |d: &str| match d.parse::<usize>() {
+        Ok(d) if d <= 7 => Ok(Some(d)),
+        Ok(d) => Err(format!("day of the week must be 0-7, not {}", d)),
+        Err(_) => Ok(None),
+    };
+
+    let mut items = Vec::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|s| *s > 0)),
+            None => (item, Some(1)),
+        };
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) => (day(first)?, day(last)?),
+            None if item.contains('/') => (day(range)?, Some(6)),
+            None => (day(range)?, day(range)?),
+        };
+
+        match (first, last, step) {
+            (Some(first), Some(last), Some(step)) if first <= last => {
+                let mut days = (first..=last).step_by(step).map(|d| d % 7 + 1).collect::<Vec<usize>>();
+                days.sort();
+                days.dedup();
+                items.extend(days.iter().map(|d| d.to_string()));
+            },
+            // Names, `*` and anything odd are for the cron crate to take or reject.
+            _ => items.push(item.to_string()),
+        }
+    }
+
+    Ok(items.join(","))
 }
 
 
@@ -66,6 +252,36 @@ fn calc_time_between_runs(period: &str) -> u64 {
 mod tests {
     use super::*;
 
+    fn shell_command(line: &str) -> ExecutableCommand {
+        ExecutableCommand::new("test".to_string(), CommandLine::Shell(line.to_string()), "/tmp".to_string(),
+                               vec!["/bin/sh".to_string(), "-c".to_string()], None, Schedule::every("1s", false))
+    }
+
+    fn argv_command(argv: &[&str]) -> ExecutableCommand {
+        ExecutableCommand::new("test".to_string(), CommandLine::Argv(argv.iter().map(|a| a.to_string()).collect()),
+                               "/tmp".to_string(), vec!["/bin/sh".to_string(), "-c".to_string()], None, Schedule::every("1s", false))
+    }
+
+    #[test]
+    fn shell_commands_run_through_the_shell() {
+        assert_eq!(shell_command("ls *.txt | head -n 1").argv(), vec!["/bin/sh", "-c", "ls *.txt | head -n 1"]);
+    }
+
+    #[test]
+    fn argv_commands_run_directly() {
+        assert_eq!(argv_command(&["ls", "my dir"]).argv(), vec!["ls", "my dir"]);
+    }
+
+    #[test]
+    fn extra_args_are_appended_to_shell_line() {
+        assert_eq!(shell_command("todo").with_args("add 'buy milk'").argv(), vec!["/bin/sh", "-c", "todo add 'buy milk'"]);
+    }
+
+    #[test]
+    fn extra_args_are_split_for_argv() {
+        assert_eq!(argv_command(&["todo"]).with_args("add 'buy milk'").argv(), vec!["todo", "add", "buy milk"]);
+    }
+
     #[test]
     fn time_between_runs_works_for_seconds() {
         assert_eq!(calc_time_between_runs("1s"), 1000);
@@ -92,4 +308,93 @@ mod tests {
         calc_time_between_runs("m");
     }
 
+    #[test]
+    fn parse_period_rejects_trailing_garbage() {
+        assert_eq!(parse_period("10x"), None);
+        assert_eq!(parse_period("ten seconds"), None);
+        assert_eq!(parse_period(" 10m "), Some(600000));
+    }
+
+    #[test]
+    fn time_between_runs_works_for_milliseconds() {
+        assert_eq!(calc_time_between_runs("500ms"), 500);
+    }
+
+    #[test]
+    fn time_between_runs_works_for_compound_durations() {
+        assert_eq!(calc_time_between_runs("1h30m"), 5400000);
+        assert_eq!(calc_time_between_runs("2m30s"), 150000);
+        assert_eq!(calc_time_between_runs("1s500ms"), 1500);
+    }
+
+    #[test]
+    fn parse_period_rejects_broken_compounds() {
+        assert_eq!(parse_period("1h 30m"), None);
+        assert_eq!(parse_period("1h30"), None);
+        assert_eq!(parse_period("0s"), None);
+        assert_eq!(parse_period(""), None);
+    }
+
+    fn at(secs: u64, millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn unaligned_schedule_measures_from_last_run() {
+        let schedule = Schedule::every("1m", false);
+        assert_eq!(schedule.millis_until_next_run(at(1000, 0), at(1005, 0)), 55000);
+        assert_eq!(schedule.millis_until_next_run(at(1000, 0), at(1100, 0)), 0);
+    }
+
+    #[test]
+    fn aligned_schedule_snaps_to_the_clock() {
+        // 1_000_000_020 seconds is exactly on a minute boundary.
+        let schedule = Schedule::every("1m", true);
+        assert_eq!(schedule.millis_until_next_run(at(1_000_000_040, 0), at(1_000_000_040, 250)), 39750);
+        assert_eq!(schedule.millis_until_next_run(at(1_000_000_075, 0), at(1_000_000_075, 0)), 5000);
+    }
+
+    #[test]
+    fn aligned_schedule_doesnt_repeat_a_boundary_when_woken_early() {
+        let schedule = Schedule::every("1m", true);
+        // Woke 2ms before the boundary, ran, and finished 1ms later - the next run is the minute after.
+        assert_eq!(schedule.millis_until_next_run(at(1_000_000_079, 998), at(1_000_000_079, 999)), 60001);
+    }
+
+    #[test]
+    fn cron_schedule_runs_at_next_match() {
+        let schedule = Schedule::cron("*/15 * * * * *").unwrap();
+        assert_eq!(schedule.millis_until_next_run(at(1_000_000_025, 0), at(1_000_000_025, 500)), 9500);
+        assert_eq!(schedule.nominal_millis(at(1_000_000_020, 0)), 15000);
+    }
+
+    #[test]
+    fn cron_accepts_five_field_expressions() {
+        let schedule = Schedule::cron("*/5 * * * *").unwrap();
+        assert_eq!(schedule.nominal_millis(at(1_000_000_020, 0)), 300000);
+    }
+
+    // The next `n` times `expression` fires after Saturday 6th January 2024, as (weekday, day of the month).
+    fn next_fires(expression: &str, n: usize) -> Vec<(chrono::Weekday, u32)> {
+        use chrono::{Datelike, Utc};
+        let saturday = Utc.with_ymd_and_hms(2024, 1, 6, 0, 0, 0).unwrap();
+        parse_cron(expression).unwrap().after(&saturday).take(n).map(|t| (t.weekday(), t.day())).collect()
+    }
+
+    #[test]
+    fn cron_numbers_weekdays_like_crontab() {
+        use chrono::Weekday::*;
+        assert_eq!(next_fires("0 9 * * 1-5", 6), vec![(Mon, 8), (Tue, 9), (Wed, 10), (Thu, 11), (Fri, 12), (Mon, 15)]);
+        assert_eq!(next_fires("0 9 * * 0", 2), vec![(Sun, 7), (Sun, 14)]);
+        assert_eq!(next_fires("0 9 * * 5-7", 4), vec![(Sat, 6), (Sun, 7), (Fri, 12), (Sat, 13)]);
+        assert_eq!(next_fires("0 9 * * 6,0", 3), vec![(Sat, 6), (Sun, 7), (Sat, 13)]);
+        assert_eq!(next_fires("0 9 * * Mon-Fri", 1), vec![(Mon, 8)]);
+        assert!(parse_cron("0 9 * * 8").is_err());
+    }
+
+    #[test]
+    fn cron_rejects_nonsense() {
+        assert!(parse_cron("every tuesday").is_err());
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::hexterm::TaskId;
+
+// After this many failures in a row we stop calling a widget "failing" and call it "dead".
+const DEAD_AFTER: u32 = 6;
+// However long the period, backoff never pushes a retry further out than this...
+const MAX_BACKOFF_MILLIS: u64 = 15 * 60 * 1000;
+
+/***
+Health: How a widget's command has been doing lately.
+    Ok         -> The last run succeeded.
+    Failing(n) -> The last n runs failed. Retries back off exponentially.
+    Dead       -> Failed so often we've given up on it coming back soon. Retried at the max backoff.
+ */
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Health {
+    Ok,
+    Failing(u32),
+    Dead,
+}
+
+impl Health {
+    pub fn from_failures(consecutive_failures: u32) -> Health {
+        match consecutive_failures {
+            0 => Health::Ok,
+            n if n >= DEAD_AFTER => Health::Dead,
+            n => Health::Failing(n),
+        }
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Health::Ok => write!(f, "ok"),
+            Health::Failing(n) => write!(f, "failing({})", n),
+            Health::Dead => write!(f, "dead"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TaskStatus {
+    pub consecutive_failures: u32,
+    pub last_run: Option<SystemTime>,
+    pub next_run: Option<SystemTime>,
+}
+
+impl TaskStatus {
+    pub fn health(&self) -> Health {
+        Health::from_failures(self.consecutive_failures)
+    }
+}
+
+/***
+StatusBoard: The latest TaskStatus of every widget, shared between the task loops and hex.
+ */
+#[derive(Clone, Default)]
+pub struct StatusBoard {
+    statuses: Arc<Mutex<HashMap<TaskId, TaskStatus>>>,
+}
+
+impl StatusBoard {
+    pub fn new() -> StatusBoard {
+        StatusBoard::default()
+    }
+
+    /// Record the outcome of a run. Returns the number of consecutive failures so far.
+    pub fn record_run(&self, task_id: &TaskId, succeeded: bool, ran_at: SystemTime) -> u32 {
+        let mut statuses = self.statuses.lock().unwrap();
+        let status = statuses.entry(task_id.clone()).or_insert(TaskStatus { consecutive_failures: 0, last_run: None, next_run: None });
+
+        status.consecutive_failures = if succeeded { 0 } else { status.consecutive_failures + 1 };
+        status.last_run = Some(ran_at);
+        status.consecutive_failures
+    }
+
+    pub fn schedule(&self, task_id: &TaskId, next_run: SystemTime) {
+        if let Some(status) = self.statuses.lock().unwrap().get_mut(task_id) {
+            status.next_run = Some(next_run);
+        }
+    }
+
+    pub fn forget(&self, task_id: &TaskId) {
+        self.statuses.lock().unwrap().remove(task_id);
+    }
+
+    /***
+     * A table of every widget's health, e.g.
+     *   widget      health      last run  next run
+     *   time        ok          1s ago    in 0s
+     ***/
+    pub fn report(&self, task_ids: &[TaskId], now: SystemTime) -> String {
+        let statuses = self.statuses.lock().unwrap();
+        let width = task_ids.iter().map(|id| id.len()).max().unwrap_or(0).max("widget".len());
+
+        let mut report = format!("{:width$}  {:11} {:9} {}", "widget", "health", "last run", "next run", width = width);
+        for task_id in task_ids {
+            let (health, last, next) = match statuses.get(task_id) {
+                Some(s) => (s.health().to_string(),
+                             s.last_run.map(|t| format!("{} ago", since(t, now))).unwrap_or_else(|| "never".to_string()),
+                             s.next_run.map(|t| format!("in {}", since(now, t))).unwrap_or_else(|| "-".to_string())),
+                None => ("pending".to_string(), "never".to_string(), "-".to_string()),
+            };
+            report += &format!("\n{:width$}  {:11} {:9} {}", task_id, health, last, next, width = width);
+        }
+
+        report
+    }
+}
+
+/***
+ * How long to wait before the next run, given the normal period and how many runs in a row have failed.
+ * Each failure doubles the wait, up to MAX_BACKOFF_MILLIS (or the period itself, if that's longer).
+ ***/
+pub fn backoff_millis(period_millis: u64, consecutive_failures: u32) -> u64 {
+    let cap = period_millis.max(MAX_BACKOFF_MILLIS);
+    let factor = 1u64.checked_shl(consecutive_failures).unwrap_or(u64::MAX);
+    period_millis.saturating_mul(factor).min(cap)
+}
+
+/// A rough, human-friendly "how long from `earlier` to `later`".
+fn since(earlier: SystemTime, later: SystemTime) -> String {
+    let secs = later.duration_since(earlier).unwrap_or(Duration::from_secs(0)).as_secs();
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s => format!("{}h", s / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_follows_consecutive_failures() {
+        assert_eq!(Health::from_failures(0), Health::Ok);
+        assert_eq!(Health::from_failures(2), Health::Failing(2));
+        assert_eq!(Health::from_failures(DEAD_AFTER), Health::Dead);
+    }
+
+    #[test]
+    fn backoff_doubles_with_each_failure() {
+        assert_eq!(backoff_millis(1000, 0), 1000);
+        assert_eq!(backoff_millis(1000, 1), 2000);
+        assert_eq!(backoff_millis(1000, 3), 8000);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(backoff_millis(1000, 40), MAX_BACKOFF_MILLIS);
+        assert_eq!(backoff_millis(1000, 200), MAX_BACKOFF_MILLIS);
+        // ...unless the period itself is longer than the cap.
+        assert_eq!(backoff_millis(3600000, 5), 3600000);
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let board = StatusBoard::new();
+        let id = "time".to_string();
+        let now = SystemTime::now();
+        assert_eq!(board.record_run(&id, false, now), 1);
+        assert_eq!(board.record_run(&id, false, now), 2);
+        assert_eq!(board.record_run(&id, true, now), 0);
+    }
+
+    #[test]
+    fn reports_every_widget() {
+        let board = StatusBoard::new();
+        let now = SystemTime::now();
+        board.record_run(&"time".to_string(), true, now - Duration::from_secs(2));
+        board.schedule(&"time".to_string(), now + Duration::from_secs(120));
+        board.record_run(&"weather".to_string(), false, now - Duration::from_secs(7200));
+
+        let report = board.report(&["time".to_string(), "weather".to_string(), "todo".to_string()], now);
+        assert_eq!(report, "widget   health      last run  next run\n\
+                            time     ok          2s ago    in 2m\n\
+                            weather  failing(1)  2h ago    -\n\
+                            todo     pending     never     -");
+    }
+}
@@ -0,0 +1,148 @@
+use crate::hexterm::TaskId;
+use crate::views::{Page, Scroll};
+
+/***
+PromptCommand: Something typed at the hex prompt.
+    Anything that isn't a built-in is treated as "<id> [args...]" - an app's id opens it, and a
+    widget's runs it as a one-off and shows it in the 'main' view until "back".
+    Open(app_id) -> Run one of the [[apps]] in place of the 'main' view.
+    Focus(id)    -> Go back to typing into the open app, or (given a widget's id) scroll that widget from now on.
+    Scroll(s)    -> Scroll the focused widget: "scroll up|down [lines]", "scroll top" or "scroll bottom".
+    Page(p)      -> Turn the stacks: "page next", "page prev" or "page <name or number>".
+    Show(t, v)   -> Show task t in the view showing v (a task id, or "main"): "show <task> [in <view>]".
+    Back         -> Give the 'main' view back to its own task, after an id's borrowed it.
+    Close        -> Quit the open app.
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PromptCommand {
+    Reload,
+    Status,
+    Open(TaskId),
+    Focus(TaskId),
+    Scroll(Option<Scroll>),
+    Page(Option<Page>),
+    Show(Option<(TaskId, TaskId)>),
+    Back,
+    Close,
+    RunTask(TaskId, String),
+}
+
+/// The prompt's own words - an id spelled like one of these could never be run or opened.
+pub const BUILTINS: [&str; 9] = ["reload", "status", "open", "focus", "scroll", "page", "show", "back", "close"];
+
+pub fn parse_command(input: &str) -> Option<PromptCommand> {
+    let mut parts = input.split_whitespace();
+    let first = parts.next()?;
+    let rest = parts.collect::<Vec<&str>>().join(" ");
+
+    match first {
+        "reload" => Some(PromptCommand::Reload),
+        "status" => Some(PromptCommand::Status),
+        "open" => Some(PromptCommand::Open(rest)),
+        "focus" => Some(PromptCommand::Focus(rest)),
+        "scroll" => Some(PromptCommand::Scroll(parse_scroll(&rest))),
+        "page" => Some(PromptCommand::Page(parse_page(&rest))),
+        "show" => Some(PromptCommand::Show(parse_show(&rest))),
+        "back" => Some(PromptCommand::Back),
+        "close" => Some(PromptCommand::Close),
+        task_id => Some(PromptCommand::RunTask(task_id.to_owned(), rest)),
+    }
+}
+
+// "up 3", "down", "top" or "bottom". None for anything else.
+fn parse_scroll(args: &str) -> Option<Scroll> {
+    let mut parts = args.split_whitespace();
+    let direction = parts.next()?;
+    let lines = match parts.next() {
+        Some(n) => n.parse::<isize>().ok()?,
+        None => 1,
+    };
+    if parts.next().is_some() { return None; }
+
+    match direction {
+        "up" => Some(Scroll::Lines(-lines)),
+        "down" => Some(Scroll::Lines(lines)),
+        "top" => Some(Scroll::Top),
+        "bottom" => Some(Scroll::Bottom),
+        _ => None,
+    }
+}
+
+// "<task>" or "<task> in <view>", where the view defaults to "main". None for anything else.
+fn parse_show(args: &str) -> Option<(TaskId, TaskId)> {
+    match args.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        [task_id] => Some((task_id.to_string(), "main".to_string())),
+        [task_id, "in", view] => Some((task_id.to_string(), view.to_string())),
+        _ => None,
+    }
+}
+
+// "next", "prev" or a page's name or number. None if there's nothing to go on.
+fn parse_page(args: &str) -> Option<Page> {
+    match args {
+        "" => None,
+        "next" => Some(Page::Next),
+        "prev" | "previous" => Some(Page::Previous),
+        name => Some(Page::Named(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_input_is_no_command() {
+        assert_eq!(parse_command("   "), None);
+    }
+
+    #[test]
+    fn parses_builtins() {
+        assert_eq!(parse_command("reload"), Some(PromptCommand::Reload));
+        assert_eq!(parse_command("status"), Some(PromptCommand::Status));
+        assert_eq!(parse_command("open notes"), Some(PromptCommand::Open("notes".to_string())));
+        assert_eq!(parse_command("focus"), Some(PromptCommand::Focus("".to_string())));
+        assert_eq!(parse_command("focus todo"), Some(PromptCommand::Focus("todo".to_string())));
+        assert_eq!(parse_command("close"), Some(PromptCommand::Close));
+    }
+
+    #[test]
+    fn parses_scrolling() {
+        assert_eq!(parse_command("scroll up 3"), Some(PromptCommand::Scroll(Some(Scroll::Lines(-3)))));
+        assert_eq!(parse_command("scroll down"), Some(PromptCommand::Scroll(Some(Scroll::Lines(1)))));
+        assert_eq!(parse_command("scroll bottom"), Some(PromptCommand::Scroll(Some(Scroll::Bottom))));
+        assert_eq!(parse_command("scroll sideways"), Some(PromptCommand::Scroll(None)));
+        assert_eq!(parse_command("scroll up lots"), Some(PromptCommand::Scroll(None)));
+    }
+
+    #[test]
+    fn parses_pages() {
+        assert_eq!(parse_command("page next"), Some(PromptCommand::Page(Some(Page::Next))));
+        assert_eq!(parse_command("page prev"), Some(PromptCommand::Page(Some(Page::Previous))));
+        assert_eq!(parse_command("page  System  Info"), Some(PromptCommand::Page(Some(Page::Named("System Info".to_string())))));
+        assert_eq!(parse_command("page"), Some(PromptCommand::Page(None)));
+    }
+
+    #[test]
+    fn parses_showing_tasks_in_views() {
+        let show = |task_id: &str, view: &str| Some(PromptCommand::Show(Some((task_id.to_string(), view.to_string()))));
+        assert_eq!(parse_command("show forecast_h in main"), show("forecast_h", "main"));
+        assert_eq!(parse_command("show forecast_h in cpu"), show("forecast_h", "cpu"));
+        assert_eq!(parse_command("show forecast_h"), show("forecast_h", "main"));
+        assert_eq!(parse_command("show"), Some(PromptCommand::Show(None)));
+        assert_eq!(parse_command("show forecast_h on main"), Some(PromptCommand::Show(None)));
+        assert_eq!(parse_command("back"), Some(PromptCommand::Back));
+    }
+
+    #[test]
+    fn every_builtin_is_a_builtin() {
+        for word in BUILTINS {
+            assert!(!matches!(parse_command(word), Some(PromptCommand::RunTask(..))), "{}", word);
+        }
+    }
+
+    #[test]
+    fn anything_else_runs_a_task() {
+        assert_eq!(parse_command(" todo add  milk "), Some(PromptCommand::RunTask("todo".to_string(), "add milk".to_string())));
+    }
+}
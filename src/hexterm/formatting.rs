@@ -1,8 +1,14 @@
-use std::cmp::min;
-use regex::{Match, Regex, Captures};
+use regex::{Match, Regex};
+use std::sync::OnceLock;
 use termion::cursor::Goto;
-use log::info;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 use crate::views::CharDims;
+use crate::views::screen::{parse_params, Style};
+
+const RESET: &str = "\u{1b}[0m";
+// Between the end of a marquee's text and its start coming round again.
+const MARQUEE_GAP: &str = "   ";
 
 /***
 TextFormatter: A trait for classes that convert from a raw string into a formatted one.
@@ -12,78 +18,232 @@ TextFormatter: A trait for classes that convert from a raw string into a formatt
 pub trait TextFormatter {
     fn format(&self, s: &str, dims: (usize, usize), location: (u16, u16)) -> String;
 }
-pub struct DumbFormatter{}
 
-impl TextFormatter for DumbFormatter {
-    fn format(&self, s: &str, dims: (usize, usize), _: (u16, u16)) -> String {
-        let last_len = min(dims.1, s.len());
-        s[0..last_len].to_string()
+/***
+ * How many columns a grapheme cluster takes on a terminal: 2 for East Asian wide characters
+ * and most emoji, 0 for control characters, and 1 for everything else. A cluster is as wide
+ * as its first character - accents and joiners ride along with it - and never wider than 2.
+ ***/
+pub fn grapheme_width(g: &str) -> usize {
+    g.chars().next().and_then(|c| c.width()).map_or(0, |w| w.min(2))
+}
+
+/// How many columns `s` takes on a terminal. Escape sequences take none.
+pub fn display_width(s: &str) -> usize {
+    let mut at = 0;
+    let mut width = 0;
+    for escape in find_vt100s(s) {
+        width += s[at..escape.start()].graphemes(true).map(grapheme_width).sum::<usize>();
+        at = escape.end();
+    }
+    width + s[at..].graphemes(true).map(grapheme_width).sum::<usize>()
+}
+
+/***
+ * The longest start of `s` that fits in `n` columns, and how many it takes. Only whole
+ * grapheme clusters are taken, so a wide character that would straddle the edge is left out.
+ ***/
+pub fn take_columns(s: &str, n: usize) -> (&str, usize) {
+    let mut end = 0;
+    let mut taken = 0;
+    for (i, g) in s.grapheme_indices(true) {
+        let width = grapheme_width(g);
+        if taken + width > n { break; }
+        taken += width;
+        end = i + g.len();
+    }
+    (&s[..end], taken)
+}
+
+/// The SGR attributes in effect after `s`, starting from `style`.
+pub fn sgr_state(s: &str, mut style: Style) -> Style {
+    static SGR: OnceLock<Regex> = OnceLock::new();
+    let sgr_regex = SGR.get_or_init(|| Regex::new(r"(?:\u001b\[|\u009b)([0-9;:]*)m").unwrap());
+    for sgr in sgr_regex.captures_iter(s) {
+        style.apply_sgr(&parse_params(&sgr[1]));
     }
+    style
+}
+
+/// What to send to pick up in `style` from a clean slate - nothing if it's the default.
+pub fn sgr_prefix(style: Style) -> String {
+    if style == Style::default() { String::new() } else { style.sgr() }
 }
 
 pub struct Vt100Formatter{}
 
-fn find_vt100s(s: &str) -> Vec<Match> {
-    let vt100_regex = Regex::new(r"((\u001b\[|\u009b)[\u0030-\u003f]*[\u0020-\u002f]*[\u0040-\u007e])+").unwrap();
+fn find_vt100s(s: &str) -> Vec<Match<'_>> {
+    static VT100: OnceLock<Regex> = OnceLock::new();
+    let vt100_regex = VT100.get_or_init(|| Regex::new(r"((\u001b\[|\u009b)[\u0030-\u003f]*[\u0020-\u002f]*[\u0040-\u007e])+").unwrap());
     vt100_regex.find_iter(s).collect()
 }
 
 impl TextFormatter for Vt100Formatter {
     fn format(&self, s: &str, dims: (usize, usize), location: (u16, u16)) -> String {
         let mut final_text = "".to_string();
+        let mut style = Style::default();
 
+        // Each line picks up the colours the last one left off with, and puts them down again
+        // before the padding, so nothing bleeds past the edge of the view.
         for (i, line) in s.split("\n").take(dims.1).enumerate() {
-            let (_, sliced) = Vt100Formatter::esc_aware_slice(line, dims.0);
-            final_text.push_str(format!("{}{:width$}", Goto(location.0, location.1 + i as u16), sliced, width = dims.0).as_str());
+            let (width, sliced) = Vt100Formatter::esc_aware_slice(line, dims.0);
+            let reset = if sgr_state(&sliced, style) == Style::default() { "" } else { RESET };
+            final_text.push_str(format!("{}{}{}{}{:pad$}", Goto(location.0, location.1 + i as u16), sgr_prefix(style), sliced, reset, "", pad = dims.0 - width).as_str());
+            style = sgr_state(line, style);
         };
 
-        return handle_clear(&final_text.as_str(), location, CharDims::new(dims.0, dims.1))
+        handle_clear(final_text.as_str(), location, CharDims::new(dims.0, dims.1))
     }
 }
 
 impl Vt100Formatter {
+    // The start of `s` that fits in `n` columns, and how many it takes.
     fn esc_aware_slice(s: &str, n: usize) -> (usize, String) {
-        if n >= s.len() { return (s.len(), format!("{:width$}", s, width = s.len())) }
-
-        let vt100s = find_vt100s(s);
-        if vt100s.last().is_none() { return (n, s[0..n].to_string()); }
-        let mut captured_chars = 0;
-        let mut end = 0;
-
-        for c in vt100s.iter() {
-            if (captured_chars + 1) < n {  // cc+1 to avoid subtraction with overflow
-                let next_block_of_text_size = c.start() - end;
-                let next_incr = if captured_chars + next_block_of_text_size >= n {
-                    (captured_chars + next_block_of_text_size) - n
-                } else {
-                    next_block_of_text_size
-                };
-
-                captured_chars += next_incr;
-                end = c.end();
+        let (end, taken) = split_columns(s, n);
+        (taken, s[..end].to_string())
+    }
+}
+
+/***
+ * Where to cut `s` so the part before fits in `n` columns: the byte index, and how many
+ * columns that part takes. Escape sequences come along for free until the columns run out.
+ ***/
+pub fn split_columns(s: &str, n: usize) -> (usize, usize) {
+    let escapes = find_vt100s(s);
+    let mut taken = 0;
+    let mut at = 0;
+    let mut end = 0;
+
+    for escape in escapes.iter().map(Some).chain(std::iter::once(None)) {
+        let text_end = escape.map_or(s.len(), |e| e.start());
+        let (text, width) = take_columns(&s[at..text_end], n - taken);
+        taken += width;
+        end = at + text.len();
+        if end < text_end { break; }
+
+        match escape {
+            Some(e) if taken < n => {
+                at = e.end();
+                end = at;
+            },
+            _ => break,
+        }
+    }
+
+    (end, taken)
+}
+
+/// `s` cut to `n` columns, ending in '…' if there was more.
+pub fn ellipsize(s: &str, n: usize) -> String {
+    if display_width(s) <= n { return s.to_string(); }
+    if n == 0 { return String::new(); }
+
+    let (end, _) = split_columns(s, n - 1);
+    format!("{}…", &s[..end])
+}
+
+/***
+ * `s` moved `offset` columns to the left, coming round again after a gap, when it's too wide
+ * for `n` columns - stepping `offset` along scrolls it like a marquee. Narrower text stays put.
+ ***/
+pub fn marquee(s: &str, n: usize, offset: usize) -> String {
+    let width = display_width(s);
+    if width <= n { return s.to_string(); }
+
+    let looped = format!("{}{}{}", s, MARQUEE_GAP, s);
+    let (start, _) = split_columns(&looped, offset % (width + MARQUEE_GAP.len()));
+    // Whatever colour the part scrolled off set still applies to what's left.
+    format!("{}{}", sgr_prefix(sgr_state(&looped[..start], Style::default())), &looped[start..])
+}
+
+/***
+ * Break `s` into lines no wider than `n` columns, between words where it can. A word too long
+ * for a line of its own starts a new one and is broken where it reaches the edge. Escape
+ * sequences take no room, and the spaces a line's broken at are dropped.
+ ***/
+pub fn wrap(s: &str, n: usize) -> Vec<String> {
+    if n == 0 { return vec![s.to_string()]; }
+
+    let mut lines = vec![String::new()];
+    let mut width = 0;
+    for (escape, piece) in wrap_pieces(s) {
+        let line = lines.last_mut().unwrap();
+        let piece_width = display_width(piece);
+        if escape {
+            line.push_str(piece);
+        } else if width + piece_width <= n {
+            line.push_str(piece);
+            width += piece_width;
+        } else if piece.starts_with(char::is_whitespace) {
+            break_line(&mut lines);
+            width = 0;
+        } else {
+            if width > 0 {
+                break_line(&mut lines);
+                width = 0;
             }
-        };
 
-        if captured_chars < n {
-            end += n - captured_chars; // grab any remaining characters we need
+            let mut word = piece;
+            loop {
+                let (part, taken) = take_columns(word, n - width);
+                // Too wide to ever fit (a wide character in a one-column view) - let it be cut off.
+                let part = if part.is_empty() && width == 0 { word.graphemes(true).next().unwrap_or("") } else { part };
+                lines.last_mut().unwrap().push_str(part);
+                width += taken;
+                word = &word[part.len()..];
+                if word.is_empty() { break; }
+
+                break_line(&mut lines);
+                width = 0;
+            }
         }
+    }
 
-        let slice_end = min(s.len(), end);
-        let sliced_str = s[0..slice_end].to_string();
+    lines
+}
 
-        (end, sliced_str)
+// Start a new line, leaving behind any spaces at the end of the last one.
+fn break_line(lines: &mut Vec<String>) {
+    if let Some(line) = lines.last_mut() {
+        line.truncate(line.trim_end().len());
     }
+    lines.push(String::new());
+}
+
+// `s` as escape sequences, and runs of either whitespace or not, with whether each is an escape sequence.
+fn wrap_pieces(s: &str) -> Vec<(bool, &str)> {
+    let mut pieces = Vec::new();
+    let mut at = 0;
+    for escape in find_vt100s(s).iter().map(Some).chain(std::iter::once(None)) {
+        let text_end = escape.map_or(s.len(), |e| e.start());
+        let text = &s[at..text_end];
+
+        let mut start = 0;
+        for (i, g) in text.grapheme_indices(true) {
+            if i > start && g.starts_with(char::is_whitespace) != text[start..].starts_with(char::is_whitespace) {
+                pieces.push((false, &text[start..i]));
+                start = i;
+            }
+        }
+        if start < text.len() { pieces.push((false, &text[start..])); }
+
+        if let Some(e) = escape {
+            pieces.push((true, e.as_str()));
+            at = e.end();
+        }
+    }
+
+    pieces
 }
 
 fn handle_clear(s: &str, location: (u16, u16), dims: CharDims) -> String {
-    // TODO: Move constants somewhere that makes sense
-    let CSI = r"\u{1B}\[";
-    let cls = Regex::new((CSI.to_string()+"2J").as_str()).unwrap();
+    static CLS: OnceLock<Regex> = OnceLock::new();
+    let cls = CLS.get_or_init(|| Regex::new(r"\u{1B}\[2J").unwrap());
 
-    cls.replace(s, clear_screen(s, location, dims).as_str() ).into()
+    cls.replace(s, clear_screen(location, dims).as_str() ).into()
 }
 
-fn clear_screen(s: &str, location: (u16, u16), dims: CharDims) -> String{
+fn clear_screen(location: (u16, u16), dims: CharDims) -> String{
     let mut clear = String::new();
     for y in 0..dims.height {
         // Go to each line in the window, then print a bunch of spaces to clear the region.
@@ -102,7 +262,7 @@ mod tests {
     fn slicing_vt100_string_works() {
         let fmt = Vt100Formatter{};
         let fmt_str = fmt.format(VT100_TEST, (2, 1), (1, 1));
-        assert_eq!("\u{1b}[1;1HT\u{1B}[33mE", fmt_str);
+        assert_eq!("\u{1b}[1;1HT\u{1B}[33mE\u{1b}[0m", fmt_str);
     }
 
     #[test]
@@ -117,4 +277,72 @@ mod tests {
         let fmt_str = fmt.format("\u{1B}[2JThis is new\nmultiline text", (2, 2), (1, 1));
         assert_eq!(fmt_str, "\u{1b}[1;1H\u{1b}[1;1H  \u{1b}[2;1H  Th\u{1b}[2;1Hmu")
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn measures_in_columns() {
+        assert_eq!(display_width("21°C"), 4);
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width("✅ done"), 7);
+        assert_eq!(display_width("e\u{301}"), 1);
+        assert_eq!(display_width(VT100_TEST), 4);
+    }
+
+    #[test]
+    fn takes_whole_graphemes_that_fit() {
+        assert_eq!(take_columns("21°C", 3), ("21°", 3));
+        assert_eq!(take_columns("日本", 3), ("日", 2));
+        assert_eq!(take_columns("e\u{301}x", 1), ("e\u{301}", 1));
+    }
+
+    #[test]
+    fn slicing_and_padding_multibyte_text_works() {
+        let fmt = Vt100Formatter{};
+        assert_eq!(fmt.format("\u{1B}[33m°C\u{1B}[39m", (4, 1), (1, 1)), "\u{1b}[1;1H\u{1B}[33m°C\u{1B}[39m  ");
+        assert_eq!(fmt.format("a日本", (4, 1), (1, 1)), "\u{1b}[1;1Ha日 ");
+    }
+
+    #[test]
+    fn colours_carry_over_to_the_next_line_but_not_past_the_edge() {
+        let fmt = Vt100Formatter{};
+        let fmt_str = fmt.format("ab\u{1B}[31mcd\nef\u{1B}[0mg\nh", (3, 3), (1, 1));
+        assert_eq!(fmt_str, "\u{1b}[1;1Hab\u{1B}[31mc\u{1b}[0m\u{1b}[2;1H\u{1b}[0;31mef\u{1B}[0mg\u{1b}[3;1Hh  ");
+    }
+
+    #[test]
+    fn tracks_sgr_state() {
+        let style = sgr_state("\u{1B}[1;31mbold red\u{1B}[22m", Style::default());
+        assert_eq!(style.fg, crate::views::screen::Color::Indexed(1));
+        assert!(!style.bold);
+        assert_eq!(sgr_state("x\u{1B}[m", style), Style::default());
+        assert_eq!(sgr_prefix(Style::default()), "");
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        assert_eq!(wrap("the quick brown fox", 10), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap("a verylongword", 5), vec!["a", "veryl", "ongwo", "rd"]);
+        assert_eq!(wrap("", 5), vec![""]);
+    }
+
+    #[test]
+    fn wrapping_ignores_escapes_and_counts_wide_characters() {
+        assert_eq!(wrap("\u{1B}[31mred\u{1B}[0m and blue", 7), vec!["\u{1B}[31mred\u{1B}[0m and", "blue"]);
+        assert_eq!(wrap("日本語テ", 5), vec!["日本", "語テ"]);
+    }
+
+    #[test]
+    fn ellipsizes_only_what_doesnt_fit() {
+        assert_eq!(ellipsize("weather", 7), "weather");
+        assert_eq!(ellipsize("weather", 5), "weat…");
+        assert_eq!(ellipsize("\u{1B}[33m21°C\u{1B}[39m", 3), "\u{1B}[33m21…");
+    }
+
+    #[test]
+    fn marquee_comes_round_again_after_a_gap() {
+        assert_eq!(marquee("short", 5, 3), "short");
+        assert_eq!(marquee("abcdef", 4, 2), "cdef   abcdef");
+        assert_eq!(marquee("abcdef", 4, 6), "   abcdef");
+        assert_eq!(marquee("abcdef", 4, 9), "abcdef   abcdef");
+        assert_eq!(marquee("\u{1B}[31mabcdef", 4, 1), "\u{1b}[0;31mbcdef   \u{1B}[31mabcdef");
+    }
+}
@@ -0,0 +1,140 @@
+use termion::event::Key;
+
+/***
+ * Read a key chord from the config, e.g. "ctrl-]", "alt-x", "f12", "esc" or "q".
+ * Modifiers and names aren't case sensitive, but a lone character is.
+ ***/
+pub fn parse_key(chord: &str) -> Result<Key, String> {
+    let lower = chord.to_lowercase();
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    if let Some(rest) = lower.strip_prefix("ctrl-") {
+        return match single(rest) {
+            Some(c @ 'a'..='z') => Ok(Key::Ctrl(c)),
+            // termion reports Ctrl-\, Ctrl-], Ctrl-^ and Ctrl-_ as Ctrl-4 to Ctrl-7.
+            Some('\\') => Ok(Key::Ctrl('4')),
+            Some(']') => Ok(Key::Ctrl('5')),
+            Some('^') => Ok(Key::Ctrl('6')),
+            Some('_') => Ok(Key::Ctrl('7')),
+            _ if rest == "space" => Ok(Key::Null),
+            _ => Err(format!("can't use '{}' - Ctrl only goes with a letter or one of \\ ] ^ _ space", chord)),
+        };
+    }
+
+    if let Some(rest) = chord.get(4..).filter(|_| lower.starts_with("alt-")) {
+        return single(rest).map(Key::Alt).ok_or_else(|| format!("can't use '{}' - Alt only goes with a single character", chord));
+    }
+
+    match lower.as_str() {
+        "esc" | "escape" => Ok(Key::Esc),
+        "tab" => Ok(Key::Char('\t')),
+        "backtab" => Ok(Key::BackTab),
+        f if f.starts_with('f') && f[1..].parse::<u8>().is_ok_and(|n| (1..=12).contains(&n)) => Ok(Key::F(f[1..].parse().unwrap())),
+        _ => single(chord).map(Key::Char).ok_or_else(|| format!("unknown key '{}' - expected e.g. \"ctrl-]\", \"alt-x\" or \"f12\"", chord)),
+    }
+}
+
+/// How a key's written in the config, and on the prompt line.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Ctrl('4') => "Ctrl-\\".to_string(),
+        Key::Ctrl('5') => "Ctrl-]".to_string(),
+        Key::Ctrl('6') => "Ctrl-^".to_string(),
+        Key::Ctrl('7') => "Ctrl-_".to_string(),
+        Key::Ctrl(c) => format!("Ctrl-{}", c),
+        Key::Null => "Ctrl-Space".to_string(),
+        Key::Alt(c) => format!("Alt-{}", c),
+        Key::Esc => "Esc".to_string(),
+        Key::Char('\t') => "Tab".to_string(),
+        Key::BackTab => "BackTab".to_string(),
+        Key::F(n) => format!("F{}", n),
+        Key::Char(c) => c.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/***
+ * The bytes a terminal would send for a key, so it can be passed on to an app.
+ * Keys termion can't tell us enough about to reproduce come back empty.
+ ***/
+pub fn key_bytes(key: Key) -> Vec<u8> {
+    match key {
+        // termion hands us Enter as '\n', but terminals send a carriage return.
+        Key::Char('\n') => b"\r".to_vec(),
+        Key::Char(c) => c.to_string().into_bytes(),
+        Key::Ctrl(c @ 'a'..='z') => vec![c as u8 - b'a' + 1],
+        // Ctrl-\, Ctrl-], Ctrl-^ and Ctrl-_ come through as '4' to '7'.
+        Key::Ctrl(c @ '4'..='7') => vec![c as u8 - b'4' + 0x1C],
+        Key::Alt(c) => format!("\u{1b}{}", c).into_bytes(),
+        Key::Backspace => vec![0x7f],
+        Key::Esc => vec![0x1b],
+        Key::Null => vec![0],
+        Key::Up => b"\x1b[A".to_vec(),
+        Key::Down => b"\x1b[B".to_vec(),
+        Key::Right => b"\x1b[C".to_vec(),
+        Key::Left => b"\x1b[D".to_vec(),
+        Key::Home => b"\x1b[H".to_vec(),
+        Key::End => b"\x1b[F".to_vec(),
+        Key::BackTab => b"\x1b[Z".to_vec(),
+        Key::Insert => b"\x1b[2~".to_vec(),
+        Key::Delete => b"\x1b[3~".to_vec(),
+        Key::PageUp => b"\x1b[5~".to_vec(),
+        Key::PageDown => b"\x1b[6~".to_vec(),
+        Key::F(n @ 1..=4) => vec![0x1b, b'O', b'P' + n - 1],
+        Key::F(n) => match n {
+            5 => b"\x1b[15~".to_vec(),
+            6 => b"\x1b[17~".to_vec(),
+            7 => b"\x1b[18~".to_vec(),
+            8 => b"\x1b[19~".to_vec(),
+            9 => b"\x1b[20~".to_vec(),
+            10 => b"\x1b[21~".to_vec(),
+            11 => b"\x1b[23~".to_vec(),
+            12 => b"\x1b[24~".to_vec(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_become_terminal_input() {
+        assert_eq!(key_bytes(Key::Char('x')), b"x");
+        assert_eq!(key_bytes(Key::Char('\n')), b"\r");
+        assert_eq!(key_bytes(Key::Ctrl('c')), vec![3]);
+        assert_eq!(key_bytes(Key::Ctrl('5')), vec![0x1D]);
+        assert_eq!(key_bytes(Key::Up), b"\x1b[A");
+        assert_eq!(key_bytes(Key::F(2)), b"\x1bOQ");
+        assert_eq!(key_bytes(Key::F(10)), b"\x1b[21~");
+    }
+
+    #[test]
+    fn parses_key_chords() {
+        assert_eq!(parse_key("ctrl-]"), Ok(Key::Ctrl('5')));
+        assert_eq!(parse_key("Ctrl-A"), Ok(Key::Ctrl('a')));
+        assert_eq!(parse_key("alt-X"), Ok(Key::Alt('X')));
+        assert_eq!(parse_key("F12"), Ok(Key::F(12)));
+        assert_eq!(parse_key("esc"), Ok(Key::Esc));
+        assert_eq!(parse_key("q"), Ok(Key::Char('q')));
+
+        assert!(parse_key("ctrl-1").is_err());
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("hyper-q").is_err());
+    }
+
+    #[test]
+    fn key_names_read_back_as_the_same_key() {
+        for chord in ["Ctrl-]", "Ctrl-a", "Alt-x", "F5", "Esc", "Tab", "q"] {
+            assert_eq!(key_name(parse_key(chord).unwrap()), chord);
+        }
+    }
+}
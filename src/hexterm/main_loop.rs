@@ -0,0 +1,458 @@
+use crate::app_session::AppSession;
+use crate::control_socket::ControlSocket;
+use crate::hexterm::{Event, HexTerm, InputMode, TaskId};
+use crate::hexterm::keys::{key_bytes, key_name};
+use crate::task_result::TaskResult;
+use std::collections::HashMap;
+use crate::hexterm::commands::{parse_command, PromptCommand};
+use crate::hexterm::reload::{changed_widgets, watch_config, ReloadFlag};
+use crate::tasks::{Config, load_task_config};
+use crate::runner::WidgetUpdater;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender, TryRecvError};
+// use portable_pty::{CommandBuilder, native_pty_system, PtySize};
+use log::{error, info, warn};
+use crate::terminal::Terminal;
+use crate::views::{Page, Scroll};
+use std::io::{stdin, stdout, Write};
+use termion::input::TermRead;
+use std::sync::mpsc::Receiver;
+use termion::event::Key;
+use std::thread;
+use std::time::Instant;
+use termion::clear;
+use signal_hook::consts::SIGWINCH;
+use signal_hook::iterator::Signals;
+
+impl HexTerm {
+    pub fn new(config: Config, config_path: PathBuf, socket_path: PathBuf) -> HexTerm {
+        // Everything the main loop waits for comes through here.
+        let (events_tx, events) = mpsc::channel();
+
+        // Create channel for widgets/apps to send output back to Hex
+        let (output_tx, output_rx) = mpsc::channel();
+        let (remote_command_tx, remote_command_rx) = mpsc::channel();
+        forward(output_rx, events_tx.clone(), Event::Output);
+        forward(remote_command_rx, events_tx.clone(), Event::Command);
+
+        let control_socket = listen(&socket_path, &config, output_tx.clone(), remote_command_tx);
+        let widget_runner = WidgetUpdater::new(config.widgets.clone(), output_tx);
+        let terminal = Terminal::new(&config.layout, &config.widgets, config.terminal.min_size());
+        let command = "".to_owned();
+        let message = "".to_owned();
+
+        HexTerm { widget_runner, terminal, events, events_tx, command, message, config, config_path, reload_requested: ReloadFlag::default(), control_socket, app: None, mode: InputMode::Command, running: false }
+    }
+
+    pub fn run(&mut self) {
+        // TODO: InteractiveRunners take a child and a master. When active, Input is directed to their master.
+        // TODO: InteractiveRunners direct their output to their Widget on update.
+
+        self.run_input_loop();
+        watch_resizes(self.events_tx.clone());
+        self.running = true;
+        self.widget_runner.start();
+        watch_config(&self.config_path, self.reload_requested.clone(), self.events_tx.clone());
+
+        // Empty the screen!
+        println!("{}{}", termion::cursor::Hide, clear::All);
+        self.print_prompt();
+
+        // Sleep until something happens, deal with it, and draw whatever that changed.
+        while self.running {
+            match self.next_event() {
+                Event::Output(out) => self.terminal.update(out),
+                Event::Key(key) => self.process_input(key),
+                Event::Command(input) => self.run_prompt_command(&input),
+                Event::Resize => self.handle_resize(),
+                Event::AppOutput => self.update_app(),
+                Event::Reload => {}, // Below, once there's no app open
+                Event::Timer => self.terminal.tick(),
+            }
+            // Reloading redraws the whole screen, so it waits until any open app's closed.
+            if self.reload_requested.take_when(self.app.is_none()) {
+                self.reload();
+            }
+            self.print_prompt();
+            stdout().flush().unwrap();
+        }
+
+        self.app = None;
+        self.widget_runner.shutdown();
+        println!("{}So long!{}", clear::All, termion::cursor::Show);
+    }
+
+    fn print_prompt(&mut self) {
+        let prompt = match (&self.app, self.mode) {
+            (Some(app), InputMode::Passthrough) => format!("{}-- {} --{} {} for the prompt  {}",
+                                                           termion::color::Green.fg_str(),
+                                                           app.id,
+                                                           termion::style::Reset,
+                                                           key_name(self.config.keys.escape_key()),
+                                                           self.message),
+            (app, _) => {
+                // Remind whoever's at the prompt that an app's still running in the background.
+                let hint = match app {
+                    Some(app) if self.message.is_empty() => format!("{} is open - 'focus' to type into it, 'close' to quit it", app.id),
+                    _ => self.message.clone(),
+                };
+                format!("{}> {}_{}  {}",
+                        termion::color::Green.fg_str(),
+                        self.command,
+                        termion::style::Reset,
+                        hint)
+            },
+        };
+        self.terminal.set_prompt(&prompt);
+    }
+
+    /// Wait for the next event - or, if a stack's due to turn its page before there is one, until then.
+    fn next_event(&mut self) -> Event {
+        let event = match self.terminal.next_turn() {
+            Some(at) => self.events.recv_timeout(at.saturating_duration_since(Instant::now())).ok(),
+            None => self.events.recv().ok(),
+        };
+        // We hold a sender ourselves, so the only way to get nothing is to time out.
+        event.unwrap_or(Event::Timer)
+    }
+
+    fn process_input(&mut self, key: Key) {
+        if self.mode == InputMode::Passthrough {
+            self.send_to_app(key);
+            return;
+        }
+
+        self.message.clear();
+        match key {
+            _ if key == self.config.keys.next_page_key() => self.turn_page(&Page::Next),
+            _ if key == self.config.keys.prev_page_key() => self.turn_page(&Page::Previous),
+            // TODO: Add more interesting key combo support
+            Key::Backspace => {
+                self.command.pop();
+            }
+            Key::Char(c) => {
+                if c == '\n' {
+                    self.execute_command();
+                } else {
+                    self.command.push(c);
+                }
+            }
+            Key::Up => self.terminal.scroll(Scroll::Lines(-1)),
+            Key::Down => self.terminal.scroll(Scroll::Lines(1)),
+            Key::PageUp => self.terminal.scroll(Scroll::Pages(-1)),
+            Key::PageDown => self.terminal.scroll(Scroll::Pages(1)),
+            Key::Home => self.terminal.scroll(Scroll::Top),
+            Key::End => self.terminal.scroll(Scroll::Bottom),
+            Key::Esc if self.terminal.main_owner().is_some() => self.restore_main(),
+            Key::Ctrl('c') => self.running = false,
+            _ => {} // No other keys do anything
+        }
+    }
+
+    pub fn execute_command(&mut self) {
+        let input = self.command.clone();
+        self.run_prompt_command(&input);
+        self.command.truncate(0);
+    }
+
+    fn run_prompt_command(&mut self, input: &str) {
+        match parse_command(input) {
+            Some(PromptCommand::Reload) => self.request_reload(),
+            Some(PromptCommand::Status) => if let Err(e) = self.terminal.show_in_main(&self.widget_runner.status_report()) {
+                self.message = e;
+            },
+            Some(PromptCommand::Open(app_id)) => self.open_app(&app_id),
+            Some(PromptCommand::Focus(id)) => self.focus(&id),
+            Some(PromptCommand::Scroll(Some(scroll))) => self.terminal.scroll(scroll),
+            Some(PromptCommand::Scroll(None)) => self.message = "scroll up|down [lines], scroll top or scroll bottom".to_owned(),
+            Some(PromptCommand::Page(Some(page))) => self.turn_page(&page),
+            Some(PromptCommand::Page(None)) => self.message = "page next, page prev or page <name or number>".to_owned(),
+            Some(PromptCommand::Show(Some((task_id, view)))) => match self.terminal.assign_task_to_view(&task_id, &view) {
+                Ok(()) => self.message = format!("showing {} in {}", task_id, view),
+                Err(e) => self.message = e,
+            },
+            Some(PromptCommand::Show(None)) => self.message = "show <widget id> [in <widget id or main>]".to_owned(),
+            Some(PromptCommand::Back) => self.restore_main(),
+            Some(PromptCommand::Close) => match &self.app {
+                Some(app) => {
+                    let message = format!("closed {}", app.id);
+                    self.close_app(&message);
+                },
+                None => self.message = "no app is open".to_owned(),
+            },
+            Some(PromptCommand::RunTask(id, _)) if self.config.apps.iter().any(|app| app.id == id) => self.open_app(&id),
+            Some(PromptCommand::RunTask(task_id, args)) => self.run_task(task_id, args),
+            None => {}
+        }
+    }
+
+    /***
+     * Start one of the [[apps]] in the 'main' view's space, and switch to passthrough mode
+     * so keystrokes go to it. Opening the app that's already open just switches back to it.
+     ***/
+    fn open_app(&mut self, app_id: &str) {
+        if let Some(app) = &self.app {
+            if app.id == app_id || app_id.is_empty() {
+                self.focus_app();
+            } else {
+                self.message = format!("{} is already open - close it first", app.id);
+            }
+            return;
+        }
+
+        let app = match self.config.apps.iter().find(|app| app.id == app_id) {
+            Some(app) => app.clone(),
+            None if app_id.is_empty() => { self.message = "open which app?".to_owned(); return; }
+            None => { self.message = format!("no app called '{}'", app_id); return; }
+        };
+
+        let size = match self.terminal.open_pane() {
+            Some(size) => size,
+            None => { self.message = "no room to open it - is there a 'main' view?".to_owned(); return; }
+        };
+
+        let events_tx = self.events_tx.clone();
+        match AppSession::start(&app, size, move || { let _ = events_tx.send(Event::AppOutput); }) {
+            Ok(session) => {
+                self.app = Some(session);
+                self.mode = InputMode::Passthrough;
+            },
+            Err(e) => {
+                error!("Couldn't open {}: {}", app.id, e);
+                self.terminal.close_pane();
+                self.message = format!("couldn't open {}: {}", app.id, e);
+            }
+        }
+    }
+
+    /// Draw whatever the open app's printed, and put things back once it's exited.
+    fn update_app(&mut self) {
+        let app = match self.app.as_mut() {
+            Some(app) => app,
+            None => return,
+        };
+
+        let mut output = String::new();
+        let hung_up = loop {
+            match app.output.try_recv() {
+                Ok(chunk) => output.push_str(&chunk),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        if !output.is_empty() {
+            let replies = self.terminal.show_in_pane(output);
+            if !replies.is_empty() {
+                if let Err(e) = app.send(replies.as_bytes()) {
+                    warn!("Couldn't answer {}: {}", app.id, e);
+                }
+            }
+        }
+
+        // Its pty closes once it's exited - though it mightn't have quite been reaped by now.
+        if hung_up || app.exited() {
+            let message = format!("{} exited", app.id);
+            info!("{}", message);
+            self.close_app(&message);
+        }
+    }
+
+    /// Redraw for a new terminal size, and let any open app know its own size has changed.
+    fn handle_resize(&mut self) {
+        let size = match self.terminal.handle_resize() {
+            Some(size) => size,
+            None => return,
+        };
+        if let Some(app) = self.app.as_mut() {
+            if let Err(e) = app.resize(size) {
+                warn!("Couldn't resize {}: {}", app.id, e);
+            }
+        }
+    }
+
+    /// Switch to the open app, or if `id` is a widget's, make that the widget that scrolls.
+    fn focus(&mut self, id: &str) {
+        if id.is_empty() || self.app.as_ref().is_some_and(|app| app.id == id) {
+            self.focus_app();
+        } else if self.terminal.focus(&id.to_owned()) {
+            self.message = format!("scrolling {} - Up/Down, PageUp/PageDown, Home/End", id);
+        } else {
+            self.message = format!("nothing on screen shows '{}'", id);
+        }
+    }
+
+    /// Run a widget's command once, and show it in the 'main' view until `back`.
+    fn run_task(&mut self, task_id: TaskId, args: String) {
+        if !self.config.widgets.iter().any(|w| w.id == task_id) {
+            self.message = format!("no widget or app called '{}'", task_id);
+            return;
+        }
+
+        self.widget_runner.run_command(task_id.clone(), args);
+        self.message = match self.terminal.borrow_main(&task_id) {
+            Ok(()) => match self.terminal.main_owner() {
+                Some(owner) => format!("showing {} - 'back' (or Esc) for {}", task_id, owner),
+                None => String::new(),
+            },
+            Err(e) => e,
+        };
+    }
+
+    fn restore_main(&mut self) {
+        self.message = match self.terminal.restore_main() {
+            Some(task_id) => format!("back to {}", task_id),
+            None => "the main view's showing its own task".to_owned(),
+        };
+    }
+
+    fn turn_page(&mut self, page: &Page) {
+        if self.terminal.turn_page(page) { return; }
+        self.message = match (&self.app, page) {
+            (Some(app), _) => format!("{} is open - close it to turn the page", app.id),
+            (None, Page::Named(name)) => format!("no page called '{}'", name),
+            (None, _) => "there's no stack to turn".to_owned(),
+        };
+    }
+
+    fn focus_app(&mut self) {
+        match self.app {
+            Some(_) => self.mode = InputMode::Passthrough,
+            None => self.message = "no app is open".to_owned(),
+        }
+    }
+
+    fn send_to_app(&mut self, key: Key) {
+        if key == self.config.keys.escape_key() {
+            self.mode = InputMode::Command;
+            return;
+        }
+
+        if let Some(app) = self.app.as_mut() {
+            if let Err(e) = app.send(&key_bytes(key)) {
+                error!("Couldn't send input to {}: {}", app.id, e);
+            }
+        }
+    }
+
+    /// Close the open app (killing it if it's still running) and give the main view back.
+    fn close_app(&mut self, message: &str) {
+        self.app = None;
+        self.mode = InputMode::Command;
+        self.terminal.close_pane();
+        self.message = message.to_owned();
+    }
+
+    /***
+     * Re-read the config file and apply it. Only widgets whose definition changed are restarted;
+     * the rest keep running and keep their last output on screen. A config that fails
+     * validation is rejected and the current one stays in place.
+     ***/
+    // Reloaded by the loop, as soon as there's no app open.
+    fn request_reload(&mut self) {
+        self.reload_requested.raise();
+        if let Some(app) = &self.app {
+            self.message = format!("reloading once {} is closed", app.id);
+        }
+    }
+
+    pub fn reload(&mut self) {
+        info!("Reloading config from {}", self.config_path.display());
+        let new_config = match load_task_config(&self.config_path) {
+            Ok(config) => config,
+            Err(errors) => {
+                errors.iter().for_each(|e| error!("Rejected config: {}", e));
+                self.message = format!("reload failed: {} problem(s), see log", errors.len());
+                return;
+            }
+        };
+
+        if new_config == self.config {
+            self.message = "config unchanged".to_owned();
+            return;
+        }
+
+        let changed = changed_widgets(&self.config.widgets, &new_config.widgets);
+        changed.iter().for_each(|task_id| self.terminal.forget(task_id));
+        self.widget_runner.reconfigure(&new_config.widgets, &changed);
+        self.terminal.relayout(&new_config.layout, &new_config.widgets, new_config.terminal.min_size());
+        if let Some(socket) = &self.control_socket {
+            socket.set_widgets(&new_config.widgets);
+        }
+
+        self.message = format!("reloaded, restarted {} widget(s)", changed.len());
+        self.config = new_config;
+    }
+
+    pub fn run_input_loop(&mut self) {
+        let tx = self.events_tx.clone();
+
+        // Kick off the input handler
+        thread::spawn( move || {
+            for k in stdin().keys() {
+                match k {
+                    Ok(key) => { if tx.send(Event::Key(key)).is_err() { break; } },
+                    Err(e) => { error!("Error reading keys: {}", e); }
+                }
+            }
+        });
+    }
+
+    // fn start_pty(&mut self, command: &str) {
+    //     // Launch a PTY session
+    //     let pty_system = native_pty_system();
+    //     let mut pair = pty_system.openpty(PtySize {
+    //         rows: 24,
+    //         cols: 80,
+    //         // Not all systems support pixel_width, pixel_height,
+    //         // but it is good practice to set it to something
+    //         // that matches the size of the selected font.  That
+    //         // is more complex than can be shown here in this
+    //         // brief example though!
+    //         pixel_width: 0,
+    //         pixel_height: 0,
+    //     }).unwrap();
+    //
+    //     let cmd = CommandBuilder::new(command);
+    //     let child = pair.slave.spawn_command(cmd).unwrap();
+    //
+    //     let mut reader = pair.master.try_clone_reader().unwrap();
+    //
+    //     // Send data to the pty by writing to the master
+    //     writeln!(pair.master, "ls -l\r\n").unwrap();
+    // }
+
+}
+
+/// Pass everything that arrives on `rx` on to the main loop, as an `event`.
+fn forward<T: Send + 'static>(rx: Receiver<T>, events_tx: Sender<Event>, event: fn(T) -> Event) {
+    thread::spawn(move || {
+        for item in rx {
+            if events_tx.send(event(item)).is_err() { break; }
+        }
+    });
+}
+
+/// Let the main loop know whenever the terminal's resized.
+fn watch_resizes(events_tx: Sender<Event>) {
+    match Signals::new([SIGWINCH]) {
+        Ok(mut signals) => {
+            thread::spawn(move || {
+                for _ in signals.forever() {
+                    if events_tx.send(Event::Resize).is_err() { break; }
+                }
+            });
+        },
+        Err(e) => error!("Couldn't listen for SIGWINCH: {}", e),
+    }
+}
+
+/// Open the control socket. Hex runs fine without one, so failing to is only logged.
+fn listen(path: &Path, config: &Config, output_tx: mpsc::Sender<HashMap<TaskId, TaskResult>>, command_tx: mpsc::Sender<String>) -> Option<ControlSocket> {
+    match ControlSocket::new(path, &config.widgets, output_tx, command_tx) {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            error!("Couldn't listen on {}: {}", path.display(), e);
+            None
+        }
+    }
+}
@@ -1,17 +1,71 @@
 use std::collections::HashMap;
-use std::sync::mpsc::Receiver;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
 
+use termion::event::Key;
+
+use crate::app_session::AppSession;
+use crate::control_socket::ControlSocket;
+use crate::hexterm::reload::ReloadFlag;
 use crate::runner::WidgetUpdater;
+use crate::task_result::TaskResult;
+use crate::tasks::Config;
 use crate::terminal::Terminal;
 
-mod hexterm;
+mod main_loop;
+pub(crate) mod commands;
+mod reload;
+pub(crate) mod keys;
 pub(crate) mod formatting;
 
 pub type TaskId = String;
+
+/***
+InputMode: Where keystrokes go.
+    Command     -> They edit the prompt.
+    Passthrough -> They're sent on to the open app, until the escape key ([keys] escape) is pressed.
+ */
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InputMode {
+    Command,
+    Passthrough,
+}
+
+/***
+Event: Something for the main loop to deal with. Every source feeds one queue, and the loop
+       sleeps until there's something on it.
+    Output(o)  -> New output from widgets, or pushed over the control socket.
+    Key(k)     -> A keypress.
+    Command(c) -> A prompt command sent over the control socket.
+    Resize     -> The terminal's changed size (SIGWINCH).
+    AppOutput  -> The open app's printed something, or exited. What it printed waits in the AppSession.
+    Reload     -> The config file's changed, or hex got SIGHUP.
+    Timer      -> Something the loop was waiting for is due, like a stack turning its page or a marquee moving.
+ */
+pub enum Event {
+    Output(HashMap<TaskId, TaskResult>),
+    Key(Key),
+    Command(String),
+    Resize,
+    AppOutput,
+    Reload,
+    Timer,
+}
+
 pub struct HexTerm {
     pub running: bool,
     widget_runner: WidgetUpdater,
     terminal: Terminal,
-    output_rx: Receiver<HashMap<TaskId, String>>,
+    events: Receiver<Event>,
+    // Handed to anything that needs to wake the main loop.
+    events_tx: Sender<Event>,
     command: String,
+    message: String,
+    config: Config,
+    config_path: PathBuf,
+    reload_requested: ReloadFlag,
+    control_socket: Option<ControlSocket>,
+    // The app open in the main view, if any.
+    app: Option<AppSession>,
+    mode: InputMode,
 }
\ No newline at end of file
@@ -0,0 +1,202 @@
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use log::{error, info};
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
+
+use crate::hexterm::{Event, TaskId};
+use crate::tasks::Widget;
+
+/***
+ReloadFlag: Whether the config's been asked to reload, and hasn't been yet.
+    Raised by `reload` at the prompt, on SIGHUP, or when the config file changes. The main loop
+    takes it down when it's ready - reloading redraws the whole screen, so not while an app's open.
+ */
+#[derive(Clone, Default)]
+pub struct ReloadFlag {
+    raised: Arc<AtomicBool>,
+}
+
+impl ReloadFlag {
+    pub fn raise(&self) {
+        self.raised.store(true, Ordering::Relaxed);
+    }
+
+    /// True (once) if a reload's been asked for and we're `ready` - otherwise it's kept for later.
+    pub fn take_when(&self, ready: bool) -> bool {
+        ready && self.raised.swap(false, Ordering::Relaxed)
+    }
+}
+
+/***
+ * Raise `flag` on SIGHUP, or when the config file at `path` changes, waking the main loop
+ * with Event::Reload.
+ ***/
+pub fn watch_config(path: &Path, flag: ReloadFlag, wake: Sender<Event>) {
+    match Signals::new([SIGHUP]) {
+        Ok(mut signals) => {
+            let (flag, wake) = (flag.clone(), wake.clone());
+            thread::Builder::new().name("sighup".to_string()).spawn(move || {
+                for _ in signals.forever() {
+                    info!("Got SIGHUP");
+                    request(&flag, &wake);
+                }
+            }).unwrap();
+        },
+        Err(e) => error!("Couldn't listen for SIGHUP: {}", e),
+    }
+
+    // Follow a symlinked config to the file that actually gets edited.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    match watch_dir_of(&path) {
+        Ok(inotify) => {
+            thread::Builder::new().name("config-watcher".to_string()).spawn(move || {
+                watch_file(inotify, &path, flag, wake)
+            }).unwrap();
+        },
+        Err(e) => error!("Couldn't watch {} for changes: {}", path.display(), e),
+    }
+}
+
+fn request(flag: &ReloadFlag, wake: &Sender<Event>) {
+    flag.raise();
+    let _ = wake.send(Event::Reload);
+}
+
+// An inotify watch on the directory holding `path` - editors often replace the file rather than
+// write to it, which a watch on the file itself wouldn't survive.
+fn watch_dir_of(path: &Path) -> io::Result<File> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = CString::new(dir.as_os_str().as_bytes())?;
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 { return Err(io::Error::last_os_error()); }
+    let inotify = unsafe { File::from_raw_fd(fd) };
+
+    if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(inotify)
+}
+
+// Block reading inotify events, and ask for a reload whenever one's about the config file.
+fn watch_file(mut inotify: File, path: &Path, flag: ReloadFlag, wake: Sender<Event>) {
+    let name = path.file_name().unwrap_or_default().as_bytes();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match inotify.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return error!("Stopped watching {} for changes: {}", path.display(), e),
+        };
+        if event_names(&buf[..n]).any(|changed| changed == name) {
+            info!("{} changed on disk", path.display());
+            request(&flag, &wake);
+        }
+    }
+}
+
+// The file names in a buffer of inotify events, each a header then its NUL-padded name.
+fn event_names(mut buf: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let header = mem::size_of::<libc::inotify_event>();
+        if buf.len() < header { return None; }
+        let event = unsafe { ptr::read_unaligned(buf.as_ptr() as *const libc::inotify_event) };
+        let (name, rest) = buf[header..].split_at((event.len as usize).min(buf.len() - header));
+        buf = rest;
+        Some(name.split(|b| *b == 0).next().unwrap_or_default())
+    })
+}
+
+/***
+ * The ids of widgets whose definition differs between `old` and `new`, or which have been removed.
+ * Widgets only present in `new` aren't "changed" - they just need starting.
+ ***/
+pub fn changed_widgets(old: &[Widget], new: &[Widget]) -> Vec<TaskId> {
+    old.iter()
+        .filter(|o| !new.iter().any(|n| n == *o))
+        .map(|o| o.id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    fn widget(id: &str, period: &str) -> Widget {
+        toml::from_str(&format!(r#"
+            id = "{}"
+            name = "A widget"
+            description = "For testing"
+            path = "/bin"
+            command = "./date"
+            period = "{}"
+        "#, id, period)).unwrap()
+    }
+
+    #[test]
+    fn reloads_wait_until_were_ready() {
+        let flag = ReloadFlag::default();
+        assert!(!flag.take_when(true));
+
+        flag.raise();
+        assert!(!flag.take_when(false));
+        assert!(!flag.take_when(false));
+        assert!(flag.take_when(true));
+        assert!(!flag.take_when(true));
+    }
+
+    #[test]
+    fn notices_the_config_file_changing() {
+        let dir = std::env::temp_dir().join(format!("hex-watch-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tasks.toml");
+        fs::write(&path, "").unwrap();
+
+        let (flag, (wake, woken)) = (ReloadFlag::default(), channel());
+        let inotify = watch_dir_of(&path).unwrap();
+        let watched = path.clone();
+        let watching = flag.clone();
+        thread::spawn(move || watch_file(inotify, &watched, watching, wake));
+
+        // Only the config file itself matters, not its neighbours.
+        fs::write(dir.join("notes.txt"), "unrelated").unwrap();
+        fs::write(dir.join(".tasks.toml.swp"), "").unwrap();
+        fs::rename(dir.join(".tasks.toml.swp"), &path).unwrap();
+
+        assert!(matches!(woken.recv_timeout(Duration::from_secs(5)), Ok(Event::Reload)));
+        assert!(flag.take_when(true));
+        assert!(woken.recv_timeout(Duration::from_millis(200)).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unchanged_widgets_are_left_alone() {
+        let old = vec![widget("time", "1s"), widget("todo", "1m")];
+        let new = vec![widget("todo", "1m"), widget("time", "1s"), widget("cpu", "10s")];
+        assert!(changed_widgets(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn edited_and_removed_widgets_are_changed() {
+        let old = vec![widget("time", "1s"), widget("todo", "1m"), widget("cpu", "10s")];
+        let new = vec![widget("time", "5s"), widget("cpu", "10s")];
+        assert_eq!(changed_widgets(&old, &new), vec!["time".to_string(), "todo".to_string()]);
+    }
+}
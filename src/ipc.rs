@@ -0,0 +1,151 @@
+use std::env;
+use std::path::PathBuf;
+
+const SOCKET_FILE: &str = "hex.sock";
+
+/***
+Request: One line sent to hex's control socket.
+    set <widget> <text>    -> Replace the widget's content with `text`.
+    append <widget> <text> -> Add `text` to the widget's content, on a new line.
+    clear <widget>         -> Empty the widget.
+    run <command>          -> Run a prompt command, exactly as if it'd been typed at the prompt.
+    `text` is escaped (see `escape`), so it can hold newlines and still fit on one line.
+    Every request is answered with a line of its own: "ok", or "error: <why>".
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Request {
+    Set(String, String),
+    Append(String, String),
+    Clear(String),
+    Run(String),
+}
+
+impl Request {
+    /// The request as a line of the protocol, without its trailing newline.
+    pub fn to_line(&self) -> String {
+        match self {
+            Request::Set(id, text) => format!("set {} {}", id, escape(text)),
+            Request::Append(id, text) => format!("append {} {}", id, escape(text)),
+            Request::Clear(id) => format!("clear {}", id),
+            Request::Run(command) => format!("run {}", escape(command)),
+        }
+    }
+}
+
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    let line = line.trim_end_matches(&['\n', '\r'][..]);
+    let (verb, rest) = split_word(line);
+    let (id, text) = split_word(rest);
+
+    match (verb, id.is_empty()) {
+        ("", _) => Err("empty request".to_string()),
+        ("run", true) => Err("run needs a command".to_string()),
+        ("run", false) => Ok(Request::Run(unescape(rest))),
+        ("set", _) | ("append", _) | ("clear", _) if id.is_empty() => Err(format!("{} needs a widget id", verb)),
+        ("set", _) => Ok(Request::Set(id.to_string(), unescape(text))),
+        ("append", _) => Ok(Request::Append(id.to_string(), unescape(text))),
+        ("clear", _) => Ok(Request::Clear(id.to_string())),
+        (verb, _) => Err(format!("unknown request '{}' - expected set, append, clear or run", verb)),
+    }
+}
+
+/// The first space-separated word of `s`, and whatever follows the space after it.
+fn split_word(s: &str) -> (&str, &str) {
+    match s.find(' ') {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => (s, ""),
+    }
+}
+
+/***
+ * Escape text to fit on one protocol line: backslash, newline, carriage return, tab and
+ * ESC become \\, \n, \r, \t and \e.
+ ***/
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{1b}' => escaped.push_str("\\e"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Undo `escape`. Unknown escapes are left as they are.
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some('e') => unescaped.push('\u{1b}'),
+            Some(other) => { unescaped.push('\\'); unescaped.push(other); }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/***
+ * Where hex listens by default: $XDG_RUNTIME_DIR/hex.sock, or /tmp/hex-<uid>.sock
+ * if there's no runtime dir.
+ ***/
+pub fn default_socket_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(SOCKET_FILE),
+        None => env::temp_dir().join(format!("hex-{}.sock", unsafe { libc::getuid() })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_request() {
+        assert_eq!(parse_request("set todo buy milk\n"), Ok(Request::Set("todo".to_string(), "buy milk".to_string())));
+        assert_eq!(parse_request("append log one\\ntwo"), Ok(Request::Append("log".to_string(), "one\ntwo".to_string())));
+        assert_eq!(parse_request("clear todo"), Ok(Request::Clear("todo".to_string())));
+        assert_eq!(parse_request("run todo add milk"), Ok(Request::Run("todo add milk".to_string())));
+    }
+
+    #[test]
+    fn set_without_text_empties_the_widget() {
+        assert_eq!(parse_request("set todo"), Ok(Request::Set("todo".to_string(), String::new())));
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(parse_request(""), Err("empty request".to_string()));
+        assert_eq!(parse_request("set"), Err("set needs a widget id".to_string()));
+        assert_eq!(parse_request("run"), Err("run needs a command".to_string()));
+        assert!(parse_request("frobnicate todo").is_err());
+    }
+
+    #[test]
+    fn escaping_round_trips() {
+        let text = "line one\n\tline \\two\u{1b}[31m red\r\n";
+        assert!(!escape(text).contains('\n'));
+        assert_eq!(unescape(&escape(text)), text);
+        assert_eq!(parse_request(&Request::Set("x".to_string(), text.to_string()).to_line()),
+                   Ok(Request::Set("x".to_string(), text.to_string())));
+    }
+
+    #[test]
+    fn unknown_escapes_are_kept() {
+        assert_eq!(unescape("C:\\dir\\"), "C:\\dir\\");
+    }
+}
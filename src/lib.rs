@@ -1 +1,3 @@
-extern crate termion;
\ No newline at end of file
+extern crate termion;
+
+pub mod ipc;
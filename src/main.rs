@@ -1,31 +1,63 @@
 extern crate regex;
 extern crate simplelog;
 
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::Path;
+use std::process;
 
+use clap::Parser;
 use simplelog::*;
 
+use crate::cli::{Cli, Mode};
 use crate::hexterm::HexTerm;
 
+mod app_session;
+mod cli;
+mod control_socket;
 mod views;
 mod tasks;
 mod executable_command;
+mod health;
 mod runner;
+mod processes;
+mod task_result;
 mod hexterm;
 mod terminal;
+mod validation;
 
 fn main() {
-    init_logging();
-    let config = tasks::load_task_config().unwrap();
-    HexTerm::new(config).run();
+    let cli = Cli::parse();
+
+    let config_path = cli.config_path();
+    let config = match tasks::load_task_config(&config_path) {
+        Ok(config) => config,
+        Err(errors) => {
+            eprintln!("{} has {} problem(s):", config_path.display(), errors.len());
+            errors.iter().for_each(|e| eprintln!("  {}", e));
+            process::exit(1);
+        }
+    };
+
+    match cli.mode() {
+        Mode::Run => {
+            // Only a running hex has anything to log - checking the config shouldn't truncate its log.
+            init_logging(&cli.log_path(), cli.log_level);
+            HexTerm::new(config, config_path, cli.socket_path()).run()
+        },
+        Mode::CheckConfig => println!("{}: OK", config_path.display()),
+        Mode::PrintLayout => print!("{}", config.layout),
+    }
 }
 
-fn init_logging() {
+fn init_logging(path: &Path, level: LevelFilter) {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).unwrap_or_else(|e| panic!("Couldn't create log dir {}: {}", dir.display(), e));
+    }
+
+    let log_file = File::create(path).unwrap_or_else(|e| panic!("Couldn't create log file {}: {}", path.display(), e));
     CombinedLogger::init(
         vec![
-            WriteLogger::new(LevelFilter::Info, Config::default(), File::create("log/hex.log").unwrap()),
+            WriteLogger::new(level, Config::default(), log_file),
         ]
     ).unwrap();
 }
-
-
@@ -0,0 +1,350 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::{info, warn};
+
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(200);
+
+/***
+RunningProcesses: The process groups of every child hex currently has running.
+    Each command runs in its own process group, so killing the group takes out
+    anything the command spawned too (e.g. every stage of a shell pipeline).
+ */
+#[derive(Clone, Default)]
+pub struct RunningProcesses {
+    groups: Arc<Mutex<HashSet<i32>>>,
+    // Notified whenever a group finishes.
+    finished: Arc<Condvar>,
+}
+
+impl RunningProcesses {
+    pub fn new() -> RunningProcesses {
+        RunningProcesses::default()
+    }
+
+    fn add(&self, pgid: i32) {
+        self.groups.lock().unwrap().insert(pgid);
+    }
+
+    fn remove(&self, pgid: i32) {
+        self.groups.lock().unwrap().remove(&pgid);
+        self.finished.notify_all();
+    }
+
+    /***
+     * Terminate everything still running: SIGTERM first, then SIGKILL for anything
+     * that hasn't finished after a short grace period.
+     ***/
+    pub fn terminate_all(&self) {
+        let groups = self.groups.lock().unwrap().clone();
+        if groups.is_empty() { return; }
+
+        info!("Terminating {} running process group(s)", groups.len());
+        groups.iter().for_each(|pgid| signal_group(*pgid, libc::SIGTERM));
+
+        let (groups, _) = self.finished
+            .wait_timeout_while(self.groups.lock().unwrap(), SHUTDOWN_GRACE, |groups| !groups.is_empty())
+            .unwrap();
+        groups.iter().for_each(|pgid| signal_group(*pgid, libc::SIGKILL));
+    }
+}
+
+fn signal_group(pgid: i32, signal: libc::c_int) {
+    // A negative pid signals the whole process group.
+    if unsafe { libc::kill(-pgid, signal) } != 0 {
+        warn!("Couldn't signal process group {}: {}", pgid, io::Error::last_os_error());
+    }
+}
+
+/***
+Finished: Everything a completed (or killed) command left behind.
+    `combined` is stdout and stderr together, in roughly the order they were written.
+ */
+pub struct Finished {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub combined: Vec<u8>,
+    pub timed_out: bool,
+}
+
+/***
+ * Run `command` to completion in its own process group, collecting its output.
+ * If it's still going after `timeout`, the whole group is killed and `timed_out` is set.
+ * Anything it left running in the background is killed when it exits, as that would
+ * otherwise keep its output open - and us waiting - for as long as it liked.
+ ***/
+pub fn run_with_timeout(mut command: Command, timeout: Option<Duration>, running: &RunningProcesses) -> io::Result<Finished> {
+    let mut child = command
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let pgid = child.id() as i32;
+    running.add(pgid);
+
+    let combined = Arc::new(Mutex::new(Vec::new()));
+    let stdout = read_in_background(child.stdout.take(), combined.clone());
+    let stderr = read_in_background(child.stderr.take(), combined.clone());
+
+    let exited = wait_in_background(&child);
+    let in_time = match timeout {
+        Some(timeout) => exited.recv_timeout(timeout).ok(),
+        None => exited.recv().ok(),
+    };
+    let timed_out = in_time.is_none();
+
+    // The leader's not been reaped yet, so the group's still there to kill even if it's exited.
+    signal_group(pgid, libc::SIGKILL);
+    // Once the waiting thread's seen it die, it's safe to reap.
+    let waited = in_time.unwrap_or_else(|| exited.recv().unwrap_or(Ok(())));
+    let status = child.wait();
+    running.remove(pgid);
+    waited?;
+    let status = status?;
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let combined = combined.lock().unwrap().clone();
+
+    Ok(Finished { status, stdout, stderr, combined, timed_out })
+}
+
+/***
+ * Wait on its own thread for the child to exit, so whoever's waiting can give up on it.
+ * Sends once it has. It's left a zombie, so its pid - and process group - can't be reused yet.
+ ***/
+fn wait_in_background(child: &Child) -> Receiver<io::Result<()>> {
+    let (tx, exited) = channel();
+    let pid = child.id();
+    thread::spawn(move || {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let result = loop {
+            if unsafe { libc::waitid(libc::P_PID, pid, &mut info, libc::WEXITED | libc::WNOWAIT) } == 0 {
+                break Ok(());
+            }
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted { break Err(e); }
+        };
+        let _ = tx.send(result);
+    });
+    exited
+}
+
+/// Drain a pipe on its own thread, copying each chunk into `combined` as it arrives.
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>, combined: Arc<Mutex<Vec<u8>>>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        if let Some(mut pipe) = pipe {
+            while let Ok(n) = pipe.read(&mut chunk) {
+                if n == 0 { break; }
+                buf.extend_from_slice(&chunk[..n]);
+                combined.lock().unwrap().extend_from_slice(&chunk[..n]);
+            }
+        }
+        buf
+    })
+}
+
+/// A line a streaming command printed, minus its newline.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StreamLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// What a Stream sends: each line as it's printed, then - always last - that it's exited.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StreamEvent {
+    Line(StreamLine),
+    Exited,
+}
+
+/***
+Stream: A long-running command whose output is read a line at a time, as it's printed.
+    It's only sent StreamEvent::Exited once it's closed both stdout and stderr, and exited.
+    Dropping a Stream kills its process group if it's still running.
+ */
+pub struct Stream {
+    child: Child,
+    pgid: i32,
+    running: RunningProcesses,
+}
+
+/***
+ * Start `command` in its own process group without waiting for it. Its output arrives as
+ * StreamEvents on `events`, which can be shared with whatever else the reader's waiting on.
+ ***/
+pub fn spawn_stream<E>(mut command: Command, running: &RunningProcesses, events: Sender<E>) -> io::Result<Stream>
+    where E: From<StreamEvent> + Send + 'static {
+    let mut child = command
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let pgid = child.id() as i32;
+    running.add(pgid);
+
+    let stdout = read_lines_in_background(child.stdout.take(), events.clone(), StreamLine::Stdout);
+    let stderr = read_lines_in_background(child.stderr.take(), events.clone(), StreamLine::Stderr);
+    let exited = wait_in_background(&child);
+    thread::spawn(move || {
+        let _ = stdout.join();
+        let _ = stderr.join();
+        let _ = exited.recv();
+        let _ = events.send(StreamEvent::Exited.into());
+    });
+
+    Ok(Stream { child, pgid, running: running.clone() })
+}
+
+impl Stream {
+    /// The command's exit status. Doesn't block once it's sent StreamEvent::Exited.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        let status = self.child.wait();
+        self.running.remove(self.pgid);
+        status
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            signal_group(self.pgid, libc::SIGKILL);
+            let _ = self.child.wait();
+        }
+        self.running.remove(self.pgid);
+    }
+}
+
+/// Send each line read from a pipe, on its own thread, until the pipe closes or nobody's listening.
+fn read_lines_in_background<R, E, F>(pipe: Option<R>, tx: Sender<E>, wrap: F) -> thread::JoinHandle<()>
+    where R: Read + Send + 'static, E: From<StreamEvent> + Send + 'static, F: Fn(String) -> StreamLine + Send + 'static {
+    thread::spawn(move || {
+        let mut reader = match pipe {
+            Some(pipe) => BufReader::new(pipe),
+            None => return,
+        };
+
+        let mut line = Vec::new();
+        while let Ok(n) = reader.read_until(b'\n', &mut line) {
+            if n == 0 { break; }
+            let text = String::from_utf8_lossy(&line).trim_end_matches(&['\n', '\r'][..]).to_string();
+            if tx.send(StreamEvent::Line(wrap(text)).into()).is_err() { break; }
+            line.clear();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn count(running: &RunningProcesses) -> usize {
+        running.groups.lock().unwrap().len()
+    }
+
+    fn sh(script: &str) -> Command {
+        let mut cmd = Command::new("/bin/sh");
+        cmd.arg("-c").arg(script);
+        cmd
+    }
+
+    #[test]
+    fn collects_output_of_fast_commands() {
+        let running = RunningProcesses::new();
+        let finished = run_with_timeout(sh("echo out; sleep 0.1; echo err >&2; exit 3"), Some(Duration::from_secs(5)), &running).unwrap();
+
+        assert!(!finished.timed_out);
+        assert_eq!(finished.status.code(), Some(3));
+        assert_eq!(finished.stdout, b"out\n");
+        assert_eq!(finished.stderr, b"err\n");
+        assert_eq!(finished.combined, b"out\nerr\n");
+        assert_eq!(count(&running), 0);
+    }
+
+    #[test]
+    fn kills_the_whole_group_on_timeout() {
+        let running = RunningProcesses::new();
+        let started = Instant::now();
+        // The pipeline means there's more than one process to kill.
+        let finished = run_with_timeout(sh("echo partial; sleep 10 | cat"), Some(Duration::from_millis(200)), &running).unwrap();
+
+        assert!(finished.timed_out);
+        assert_eq!(finished.stdout, b"partial\n");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(count(&running), 0);
+    }
+
+    #[test]
+    fn background_children_dont_keep_it_waiting() {
+        let running = RunningProcesses::new();
+        let started = Instant::now();
+        let finished = run_with_timeout(sh("echo done; sleep 10 &"), Some(Duration::from_secs(5)), &running).unwrap();
+
+        assert!(!finished.timed_out);
+        assert!(finished.status.success());
+        assert_eq!(finished.stdout, b"done\n");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(count(&running), 0);
+    }
+
+    #[test]
+    fn streams_lines_as_they_are_printed() {
+        let running = RunningProcesses::new();
+        let (tx, events) = channel();
+        let mut stream = spawn_stream(sh("echo one; echo two >&2; sleep 0.1; echo three"), &running, tx).unwrap();
+
+        let events = events.iter().collect::<Vec<StreamEvent>>();
+        assert_eq!(events, vec![StreamEvent::Line(StreamLine::Stdout("one".to_string())),
+                                StreamEvent::Line(StreamLine::Stderr("two".to_string())),
+                                StreamEvent::Line(StreamLine::Stdout("three".to_string())),
+                                StreamEvent::Exited]);
+
+        assert!(stream.wait().unwrap().success());
+        assert_eq!(count(&running), 0);
+    }
+
+    #[test]
+    fn dropping_a_stream_kills_it() {
+        let running = RunningProcesses::new();
+        let (tx, events) = channel();
+        let stream = spawn_stream(sh("while true; do echo tick; sleep 0.05; done"), &running, tx).unwrap();
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)), Ok(StreamEvent::Line(StreamLine::Stdout("tick".to_string()))));
+        assert_eq!(count(&running), 1);
+
+        drop(stream);
+        assert_eq!(count(&running), 0);
+    }
+
+    #[test]
+    fn terminating_kills_whatever_ignores_sigterm() {
+        let running = RunningProcesses::new();
+        let (tx, events) = channel();
+        let mut stream = spawn_stream(sh("trap '' TERM; echo ready; sleep 10"), &running, tx).unwrap();
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)), Ok(StreamEvent::Line(StreamLine::Stdout("ready".to_string()))));
+
+        running.terminate_all();
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)), Ok(StreamEvent::Exited));
+        assert!(!stream.wait().unwrap().success());
+        assert_eq!(count(&running), 0);
+    }
+
+    #[test]
+    fn spawn_failures_are_errors() {
+        let running = RunningProcesses::new();
+        assert!(run_with_timeout(Command::new("/no/such/program"), None, &running).is_err());
+    }
+}
@@ -1,119 +1,350 @@
 use std::collections::HashMap;
-use std::process::{Command, Output};
-use std::str;
-use std::sync::mpsc::Sender;
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
 use std::thread;
-use std::thread::sleep;
 use std::time::{Duration, SystemTime};
 
 use log::{info, trace, warn};
 
-use crate::executable_command::ExecutableCommand;
-use crate::tasks::Widget;
+use crate::executable_command::{parse_period, ExecutableCommand, Schedule};
+use crate::health::{backoff_millis, Health, StatusBoard};
+use crate::hexterm::TaskId;
+use crate::processes::{run_with_timeout, spawn_stream, Finished, RunningProcesses, StreamEvent};
+use crate::task_result::{StreamBuffer, TaskResult};
+use crate::tasks::{Widget, WidgetMode, DEFAULT_SHELL};
+
+// What a stream widget's loop waits on: its command, or being told to stop.
+enum StreamLoopEvent {
+    Stream(StreamEvent),
+    Stop,
+}
+
+impl From<StreamEvent> for StreamLoopEvent {
+    fn from(event: StreamEvent) -> StreamLoopEvent {
+        StreamLoopEvent::Stream(event)
+    }
+}
 
 pub struct WidgetUpdater {
     pub commands: Vec<ExecutableCommand>,
-    system_command_sender: Sender<HashMap<String, String>>,
+    system_command_sender: Sender<HashMap<TaskId, TaskResult>>,
+    // Dropping a task loop's Sender tells that loop to stop.
+    task_loops: HashMap<TaskId, Sender<()>>,
+    running: RunningProcesses,
+    statuses: StatusBoard,
 }
 
 impl WidgetUpdater {
     pub fn new(tasks: Vec<Widget>,
-               output_tx: Sender<HashMap<String, String>>) -> WidgetUpdater {
-        let commands = tasks.iter().
-            map(|t| task_to_command(t)).
-            collect();
+               output_tx: Sender<HashMap<TaskId, TaskResult>>) -> WidgetUpdater {
+        let commands = widgets_to_commands(&tasks);
 
-        WidgetUpdater { commands, system_command_sender: output_tx }
+        WidgetUpdater { commands, system_command_sender: output_tx, task_loops: HashMap::new(), running: RunningProcesses::new(), statuses: StatusBoard::new() }
     }
 
+    /***
+     * Start a loop for every command that doesn't already have one running.
+     ***/
     pub fn start(&mut self) {
-        self.commands.iter().for_each(|cmd| self.start_task_loop(&cmd) );
+        let stopped = self.commands.iter()
+            .filter(|cmd| !self.task_loops.contains_key(&cmd.id))
+            .cloned()
+            .collect::<Vec<ExecutableCommand>>();
+
+        for cmd in stopped {
+            let stopper = match cmd.schedule {
+                Schedule::Continuous => self.start_stream_loop(&cmd),
+                _ => self.start_task_loop(&cmd),
+            };
+            self.task_loops.insert(cmd.id.clone(), stopper);
+        }
+    }
+
+    /***
+     * Swap in a new set of widgets. Loops for the `changed` widgets, and for any widget
+     * that no longer exists, are stopped. New and changed widgets are then (re)started.
+     * Widgets that didn't change keep running undisturbed.
+     ***/
+    pub fn reconfigure(&mut self, widgets: &[Widget], changed: &[TaskId]) {
+        self.commands = widgets_to_commands(widgets);
+
+        let commands = &self.commands;
+        let statuses = &self.statuses;
+        self.task_loops.retain(|id, _| {
+            let keep = !changed.contains(id) && commands.iter().any(|cmd| &cmd.id == id);
+            if !keep { statuses.forget(id); }
+            keep
+        });
+
+        self.start();
+    }
+
+    /// A table of each widget's health, last run and next scheduled run.
+    pub fn status_report(&self) -> String {
+        let task_ids = self.commands.iter().map(|cmd| cmd.id.clone()).collect::<Vec<TaskId>>();
+        self.statuses.report(&task_ids, SystemTime::now())
+    }
+
+    /***
+     * Stop every task loop and terminate any commands that are still running.
+     ***/
+    pub fn shutdown(&mut self) {
+        self.task_loops.clear();
+        self.running.terminate_all();
     }
 
     pub fn run_command(&self, task_id: String, command: String) {
         match self.commands.iter().find(|cmd| cmd.id == task_id) {
-            Some(cmd) => {
-                let mut mutcmd = cmd.clone();
-                let mut parts = command.split_whitespace();
-                parts.next(); // pop the initial command off
-                mutcmd.command += " ";
-                mutcmd.command += parts.collect::<Vec<&str>>().join(" ").as_str();
-
-                self.run_task_once(&mutcmd);
-            }
+            Some(cmd) if matches!(cmd.schedule, Schedule::Continuous) => { warn!("'{}' is a stream - it's already running", task_id) }
+            Some(cmd) => { self.run_task_once(&cmd.with_args(&command)); }
             None => { warn!("Could not find command '{}'", task_id) }
         }
     }
 
-    fn start_task_loop(&self, command: &ExecutableCommand) {
+    fn start_task_loop(&self, command: &ExecutableCommand) -> Sender<()> {
         let trx = self.system_command_sender.clone();
         let cmd = command.clone();
+        let running = self.running.clone();
+        let statuses = self.statuses.clone();
+        let (stop_tx, stop_rx) = channel();
         info!("spawn {} thread", cmd.id);
 
         thread::Builder::new().name(cmd.id.clone()).spawn(move ||
             {
+                // The output so far, for widgets that keep their scrollback.
+                let mut earlier: Option<TaskResult> = None;
                 loop {
                     let last_run = SystemTime::now();
 
+                    let result = match (exec_command(&cmd, &running), cmd.scrollback, &earlier) {
+                        (result, Some(max_lines), Some(earlier)) => result.after(earlier, max_lines),
+                        (result, _, _) => result,
+                    };
+                    let failures = statuses.record_run(&cmd.id, result.succeeded(), last_run);
+                    if cmd.scrollback.is_some() { earlier = Some(result.clone()); }
+
                     let mut h = HashMap::new();
-                    h.insert(cmd.id.clone(), convert_output(exec_command(cmd.command.clone(), cmd.working_dir.clone())));
+                    h.insert(cmd.id.clone(), result);
+
+                    // Don't report output from a config that's since been replaced.
+                    if stopped(&stop_rx) { break; }
                     trx.send(h).unwrap();
 
-                    let nap_millis = cmd.millis_until_next_run(last_run.elapsed().unwrap().as_millis() as u64);
+                    let now = SystemTime::now();
+                    let elapsed = now.duration_since(last_run).unwrap_or_default().as_millis() as u64;
+                    let nap_millis = match failures {
+                        0 => cmd.schedule.millis_until_next_run(last_run, now),
+                        n => {
+                            let backoff = backoff_millis(cmd.schedule.nominal_millis(now), n);
+                            warn!("{} is {} - retrying in {}ms", cmd.id, Health::from_failures(n), backoff);
+                            backoff.saturating_sub(elapsed)
+                        }
+                    };
                     let naptime = Duration::from_millis(nap_millis);
-                    info!("{} ran for {:.2?}", cmd.id, last_run.elapsed().unwrap());
+                    statuses.schedule(&cmd.id, SystemTime::now() + naptime);
+                    info!("{} ran for {:.2?}", cmd.id, last_run.elapsed().unwrap_or_default());
                     trace!("{} sleeping for {}ms", cmd.id, nap_millis);
-                    sleep(naptime);
+                    match stop_rx.recv_timeout(naptime) {
+                        Err(RecvTimeoutError::Timeout) => {},
+                        _ => break
+                    }
+                }
+                info!("{} thread stopped", cmd.id);
+            }).unwrap();
+
+        stop_tx
+    }
+
+    /***
+     * Like a task loop, but for a stream widget: its command runs until it exits, and
+     * is then restarted - backing off further each time it exits without printing anything.
+     ***/
+    fn start_stream_loop(&self, command: &ExecutableCommand) -> Sender<()> {
+        let trx = self.system_command_sender.clone();
+        let cmd = command.clone();
+        let running = self.running.clone();
+        let statuses = self.statuses.clone();
+        let (stop_tx, stop_rx) = channel::<()>();
+        let (events_tx, events) = channel();
+        info!("spawn {} stream thread", cmd.id);
+
+        // Being stopped has to wake the loop from waiting on its command too.
+        let stop = events_tx.clone();
+        thread::spawn(move || {
+            let _ = stop_rx.recv();
+            let _ = stop.send(StreamLoopEvent::Stop);
+        });
+
+        thread::Builder::new().name(cmd.id.clone()).spawn(move ||
+            {
+                let mut buffer = StreamBuffer::new(cmd.max_lines);
+                loop {
+                    let started = SystemTime::now();
+                    let result = match stream_command(&cmd, &running, &mut buffer, &events_tx, &events, &trx, &statuses) {
+                        Some(result) => result,
+                        None => break,
+                    };
+                    let failures = statuses.record_run(&cmd.id, false, started);
+
+                    let mut h = HashMap::new();
+                    h.insert(cmd.id.clone(), result);
+                    // Its command's exited, so the only thing left to hear is being stopped.
+                    if events.try_recv().is_ok() || trx.send(h).is_err() { break; }
+
+                    let nap_millis = backoff_millis(cmd.schedule.nominal_millis(SystemTime::now()), failures);
+                    warn!("{} is {} - restarting in {}ms", cmd.id, Health::from_failures(failures), nap_millis);
+                    let naptime = Duration::from_millis(nap_millis);
+                    statuses.schedule(&cmd.id, SystemTime::now() + naptime);
+                    match events.recv_timeout(naptime) {
+                        Err(RecvTimeoutError::Timeout) => {},
+                        _ => break
+                    }
                 }
+                info!("{} stream thread stopped", cmd.id);
             }).unwrap();
+
+        stop_tx
     }
 
-    fn run_task_once(&self, command: &ExecutableCommand) -> () {
+    fn run_task_once(&self, command: &ExecutableCommand) {
         let trx = self.system_command_sender.clone();
         let cmd = command.clone();
         info!("Running manual '{}' command", cmd.id);
 
         let mut h = HashMap::new();
-        h.insert(cmd.id.clone(), convert_output(exec_command(cmd.command.clone(), cmd.working_dir.clone())));
+        h.insert(cmd.id.clone(), exec_command(&cmd, &self.running));
         trx.send(h).unwrap();
     }
 }
 
-fn convert_output(output: Output) -> String {
-    let std_text = match str::from_utf8(&output.stdout) {
-        Ok(t) => t.to_owned(),
-        Err(_) => String::from("")
-    };
+fn stopped(stop_rx: &Receiver<()>) -> bool {
+    !matches!(stop_rx.try_recv(), Err(TryRecvError::Empty))
+}
 
-    let err_text = match str::from_utf8(&output.stderr) {
-        Ok(t) => t.to_owned(),
-        Err(_) => String::from("")
-    };
+fn convert_output(finished: Finished, timeout: Option<Duration>, started: SystemTime) -> TaskResult {
+    TaskResult {
+        stdout: String::from_utf8_lossy(&finished.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&finished.stderr).into_owned(),
+        interleaved: String::from_utf8_lossy(&finished.combined).into_owned(),
+        exit_code: finished.status.code(),
+        duration: started.elapsed().unwrap_or_default(),
+        timestamp: started,
+        timed_out: if finished.timed_out { timeout } else { None },
+        error: None,
+        running: false,
+    }
+}
+
+fn build_command(cmd: &ExecutableCommand) -> Command {
+    let argv = cmd.argv();
+    info!("Running {:?} in {}", argv, cmd.working_dir);
 
-    // If we got an error, show that.
-    match err_text.len() {
-        0 => std_text,
-        _ => err_text
+    let mut command = Command::new(&argv[0]);
+    command.args(&argv[1..]);
+    if !cmd.working_dir.is_empty() {
+        command.current_dir(&cmd.working_dir);
     }
+    command
 }
 
-fn exec_command(command: String, working_dir: String) -> Output {
-    let mut parts = command.trim().split_whitespace();
-    let cmd = parts.next().unwrap();
-    let args = parts;
+fn exec_command(cmd: &ExecutableCommand, running: &RunningProcesses) -> TaskResult {
+    let started = SystemTime::now();
+    let timeout = cmd.timeout.map(Duration::from_millis);
+    let result = match run_with_timeout(build_command(cmd), timeout, running) {
+        Ok(finished) => convert_output(finished, timeout, started),
+        Err(e) => TaskResult::spawn_failure(e.to_string(), started),
+    };
+    if !result.succeeded() {
+        warn!("{} failed (exit {:?}, timed out {:?}, error {:?}): {}", cmd.id, result.exit_code, result.timed_out, result.error, result.stderr.trim());
+    }
+
+    result
+}
 
-    info!("Running {}/{} {}", working_dir, cmd, args.clone().map(|s| s.to_string()).collect::<Vec<String>>().join(" "));
+/***
+ * Run a stream widget's command, sending the buffer on each time it prints something.
+ * Returns how it ended once it exits, or None if its loop was stopped first (which kills it).
+ ***/
+fn stream_command(cmd: &ExecutableCommand,
+                  running: &RunningProcesses,
+                  buffer: &mut StreamBuffer,
+                  events_tx: &Sender<StreamLoopEvent>,
+                  events: &Receiver<StreamLoopEvent>,
+                  trx: &Sender<HashMap<TaskId, TaskResult>>,
+                  statuses: &StatusBoard) -> Option<TaskResult> {
+    let started = SystemTime::now();
+    let mut stream = match spawn_stream(build_command(cmd), running, events_tx.clone()) {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("{} couldn't start: {}", cmd.id, e);
+            return Some(TaskResult::spawn_failure(e.to_string(), started));
+        }
+    };
 
-    Command::new(vec!(working_dir.clone(), cmd.to_string()).join("/"))
-        .current_dir(working_dir.clone())
-        .args(args)
-        .output()
-        .expect("failed to execute process")
+    let mut printed = false;
+    let mut unsent = false;
+    loop {
+        // Catch up on anything else that's waiting before redrawing.
+        let event = match events.try_recv() {
+            Ok(event) => event,
+            Err(_) => {
+                if unsent {
+                    let mut h = HashMap::new();
+                    h.insert(cmd.id.clone(), buffer.result(started));
+                    if trx.send(h).is_err() { return None; }
+                }
+                events.recv().ok()?
+            }
+        };
+
+        match event {
+            StreamLoopEvent::Stream(StreamEvent::Line(line)) => {
+                buffer.push(line);
+                // A stream that's printing is healthy, even if it failed to start last time.
+                if !printed { statuses.record_run(&cmd.id, true, started); }
+                printed = true;
+                unsent = true;
+            }
+            StreamLoopEvent::Stream(StreamEvent::Exited) => break,
+            StreamLoopEvent::Stop => return None,
+        }
+    }
+
+    match stream.wait() {
+        Ok(status) => {
+            warn!("{} exited with {}", cmd.id, status);
+            Some(TaskResult { exit_code: status.code(), running: false, ..buffer.result(started) })
+        }
+        Err(e) => Some(TaskResult::spawn_failure(e.to_string(), started)),
+    }
+}
+
+/// Commands for every widget that has one to run - push widgets don't.
+fn widgets_to_commands(widgets: &[Widget]) -> Vec<ExecutableCommand> {
+    widgets.iter()
+        .filter(|w| w.mode != WidgetMode::Push)
+        .map(task_to_command)
+        .collect()
 }
 
 fn task_to_command(t: &Widget) -> ExecutableCommand {
-    ExecutableCommand::new(t.id.clone(),
-                           t.command.clone(),
-                           t.path.clone(),
-                           t.period.clone())
+    let shell = t.shell.clone().unwrap_or_else(|| DEFAULT_SHELL.to_string());
+    // The config's been validated by now, so one of these is good.
+    let schedule = match (&t.schedule, &t.period) {
+        _ if t.mode == WidgetMode::Stream => Schedule::Continuous,
+        (Some(cron), _) => Schedule::cron(cron).expect("invalid schedule"),
+        (None, period) => Schedule::every(period.as_deref().unwrap_or_default(), t.align.unwrap_or(false)),
+    };
+
+    let command = ExecutableCommand::new(t.id.clone(),
+                                         t.command.clone().expect("widget has no command"),
+                                         t.path.clone(),
+                                         shell_words::split(&shell).unwrap_or_default(),
+                                         t.timeout.as_ref().and_then(|t| parse_period(t)),
+                                         schedule);
+    let command = ExecutableCommand { scrollback: t.scrollback, ..command };
+    match t.max_lines {
+        Some(max_lines) => ExecutableCommand { max_lines, ..command },
+        None => command,
+    }
 }
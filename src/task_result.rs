@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+
+use crate::processes::StreamLine;
+
+// How many lines a stream widget keeps if it doesn't set max_lines.
+pub const DEFAULT_MAX_LINES: usize = 10;
+
+/***
+TaskResult: What a single run of a widget's command produced.
+    `interleaved` holds stdout and stderr together, in roughly the order they were written.
+    `exit_code` is None if the process was killed by a signal (including by a timeout),
+    or never started at all - in which case `error` says why.
+    `running` is set for a stream widget whose command hasn't exited yet.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub interleaved: String,
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    pub timestamp: SystemTime,
+    pub timed_out: Option<Duration>,
+    pub error: Option<String>,
+    pub running: bool,
+}
+
+/***
+OutputDisplay: How a widget turns a TaskResult into text. Set per widget with `display = "..."`.
+    Stdout   -> Just stdout.
+    Status   -> stdout, plus a red status line whenever the command failed.
+    Combined -> stdout and stderr interleaved, plus the status line on failure.
+ */
+#[derive(Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputDisplay {
+    Stdout,
+    #[default]
+    Status,
+    Combined,
+}
+
+impl TaskResult {
+    /// The result of a command that couldn't even be started.
+    pub fn spawn_failure(error: String, timestamp: SystemTime) -> TaskResult {
+        TaskResult {
+            stdout: String::new(),
+            stderr: String::new(),
+            interleaved: String::new(),
+            exit_code: None,
+            duration: Duration::from_millis(0),
+            timestamp,
+            timed_out: None,
+            error: Some(error),
+            running: false,
+        }
+    }
+
+    /// Content set from outside (e.g. over the control socket), rather than by running a command.
+    pub fn from_text(text: String, timestamp: SystemTime) -> TaskResult {
+        TaskResult {
+            stdout: text.clone(),
+            stderr: String::new(),
+            interleaved: text,
+            exit_code: Some(0),
+            duration: Duration::from_millis(0),
+            timestamp,
+            timed_out: None,
+            error: None,
+            running: false,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        (self.running || self.exit_code == Some(0)) && self.timed_out.is_none() && self.error.is_none()
+    }
+
+    /***
+     * This run's output, below what `earlier` runs left - down to the last `max_lines` lines.
+     * Anything else (e.g. how it exited) is this run's alone.
+     ***/
+    pub fn after(self, earlier: &TaskResult, max_lines: usize) -> TaskResult {
+        TaskResult {
+            stdout: last_lines(&earlier.stdout, &self.stdout, max_lines),
+            stderr: last_lines(&earlier.stderr, &self.stderr, max_lines),
+            interleaved: last_lines(&earlier.interleaved, &self.interleaved, max_lines),
+            ..self
+        }
+    }
+
+    pub fn display(&self, display: OutputDisplay) -> String {
+        let text = match display {
+            OutputDisplay::Combined => &self.interleaved,
+            OutputDisplay::Stdout | OutputDisplay::Status => &self.stdout,
+        };
+
+        // Timeouts and spawn errors are always flagged - otherwise the widget just looks stuck.
+        if let Some(error) = &self.error {
+            return format!("{}[couldn't run: {}]{}", termion::color::Red.fg_str(), error, termion::color::Reset.fg_str());
+        }
+
+        let status = match (display, self.timed_out, self.exit_code) {
+            (_, Some(timeout), _) => format!("[timed out after {:?}]", timeout),
+            (OutputDisplay::Stdout, None, _) => return text.clone(),
+            (_, None, _) if self.running => return text.clone(),
+            (_, None, Some(0)) => return text.clone(),
+            (_, None, Some(code)) => format!("[exit {}]", code),
+            (_, None, None) => "[killed]".to_string(),
+        };
+
+        let separator = if text.is_empty() || text.ends_with('\n') { "" } else { "\n" };
+        format!("{}{}{}{}{}", text, separator, termion::color::Red.fg_str(), status, termion::color::Reset.fg_str())
+    }
+}
+
+// The last `max_lines` lines of `earlier` followed by `later`.
+fn last_lines(earlier: &str, later: &str, max_lines: usize) -> String {
+    let lines = earlier.lines().chain(later.lines()).collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(max_lines)..].join("\n")
+}
+
+/***
+StreamBuffer: The last `max_lines` lines a stream widget's command has printed.
+    Kept across restarts, so the view doesn't go blank whenever the command does.
+ */
+pub struct StreamBuffer {
+    stdout: VecDeque<String>,
+    stderr: VecDeque<String>,
+    interleaved: VecDeque<String>,
+    max_lines: usize,
+}
+
+impl StreamBuffer {
+    pub fn new(max_lines: usize) -> StreamBuffer {
+        StreamBuffer { stdout: VecDeque::new(), stderr: VecDeque::new(), interleaved: VecDeque::new(), max_lines }
+    }
+
+    pub fn push(&mut self, line: StreamLine) {
+        let (text, lines) = match line {
+            StreamLine::Stdout(text) => (text, &mut self.stdout),
+            StreamLine::Stderr(text) => (text, &mut self.stderr),
+        };
+
+        for buffer in [lines, &mut self.interleaved] {
+            buffer.push_back(text.clone());
+            if buffer.len() > self.max_lines { buffer.pop_front(); }
+        }
+    }
+
+    /// The buffer as the result of a command that started at `started` and is still running.
+    pub fn result(&self, started: SystemTime) -> TaskResult {
+        let join = |lines: &VecDeque<String>| lines.iter().cloned().collect::<Vec<String>>().join("\n");
+        TaskResult {
+            stdout: join(&self.stdout),
+            stderr: join(&self.stderr),
+            interleaved: join(&self.interleaved),
+            exit_code: None,
+            duration: started.elapsed().unwrap_or_default(),
+            timestamp: started,
+            timed_out: None,
+            error: None,
+            running: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn succeeded(text: &str) -> TaskResult {
+        TaskResult {
+            stdout: text.to_string(),
+            stderr: String::new(),
+            interleaved: text.to_string(),
+            exit_code: Some(0),
+            duration: Duration::from_millis(0),
+            timestamp: SystemTime::now(),
+            timed_out: None,
+            error: None,
+            running: false,
+        }
+    }
+
+    fn failed() -> TaskResult {
+        TaskResult {
+            stdout: "some output\n".to_string(),
+            stderr: "a warning\n".to_string(),
+            interleaved: "a warning\nsome output\n".to_string(),
+            exit_code: Some(2),
+            ..succeeded("")
+        }
+    }
+
+    #[test]
+    fn stdout_display_ignores_failures() {
+        assert_eq!(failed().display(OutputDisplay::Stdout), "some output\n");
+    }
+
+    #[test]
+    fn status_display_flags_failures() {
+        assert_eq!(failed().display(OutputDisplay::Status), "some output\n\u{1b}[38;5;1m[exit 2]\u{1b}[39m");
+    }
+
+    #[test]
+    fn status_display_is_plain_on_success() {
+        assert_eq!(succeeded("all good").display(OutputDisplay::Status), "all good");
+    }
+
+    #[test]
+    fn combined_display_interleaves_stderr() {
+        assert_eq!(failed().display(OutputDisplay::Combined), "a warning\nsome output\n\u{1b}[38;5;1m[exit 2]\u{1b}[39m");
+    }
+
+    #[test]
+    fn spawn_failures_are_always_shown() {
+        let result = TaskResult::spawn_failure("No such file or directory".to_string(), SystemTime::now());
+        assert!(!result.succeeded());
+        assert_eq!(result.display(OutputDisplay::Stdout), "\u{1b}[38;5;1m[couldn't run: No such file or directory]\u{1b}[39m");
+    }
+
+    #[test]
+    fn timeouts_are_always_shown() {
+        let result = TaskResult { timed_out: Some(Duration::from_secs(5)), exit_code: None, ..succeeded("partial") };
+        assert_eq!(result.display(OutputDisplay::Stdout), "partial\n\u{1b}[38;5;1m[timed out after 5s]\u{1b}[39m");
+    }
+
+    #[test]
+    fn scrollback_keeps_the_latest_lines_of_every_run() {
+        let result = succeeded("three\nfour\n").after(&succeeded("one\ntwo\n"), 3);
+        assert_eq!(result.stdout, "two\nthree\nfour");
+        assert_eq!(result.interleaved, "two\nthree\nfour");
+
+        let result = failed().after(&result, 3);
+        assert_eq!(result.display(OutputDisplay::Status), "three\nfour\nsome output\n\u{1b}[38;5;1m[exit 2]\u{1b}[39m");
+    }
+
+    #[test]
+    fn running_streams_are_shown_as_is() {
+        let mut buffer = StreamBuffer::new(5);
+        buffer.push(StreamLine::Stdout("tick".to_string()));
+        let result = buffer.result(SystemTime::now());
+        assert!(result.succeeded());
+        assert_eq!(result.display(OutputDisplay::Status), "tick");
+    }
+
+    #[test]
+    fn stream_buffer_keeps_the_latest_lines() {
+        let mut buffer = StreamBuffer::new(2);
+        buffer.push(StreamLine::Stdout("one".to_string()));
+        buffer.push(StreamLine::Stderr("uh oh".to_string()));
+        buffer.push(StreamLine::Stdout("two".to_string()));
+        buffer.push(StreamLine::Stdout("three".to_string()));
+
+        let result = buffer.result(SystemTime::now());
+        assert_eq!(result.stdout, "two\nthree");
+        assert_eq!(result.stderr, "uh oh");
+        assert_eq!(result.interleaved, "two\nthree");
+    }
+}
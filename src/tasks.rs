@@ -1,18 +1,88 @@
 use std::fs::File;
 use std::io::Read;
+use std::path::Path;
 use std::fmt;
 use serde::Deserialize;
 use std::ops::Deref;
 use fmt::Formatter;
+use termion::event::Key;
+use crate::hexterm::keys::parse_key;
+use crate::task_result::OutputDisplay;
+use crate::views::{CharDims, DimConstraint};
+use crate::views::decoration::Border;
+use crate::views::Overflow;
+use crate::validation::{validate, ConfigError};
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, PartialEq)]
 pub struct Config {
     pub widgets: Vec<Widget>,
+    #[serde(default)]
     pub apps: Vec<App>,
     pub layout: Layout,
+    #[serde(default)]
+    pub keys: Keys,
+    #[serde(default)]
+    pub terminal: TerminalOptions,
 }
 
-#[derive(Deserialize, Clone)]
+/***
+Keys: hex's own key bindings, from the optional [keys] table.
+    escape    -> Leaves passthrough mode, so keys go back to the prompt rather than the open app.
+    next_page -> At the prompt, turns every stack to its next page.
+    prev_page -> ...and back again.
+ */
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct Keys {
+    #[serde(default = "default_escape")]
+    pub escape: String,
+    #[serde(default = "default_next_page")]
+    pub next_page: String,
+    #[serde(default = "default_prev_page")]
+    pub prev_page: String,
+}
+
+fn default_escape() -> String { "ctrl-]".to_string() }
+fn default_next_page() -> String { "tab".to_string() }
+fn default_prev_page() -> String { "backtab".to_string() }
+
+impl Default for Keys {
+    fn default() -> Keys { Keys { escape: default_escape(), next_page: default_next_page(), prev_page: default_prev_page() } }
+}
+
+impl Keys {
+    pub fn escape_key(&self) -> Key {
+        parse_key(&self.escape).unwrap_or(Key::Ctrl('5'))
+    }
+
+    pub fn next_page_key(&self) -> Key {
+        parse_key(&self.next_page).unwrap_or(Key::Char('\t'))
+    }
+
+    pub fn prev_page_key(&self) -> Key {
+        parse_key(&self.prev_page).unwrap_or(Key::BackTab)
+    }
+}
+
+/***
+TerminalOptions: The optional [terminal] table.
+    min_width, min_height -> The smallest terminal the layout's usable in. Below that, hex
+                             shows a "terminal too small" notice instead of the layout.
+ */
+#[derive(Deserialize, Clone, Default, PartialEq)]
+pub struct TerminalOptions {
+    #[serde(default)]
+    pub min_width: usize,
+    #[serde(default)]
+    pub min_height: usize,
+}
+
+impl TerminalOptions {
+    pub fn min_size(&self) -> CharDims {
+        CharDims::new(self.min_width, self.min_height)
+    }
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
 pub struct App {
     pub id: String,
     pub name: String,
@@ -21,35 +91,144 @@ pub struct App {
     pub command: String
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, PartialEq)]
 pub struct Widget {
     pub id: String,
     pub name: String,
     pub description: String,
+    #[serde(default)]
     pub path: String,
-    pub command: String,
-    pub period: String,
+    pub command: Option<CommandLine>,
+    pub period: Option<String>,
+    pub align: Option<bool>,
+    pub schedule: Option<String>,
+    pub shell: Option<String>,
+    pub timeout: Option<String>,
+    #[serde(default)]
+    pub display: OutputDisplay,
+    #[serde(default)]
+    pub mode: WidgetMode,
+    pub max_lines: Option<usize>,
+    pub scrollback: Option<usize>,
+}
+
+/***
+WidgetMode: How a widget's command is run.
+    Poll   -> Run it every period (or on its schedule), replacing the view with each run's output.
+    Stream -> Start it once and leave it running, adding each line it prints to the view.
+              It's restarted, with backoff, whenever it exits.
+    Push   -> No command at all - other programs set its content over the control socket.
+ */
+#[derive(Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WidgetMode {
+    #[default]
+    Poll,
+    Stream,
+    Push,
+}
+
+/***
+CommandLine: What a widget runs.
+    Shell(s) -> A command line handed to the widget's shell, so pipes, globs, quoting and $VARS all work.
+    Argv(v)  -> A program and its arguments, run directly without a shell.
+ */
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum CommandLine {
+    Shell(String),
+    Argv(Vec<String>),
 }
 
-#[derive(Deserialize, Clone)]
+pub const DEFAULT_SHELL: &str = "/bin/sh -c";
+
+#[derive(Deserialize, Clone, PartialEq)]
 pub struct Layout {
     pub kind: String,
     pub layout_id: Option<String>,
     pub main: Option<bool>,
     pub children: Option<Vec<Layout>>,
     pub orientation: Option<String>,
-    pub width: Option<usize>,
-    pub height: Option<usize>,
+    pub width: Option<Size>,
+    pub height: Option<Size>,
     pub task_id: Option<String>,
+    pub follow: Option<bool>,
+    pub overflow: Option<Overflow>,
+    pub border: Option<Border>,
+    pub title: Option<String>,
+    pub padding: Option<usize>,
+    pub margin: Option<usize>,
+    // A grid's row heights and column widths
+    pub rows: Option<Vec<Size>>,
+    pub cols: Option<Vec<Size>>,
+    // Where a grid's child goes
+    pub row: Option<usize>,
+    pub col: Option<usize>,
+    pub rowspan: Option<usize>,
+    pub colspan: Option<usize>,
+    // A stack's tab bar and how often it turns the page by itself
+    pub tabs: Option<bool>,
+    pub rotate: Option<String>,
+    // What a stack calls this page
+    pub name: Option<String>,
+}
+
+/***
+Size: A layout's width or height.
+    Chars(n) -> Exactly n characters, e.g. 20.
+    Spec(s)  -> "30%" of the containing layout, "2*" for two shares of the room it has left over,
+                or "5..20" for as much as the content needs, between 5 and 20 characters.
+ */
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum Size {
+    Chars(usize),
+    Spec(String),
+}
+
+impl Size {
+    pub fn constraint(&self) -> Result<DimConstraint, String> {
+        let spec = match self {
+            Size::Chars(n) => return Ok(DimConstraint::Fixed(*n)),
+            Size::Spec(spec) => spec.trim(),
+        };
+        let number = |s: &str| s.trim().parse::<usize>().ok();
+        let bad = || format!("can't understand size '{}' - expected e.g. 20, \"30%\", \"2*\" or \"5..20\"", spec);
+
+        if let Some(percent) = spec.strip_suffix('%') {
+            return match number(percent) {
+                Some(p) if p <= 100 => Ok(DimConstraint::Percent(p)),
+                Some(_) => Err(format!("'{}' is more than 100%", spec)),
+                None => Err(bad()),
+            };
+        }
+        if let Some(weight) = spec.strip_suffix('*') {
+            return match number(weight) {
+                Some(0) => Err(format!("'{}' has no weight - use at least \"1*\"", spec)),
+                Some(w) => Ok(DimConstraint::Weight(w)),
+                None => Err(bad()),
+            };
+        }
+        if let Some((least, most)) = spec.split_once("..") {
+            return match (number(least), number(most)) {
+                (Some(l), Some(m)) if l <= m => Ok(DimConstraint::Between(l, m)),
+                (Some(_), Some(_)) => Err(format!("'{}' has its limits the wrong way round", spec)),
+                _ => Err(bad()),
+            };
+        }
+        number(spec).map(DimConstraint::Fixed).ok_or_else(bad)
+    }
 }
 
 impl Layout {
 
     pub fn to_str(&self, depth: usize) -> Option<String> {
-        let mut out = String::from(format!("{:indent$}{}", "", self.kind.clone(), indent=depth*2));
+        let mut out = format!("{:indent$}{}", "", self.kind.clone(), indent=depth*2);
         match self.kind.deref() {
             "linearlayout" => { out += format!(" ({})\n", self.orientation.as_ref().unwrap_or(&String::from("unknown"))).as_ref() },
             "textview" => { out += format!(" ({})\n", self.task_id.as_ref().unwrap_or(&String::from(""))).as_ref() }
+            "grid" => { out += format!(" ({} children)\n", self.children.as_ref().map_or(0, |c| c.len())).as_ref() },
+            "stack" => { out += format!(" ({} pages)\n", self.children.as_ref().map_or(0, |c| c.len())).as_ref() },
             "panel" => { out+= format!(" ({} children)\n", self.children.as_ref().unwrap_or(Vec::new().as_ref()).len()).as_ref() },
             _ => { out += "Unknown" }
         }
@@ -72,41 +251,22 @@ impl fmt::Display for Layout {
     }
 }
 
-pub fn load_task_config() -> Option<Config> {
-    let mut tasks_file = File::open("config/tasks.toml").unwrap();
+pub fn load_task_config(path: &Path) -> Result<Config, Vec<ConfigError>> {
+    let file_name = path.display().to_string();
     let mut toml_tasks = String::new();
-    tasks_file.read_to_string(&mut toml_tasks).unwrap();
-    let config = toml::from_str(&toml_tasks);
-
-    match config {
-        Ok(conf) => {
-            let conf = populate_layout_ids(conf)?;
-            match how_many_mains(&conf.layout) {
-                0 => { panic!("No 'main' layout! Mark one of your textviews as being 'main'"); },
-                1 => { Some(conf) }, // perfect!
-                _ => { panic!("More than one 'main' textview in tasks.toml!"); }
-            }
-        },
-        Err(err) => {
-            println!("conf err: {}", err);
-            None
-        }
-    }
+    File::open(path)
+        .and_then(|mut tasks_file| tasks_file.read_to_string(&mut toml_tasks))
+        .map_err(|err| vec![ConfigError::new(&file_name, err.to_string())])?;
+
+    let conf: Config = toml::from_str(&toml_tasks)
+        .map_err(|err| vec![ConfigError::new(&file_name, err.to_string())])?;
+
+    let errors = validate(&conf);
+    if !errors.is_empty() { return Err(errors); }
+
+    populate_layout_ids(conf).ok_or_else(|| vec![ConfigError::new(&file_name, "couldn't assign layout ids".to_string())])
 }
 
 pub fn populate_layout_ids(conf: Config) -> Option<Config> {
     Some(conf)
 }
-
-pub fn how_many_mains(l: &Layout) -> usize {
-    let main_children = match &l.children {
-        Some(children) => { children.iter().map(|c| how_many_mains(c)).sum() },
-        None => 0
-    };
-
-    let total_mains = if l.main.unwrap_or(false) { 1 } else { 0 } + main_children;
-
-    if l.main.unwrap_or(false) && l.kind != "textview".to_string() { panic!("only textview's can be 'main'"); }
-
-    total_mains
-}
\ No newline at end of file
@@ -0,0 +1,195 @@
+use crate::hexterm::TaskId;
+use crate::terminal::WindowMap;
+use crate::views::ViewId;
+
+/***
+Bindings: Which view shows which task, kept apart from the views themselves.
+    windows    -> The view showing each task that's on screen.
+    main_task  -> The task the 'main' view's showing.
+    main_owner -> While another task's borrowed the 'main' view, the one it belongs to.
+    focused    -> The task whose view scrolls. The 'main' view's, unless `focus` picked another.
+    Moving a task to another view takes `main_task` and `focused` along with the view.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bindings {
+    pub windows: WindowMap,
+    pub main_task: Option<TaskId>,
+    pub main_owner: Option<TaskId>,
+    pub focused: Option<TaskId>,
+}
+
+impl Bindings {
+    pub fn new(windows: WindowMap, main_task: Option<TaskId>) -> Bindings {
+        Bindings { windows, main_task, main_owner: None, focused: None }
+    }
+
+    /***
+     * Show `task_id` in the view showing `target` - a task id, or "main". If `task_id` was already
+     * showing somewhere else, the two views swap. Returns the task `target`'s view was showing.
+     * `app_open` is whether an app's using the 'main' view, in which case it can't change hands.
+     * Giving the 'main' view to a new task for good forgets whoever it was borrowed from.
+     ***/
+    pub fn assign(&mut self, task_id: &TaskId, target: &str, app_open: bool) -> Result<TaskId, String> {
+        let main_task = self.main_task.clone();
+        let was = self.rebind(task_id, target, app_open)?;
+        if self.main_task != main_task { self.main_owner = None; }
+        Ok(was)
+    }
+
+    /// Show `task_id` in the 'main' view until `restore_main`. Returns the task it was showing.
+    pub fn borrow_main(&mut self, task_id: &TaskId, app_open: bool) -> Result<TaskId, String> {
+        let owner = self.main_owner.clone().or_else(|| self.main_task.clone());
+        let was = self.rebind(task_id, "main", app_open)?;
+        self.main_owner = owner.filter(|owner| owner != task_id);
+        Ok(was)
+    }
+
+    /***
+     * Give the 'main' view back to the task it belongs to after `borrow_main` - however many others
+     * have borrowed it in the meantime. Returns that task and the one it took the view back from.
+     ***/
+    pub fn restore_main(&mut self, app_open: bool) -> Option<(TaskId, TaskId)> {
+        let owner = self.main_owner.take()?;
+        let was = self.rebind(&owner, "main", app_open).ok()?;
+        Some((owner, was))
+    }
+
+    /// The 'main' view, if it's on screen and no app's using it.
+    pub fn main_view(&self, app_open: bool) -> Result<ViewId, String> {
+        let main_task = self.main_task.as_ref().ok_or_else(|| "there's no 'main' view".to_string())?;
+        if app_open { return Err("an app is using the main view - close it first".to_string()); }
+        self.windows.get(main_task).cloned().ok_or_else(|| "nothing on screen shows 'main'".to_string())
+    }
+
+    // See `assign`. Whether `task_id` is a widget at all is for the caller to check.
+    fn rebind(&mut self, task_id: &TaskId, target: &str, app_open: bool) -> Result<TaskId, String> {
+        let target_task = match target {
+            "main" => self.main_task.clone().ok_or_else(|| "there's no 'main' view".to_string())?,
+            _ => target.to_string(),
+        };
+        let main_task = self.main_task.as_ref();
+        if app_open && (main_task == Some(&target_task) || main_task == Some(task_id)) {
+            return Err("an app is using the main view - close it first".to_string());
+        }
+
+        let view_id = match self.windows.get(&target_task) {
+            Some(view_id) => view_id.clone(),
+            None => return Err(format!("nothing on screen shows '{}'", target)),
+        };
+        if &target_task == task_id { return Ok(target_task); }
+
+        self.windows.remove(&target_task);
+        let swapped = match self.windows.insert(task_id.clone(), view_id) {
+            Some(other_view) => {
+                self.windows.insert(target_task.clone(), other_view);
+                true
+            },
+            None => false,
+        };
+        // Whatever was showing in a view that's changed hands is now what's showing there instead.
+        let follow = |shown: &mut Option<TaskId>| {
+            if shown.as_ref() == Some(&target_task) {
+                *shown = Some(task_id.clone());
+            } else if swapped && shown.as_ref() == Some(task_id) {
+                *shown = Some(target_task.clone());
+            }
+        };
+        follow(&mut self.main_task);
+        follow(&mut self.focused);
+
+        Ok(target_task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> TaskId { s.to_string() }
+
+    // 'clock' is in the 'main' view, 'weather' in another, and 'todo' isn't on screen.
+    fn bindings() -> Bindings {
+        let windows = [("clock", "v1"), ("weather", "v2")].iter().map(|(t, v)| (id(t), id(v))).collect();
+        Bindings::new(windows, Some(id("clock")))
+    }
+
+    #[test]
+    fn tasks_already_showing_swap_views() {
+        let mut b = bindings();
+        b.focused = Some(id("weather"));
+        assert_eq!(b.assign(&id("weather"), "clock", false), Ok(id("clock")));
+
+        assert_eq!(b.windows.get("weather"), Some(&id("v1")));
+        assert_eq!(b.windows.get("clock"), Some(&id("v2")));
+        assert_eq!(b.main_task, Some(id("weather")));
+        assert_eq!(b.focused, Some(id("clock")));
+    }
+
+    #[test]
+    fn a_task_not_showing_takes_the_view_over() {
+        let mut b = bindings();
+        b.focused = Some(id("weather"));
+        assert_eq!(b.assign(&id("todo"), "weather", false), Ok(id("weather")));
+
+        assert_eq!(b.windows.get("todo"), Some(&id("v2")));
+        assert_eq!(b.windows.get("weather"), None);
+        assert_eq!(b.main_task, Some(id("clock")));
+        assert_eq!(b.focused, Some(id("todo")));
+    }
+
+    #[test]
+    fn only_views_on_screen_can_be_targets() {
+        let mut b = bindings();
+        assert_eq!(b.assign(&id("clock"), "todo", false), Err("nothing on screen shows 'todo'".to_string()));
+        assert_eq!(b, bindings());
+
+        b.main_task = Some(id("gone"));
+        assert_eq!(b.assign(&id("clock"), "main", false), Err("nothing on screen shows 'main'".to_string()));
+    }
+
+    #[test]
+    fn borrowing_main_remembers_its_owner_until_restored() {
+        let mut b = bindings();
+        assert_eq!(b.borrow_main(&id("todo"), false), Ok(id("clock")));
+        assert_eq!(b.borrow_main(&id("weather"), false), Ok(id("todo")));
+        assert_eq!(b.main_task, Some(id("weather")));
+        assert_eq!(b.main_owner, Some(id("clock")));
+
+        assert_eq!(b.restore_main(false), Some((id("clock"), id("weather"))));
+        assert_eq!(b.main_task, Some(id("clock")));
+        assert_eq!(b.windows.get("clock"), Some(&id("v1")));
+        assert_eq!(b.main_owner, None);
+        assert_eq!(b.restore_main(false), None);
+    }
+
+    #[test]
+    fn assigning_main_for_good_forgets_its_owner() {
+        let mut b = bindings();
+        b.borrow_main(&id("todo"), false).unwrap();
+        b.assign(&id("weather"), "main", false).unwrap();
+        assert_eq!(b.main_owner, None);
+    }
+
+    #[test]
+    fn the_main_view_is_only_there_if_its_on_screen_and_free() {
+        let mut b = bindings();
+        assert_eq!(b.main_view(false), Ok(id("v1")));
+        assert_eq!(b.main_view(true), Err("an app is using the main view - close it first".to_string()));
+
+        b.main_task = Some(id("todo"));
+        assert_eq!(b.main_view(false), Err("nothing on screen shows 'main'".to_string()));
+        b.main_task = None;
+        assert_eq!(b.main_view(false), Err("there's no 'main' view".to_string()));
+    }
+
+    #[test]
+    fn the_main_view_cant_change_hands_while_an_app_uses_it() {
+        let mut b = bindings();
+        let in_use = Err("an app is using the main view - close it first".to_string());
+        assert_eq!(b.borrow_main(&id("todo"), true), in_use);
+        assert_eq!(b.assign(&id("clock"), "weather", true), in_use);
+        assert_eq!(b, bindings());
+
+        assert_eq!(b.assign(&id("todo"), "weather", true), Ok(id("weather")));
+    }
+}
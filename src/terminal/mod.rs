@@ -1,42 +1,294 @@
-use crate::tasks::Layout;
-use crate::views::{View, Widget, DimConstraint, Orientation, LinearLayout, ViewId, CharDims, TermLocation};
+use crate::tasks::{Layout, Size, Widget as TaskWidget};
+use crate::task_result::{OutputDisplay, TaskResult};
+use crate::views::{View, Widget, DimConstraint, Orientation, LinearLayout, GridLayout, GridCell, StackLayout, Page, ViewId, CharDims, TermLocation, Scroll};
+use crate::views::window::Window;
+use crate::views::decoration::{Border, Decoration};
+use crate::views::screen::{Screen, Cell};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use log::{trace, info};
 
 extern crate termion;
 
 use std::io::{Write, stdout, Stdout};
 use self::termion::raw::{IntoRawMode, RawTerminal};
-use self::termion::{style, terminal_size};
-use crate::hexterm::formatting::{Vt100Formatter, TextFormatter};
+use self::termion::{clear, cursor, terminal_size};
+use crate::hexterm::formatting::Vt100Formatter;
 use crate::hexterm::TaskId;
+use crate::executable_command::parse_period;
+use self::bindings::Bindings;
+
+mod bindings;
 
 pub type WindowMap = HashMap<TaskId, ViewId>;
-type TaskStore = HashMap<TaskId, String>;
+type TaskStore = HashMap<TaskId, TaskResult>;
+type DisplayMap = HashMap<TaskId, OutputDisplay>;
+type NameMap = HashMap<TaskId, String>;
 
 pub struct Terminal {
-    pub windows: WindowMap,
-    formatter: Box<dyn TextFormatter>,
+    // Which view shows which task, and which ones the 'main' and focused views show.
+    bindings: Bindings,
     root: Box<dyn View>,
     tasks: TaskStore,
+    displays: DisplayMap,
+    // Each widget's name, for the titles of views that show it.
+    names: NameMap,
+    // An app drawing over the 'main' view, if one's open.
+    pane: Option<Window>,
+    // The prompt line, drawn over the bottom of everything else.
+    prompt: String,
+    // The views as they were last rendered - reused until one of them changes.
+    views: Screen,
+    // What the terminal's showing right now, so each update only draws what's changed.
+    front: Screen,
+    cursor_shown: bool,
+    // Anything smaller gets a notice rather than the layout.
+    min_size: CharDims,
     stdout: RawTerminal<Stdout>
 }
 
 impl Terminal {
 
-    pub fn new(layout: &Layout) -> Terminal {
+    pub fn new(layout: &Layout, widgets: &[TaskWidget], min_size: CharDims) -> Terminal {
         let mut windows = WindowMap::new();
         let tasks = TaskStore::new();
-        let root = construct_layout(layout, &mut windows, TermLocation::new(1, 1));
+        let displays = display_map(widgets);
+        let names = name_map(widgets);
+        let root = construct_layout(layout, widgets, &mut windows, TermLocation::new(1, 1));
         let stdout = stdout().into_raw_mode().unwrap();
-        let formatter = Box::new(Vt100Formatter {});
+        let main_task = find_main_task(layout);
+
+        Terminal {  bindings: Bindings::new(windows, main_task), tasks, displays, names, root, stdout, pane: None,
+                    prompt: String::new(), views: Screen::new(CharDims::new(0, 0)), front: Screen::new(CharDims::new(0, 0)),
+                    cursor_shown: false, min_size }
+    }
+
+    /***
+     * Throw away the current View tree and build a new one from `layout`.
+     * Any output we've already got for a task is put straight back into its new View.
+     ***/
+    pub fn relayout(&mut self, layout: &Layout, widgets: &[TaskWidget], min_size: CharDims) {
+        self.min_size = min_size;
+        let mut windows = WindowMap::new();
+        self.root = construct_layout(layout, widgets, &mut windows, TermLocation::new(1, 1));
+        self.displays = display_map(widgets);
+        self.names = name_map(widgets);
+        let focused = self.bindings.focused.take().filter(|task_id| windows.contains_key(task_id));
+        self.bindings = Bindings { focused, ..Bindings::new(windows, find_main_task(layout)) };
+
+        let cached = self.tasks.clone();
+        self.store_output(cached);
+
+        self.clear();
+        self.update_screen();
+    }
+
+    /***
+     * The terminal's changed size, so start again from a clear screen, and refit any open
+     * app to the 'main' view's new area. Returns the app's new size, if it's changed,
+     * so the app can be told.
+     ***/
+    pub fn handle_resize(&mut self) -> Option<CharDims> {
+        let (width, height) = terminal_size().unwrap();
+        info!("Terminal resized to {}x{}", width, height);
+        self.clear();
+        self.update_screen();
+
+        self.pane.as_ref()?;
+        let (location, size) = self.main_area()?;
+        if !self.pane.as_mut()?.fit(location, size) { return None; }
+        self.update_screen();
+        Some(size)
+    }
 
-        Terminal {  windows, tasks, root, stdout, formatter }
+    /// Wipe the terminal, so the next update draws everything afresh.
+    fn clear(&mut self) {
+        write!(self.stdout, "{}", clear::All).unwrap();
+        self.front = Screen::new(self.front.size());
     }
 
-    // TODO: Alter the task ID assigned to a View. This is altering
-    //       the Layout... might need to keep it after all?
-    //  pub fn assign_task_to_view(task_id, view)
+    /***
+     * Show some text in the 'main' view, in place of its task's output.
+     * It stays there until that task next produces output. An error says why it can't be shown.
+     ***/
+    pub fn show_in_main(&mut self, text: &str) -> Result<(), String> {
+        let view_id = self.bindings.main_view(self.pane.is_some())?;
+        set_view_content(&view_id, &mut self.root, text);
+        self.update_screen();
+        Ok(())
+    }
+
+    /***
+     * Hand the 'main' view's area over to an app. Returns the size of the area, or None
+     * if there's no main view to put it in. Until `close_pane`, the main view's own content
+     * is kept off the screen, and anything else hex draws leaves the app's cursor alone.
+     ***/
+    pub fn open_pane(&mut self) -> Option<CharDims> {
+        let (location, size) = self.main_area()?;
+        self.pane = Some(Window::new(location, size));
+        self.update_screen();
+        Some(size)
+    }
+
+    /// Draw output from the app in the pane. Returns any replies the app's waiting for.
+    pub fn show_in_pane(&mut self, output: String) -> String {
+        let replies = match self.pane.as_mut() {
+            Some(pane) => {
+                pane.push(output);
+                pane.replies()
+            },
+            None => return String::new(),
+        };
+        self.update_screen();
+        replies
+    }
+
+    /// Give the pane's area back to the 'main' view, redrawing whatever it was showing.
+    pub fn close_pane(&mut self) {
+        if self.pane.take().is_some() {
+            if let Some(main_task) = self.bindings.main_task.clone() {
+                let cached = self.tasks.get(&main_task).cloned().map(|result| (main_task, result));
+                self.store_output(cached.into_iter().collect());
+            }
+            self.update_screen();
+        }
+    }
+
+    // Where the 'main' view is, and the room it has. None if there isn't one, or it's got no room.
+    fn main_area(&mut self) -> Option<(TermLocation, CharDims)> {
+        let view_id = self.bindings.main_task.as_ref().and_then(|task_id| self.bindings.windows.get(task_id))?.clone();
+        let (location, size) = find_view(&view_id, &mut self.root).map(|v| v.content_area())?;
+        if size.width == 0 || size.height == 0 { return None; }
+        Some((location, size))
+    }
+
+    /// Make `task_id`'s view the one that scrolls. False if no view shows that task.
+    pub fn focus(&mut self, task_id: &TaskId) -> bool {
+        if !self.bindings.windows.contains_key(task_id) { return false; }
+        self.bindings.focused = Some(task_id.clone());
+        true
+    }
+
+    pub fn focused(&self) -> Option<&TaskId> {
+        self.bindings.focused.as_ref().or(self.bindings.main_task.as_ref())
+    }
+
+    /// Scroll the focused view's content.
+    pub fn scroll(&mut self, scroll: Scroll) {
+        let task_id = match self.focused() {
+            Some(task_id) => task_id.clone(),
+            None => return,
+        };
+        // Under an open app, the main view's not drawn at all.
+        if self.pane.is_some() && self.bindings.main_task.as_ref() == Some(&task_id) { return; }
+
+        if let Some(view) = self.bindings.windows.get(&task_id).cloned().and_then(|view_id| find_view(&view_id, &mut self.root)) {
+            view.scroll(scroll);
+            self.update_screen();
+        }
+    }
+
+    /***
+     * Turn every stack that has `page` to it. False if none did - or if an app's open, since
+     * that's using the 'main' view's space and hiding it would leave the app nowhere to be.
+     ***/
+    pub fn turn_page(&mut self, page: &Page) -> bool {
+        if self.pane.is_some() || !turn_pages(&mut self.root, page) { return false; }
+        self.update_screen();
+        true
+    }
+
+    /// Turn any rotating stack that's due to its next page, and move along any marquee that's due.
+    pub fn tick(&mut self) {
+        if self.pane.is_some() { return; }
+
+        let now = Instant::now();
+        let turned = turn_pages(&mut self.root, &Page::Rotate(now));
+        if tick(&mut self.root, now) || turned {
+            self.update_screen();
+        }
+    }
+
+    /// When the next stack's due to turn its page, or marquee to move - never, while an app's open.
+    pub fn next_turn(&mut self) -> Option<Instant> {
+        if self.pane.is_some() { return None; }
+        next_turn(&mut self.root)
+    }
+
+    /// Drop the cached output for a task, e.g. because its definition has changed.
+    pub fn forget(&mut self, task_id: &TaskId) {
+        self.tasks.remove(task_id);
+    }
+
+    /***
+     * Show `task_id` in the view that's showing `target` - a task id, or "main" - and put that
+     * task's last output straight into it. If `task_id` is already showing somewhere else, the
+     * two views swap tasks. It stays like that until the next reload - if that gives the 'main'
+     * view a new task, that's its task from now on, even if it was only borrowed before.
+     ***/
+    pub fn assign_task_to_view(&mut self, task_id: &TaskId, target: &TaskId) -> Result<(), String> {
+        self.check_widget(task_id)?;
+        let was = self.bindings.assign(task_id, target, self.pane.is_some())?;
+        self.rebound(task_id, &was);
+        Ok(())
+    }
+
+    /***
+     * Show `task_id` in the 'main' view for now. `restore_main` gives the view back to the
+     * task it belongs to - however many others have borrowed it in the meantime.
+     ***/
+    pub fn borrow_main(&mut self, task_id: &TaskId) -> Result<(), String> {
+        self.check_widget(task_id)?;
+        let was = self.bindings.borrow_main(task_id, self.pane.is_some())?;
+        self.rebound(task_id, &was);
+        Ok(())
+    }
+
+    /// Put the 'main' view's own task back after `borrow_main`. Returns that task, if it was borrowed.
+    pub fn restore_main(&mut self) -> Option<TaskId> {
+        let (owner, was) = self.bindings.restore_main(self.pane.is_some())?;
+        self.rebound(&owner, &was);
+        Some(owner)
+    }
+
+    /// The task the 'main' view's been borrowed from, if it has been.
+    pub fn main_owner(&self) -> Option<&TaskId> {
+        self.bindings.main_owner.as_ref()
+    }
+
+    fn check_widget(&self, task_id: &TaskId) -> Result<(), String> {
+        if self.displays.contains_key(task_id) { Ok(()) } else { Err(format!("no widget called '{}'", task_id)) }
+    }
+
+    // `task_id` has taken over the view that was showing `was` - and `was` may have its old one.
+    fn rebound(&mut self, task_id: &TaskId, was: &TaskId) {
+        if task_id == was { return; }
+        self.retitle(task_id, was);
+        self.retitle(was, task_id);
+        self.refresh(task_id);
+        self.refresh(was);
+        self.update_screen();
+    }
+
+    // `task_id`'s view was showing `was` - if it was titled with that widget's name, it's now this one's.
+    fn retitle(&mut self, task_id: &TaskId, was: &TaskId) {
+        let (root, names) = (&mut self.root, &self.names);
+        let view = self.bindings.windows.get(task_id).and_then(|view_id| find_view(view_id, root));
+        if let (Some(view), Some(from), Some(to)) = (view, names.get(was), names.get(task_id)) {
+            view.retitle(from, to);
+        }
+    }
+
+    // Redraw a task's view from its cached output - or blank, if it hasn't produced any yet.
+    fn refresh(&mut self, task_id: &TaskId) {
+        let view_id = match self.bindings.windows.get(task_id) {
+            Some(view_id) => view_id.clone(),
+            None => return,
+        };
+        let display = self.displays.get(task_id).cloned().unwrap_or_default();
+        let text = self.tasks.get(task_id).map(|result| result.display(display)).unwrap_or_default();
+        set_view_content(&view_id, &mut self.root, &text);
+    }
 
     /***
      * Print the current display to the screen
@@ -44,25 +296,32 @@ impl Terminal {
      * TODO: The rendering here will differ a bit for Interactive processes.
      *        I guess we'll need to know if we're interactive or not in here.
      ***/
-    pub fn update(&mut self, output: HashMap<String, String>) {
+    pub fn update(&mut self, output: HashMap<TaskId, TaskResult>) {
         self.store_output(output);
-        self.update_screen();
+        // Output that's the same as last time, or for a task that isn't on screen, changes nothing.
+        if self.root.dirty() {
+            self.update_screen();
+        }
     }
 
-    fn store_output(&mut self, output: HashMap<String, String>) {
-        output.iter().for_each(|(task_id, text)| {
+    fn store_output(&mut self, output: HashMap<TaskId, TaskResult>) {
+        output.iter().for_each(|(task_id, result)| {
             // Store the output for later swapping into/out of a Window
-            self.tasks.insert(task_id.clone(), text.clone());
+            self.tasks.insert(task_id.clone(), result.clone());
+
+            // An app's using the main view's space - leave it be until it's done.
+            if self.pane.is_some() && self.bindings.main_task.as_ref() == Some(task_id) { return; }
 
             // Check - if a Window is displaying this task, update its associated View.
-            match self.windows.get(task_id) {
+            match self.bindings.windows.get(task_id) {
                 None => {},
                 Some(view_id) => {
                     // If there's a View with this ID, set its contents to this value.
                     match self.tasks.get(task_id) {
                         None => {},
-                        Some(task_text) => {
-                            set_view_content(view_id, &mut self.root, &task_text, &self.formatter);
+                        Some(task_result) => {
+                            let display = self.displays.get(task_id).cloned().unwrap_or_default();
+                            set_view_content(view_id, &mut self.root, &task_result.display(display));
                         }
                     }
                 }
@@ -70,27 +329,122 @@ impl Terminal {
         });
     }
 
+    /// Show `text` on the prompt line. Nothing's drawn unless it's changed.
+    pub fn set_prompt(&mut self, text: &str) {
+        if self.prompt != text {
+            self.prompt = text.to_string();
+            self.update_screen();
+        }
+    }
+
+    /***
+     * Draw the whole display into a fresh Screen - views, then any open app, then the prompt -
+     * and send the terminal just the cells that differ from what it's already showing.
+     * The views are only laid out and rendered again if one of them has changed, or the
+     * terminal's size has.
+     ***/
     fn update_screen(&mut self) {
         let (width, height) = terminal_size().unwrap();
-        self.root.inflate(&CharDims::new(width as usize, height as usize), &TermLocation::new(1, 1));
-        writeln!(self.stdout, "{}", self.root.render()).unwrap();
-        self.root.wash();
+        let size = CharDims::new(width as usize, height as usize);
+        let too_small = size.width < self.min_size.width || size.height < self.min_size.height;
+
+        if self.root.dirty() || self.views.size() != size {
+            self.root.inflate(&size, &TermLocation::new(1, 1));
+            self.views = Screen::new(size);
+            if too_small {
+                let notice = format!("terminal too small\n{}x{} needed\n{}x{} here",
+                                     self.min_size.width, self.min_size.height, size.width, size.height);
+                self.views.draw(&notice, &TermLocation::new(1, 1), &size);
+            } else {
+                self.root.render(&mut self.views);
+            }
+            self.root.wash();
+        }
+        let mut back = self.views.clone();
+
+        if let Some(pane) = self.pane.as_ref().filter(|_| !too_small) {
+            pane.draw(&mut back);
+        }
+
+        // The prompt sits on the second-to-last row, where it's always been.
+        if height > 1 && !too_small {
+            let row = height - 1;
+            back.erase(row as usize - 1, 0, size.width, Cell::default());
+            back.draw(&self.prompt, &TermLocation::new(1, row), &CharDims::new(size.width, 1));
+        }
 
-        // and reset our style back to standard. JIC.
-        writeln!(self.stdout, "{}", style::Reset).unwrap();
+        write!(self.stdout, "{}", back.diff(&self.front)).unwrap();
+        self.front = back;
+
+        // Leave the cursor where an open app put it, or out of sight.
+        match self.pane.as_ref().filter(|_| !too_small).and_then(|pane| pane.cursor()) {
+            Some(at) => {
+                write!(self.stdout, "{}", cursor::Goto(at.x, at.y)).unwrap();
+                if !self.cursor_shown { write!(self.stdout, "{}", cursor::Show).unwrap(); }
+                self.cursor_shown = true;
+            },
+            None => {
+                if self.cursor_shown { write!(self.stdout, "{}", cursor::Hide).unwrap(); }
+                self.cursor_shown = false;
+            },
+        }
 
         self.stdout.flush().unwrap();
     }
 }
 
-fn set_view_content<'a>(id: &ViewId, view: &'a mut Box<dyn View>, text: &String, formatter: &Box<dyn TextFormatter>) -> bool {
+/// The task shown in the layout's 'main' textview, if any.
+fn find_main_task(layout: &Layout) -> Option<TaskId> {
+    if layout.main.unwrap_or(false) {
+        return layout.task_id.clone();
+    }
+
+    layout.children.as_ref()?.iter().find_map(find_main_task)
+}
+
+fn find_view<'a>(id: &ViewId, view: &'a mut Box<dyn View>) -> Option<&'a mut Box<dyn View>> {
+    if view.id().eq(id) {
+        return Some(view);
+    }
+
+    view.children().find_map(|c| find_view(id, c))
+}
+
+// Every stack gets the chance to turn, not just the first.
+fn turn_pages(view: &mut Box<dyn View>, page: &Page) -> bool {
+    let turned = view.turn_page(page);
+    view.children().fold(turned, |turned, c| turn_pages(c, page) || turned)
+}
+
+// Hidden views - e.g. on a stack's other pages - aren't moved along, or waited for.
+fn tick(view: &mut Box<dyn View>, now: Instant) -> bool {
+    if !view.visible() { return false; }
+    let ticked = view.tick(now);
+    view.children().fold(ticked, |ticked, c| tick(c, now) || ticked)
+}
+
+fn next_turn(view: &mut Box<dyn View>) -> Option<Instant> {
+    if !view.visible() { return None; }
+    let own = view.next_turn();
+    view.children().filter_map(next_turn).chain(own).min()
+}
+
+fn display_map(widgets: &[TaskWidget]) -> DisplayMap {
+    widgets.iter().map(|w| (w.id.clone(), w.display)).collect()
+}
+
+fn name_map(widgets: &[TaskWidget]) -> NameMap {
+    widgets.iter().map(|w| (w.id.clone(), w.name.clone())).collect()
+}
+
+fn set_view_content(id: &ViewId, view: &mut Box<dyn View>, text: &str) -> bool {
     if view.id().eq(id) {
-        view.update_content(text.clone());
+        view.update_content(text.to_string());
         return true;
     }
 
     view.children().any(|c|
-        set_view_content(id, c, text, formatter)
+        set_view_content(id, c, text)
     )
 }
 
@@ -98,63 +452,157 @@ fn set_view_content<'a>(id: &ViewId, view: &'a mut Box<dyn View>, text: &String,
  * Converts Layout to View
  * Pass in a Layout description at the top and it'll build the concrete View objects.
  */
-pub fn construct_layout(layout: &Layout, windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
+pub fn construct_layout(layout: &Layout, widgets: &[TaskWidget], windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
     info!("Building {}:{}", layout.kind, layout.task_id.clone().unwrap_or("".to_string()));
 
     let constructed: Box<dyn View> = match layout.kind.as_ref() {
-        "linearlayout" => build_linear_layout(&layout, windows, location),
-        "textview" => build_text_view(&layout, windows, location),
+        "linearlayout" => build_linear_layout(layout, widgets, windows, location),
+        "textview" => build_text_view(layout, widgets, windows, location),
+        "grid" => build_grid(layout, widgets, windows, location),
+        "stack" => build_stack(layout, widgets, windows, location),
         _ => panic!("Unknown layout {}", layout.kind)
     };
 
-    return constructed;
+    constructed
 }
 
-fn build_text_view(layout: &Layout, windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
-    let h_const = match layout.height {
-        Some(h) => DimConstraint::Fixed(h),
-        None => DimConstraint::WrapContent
-    };
-    let w_const = match layout.width {
-        Some(w) => DimConstraint::Fixed(w),
-        None => DimConstraint::WrapContent
+// A size that doesn't parse was already reported when the config was validated.
+fn constraint(size: &Option<Size>) -> DimConstraint {
+    size.as_ref().and_then(|s| s.constraint().ok()).unwrap_or(DimConstraint::WrapContent)
+}
+
+/***
+ * The border, title, padding and margin for a Layout. A bordered textview's title
+ * defaults to its widget's name; an empty title turns that off.
+ ***/
+fn decoration(layout: &Layout, widgets: &[TaskWidget]) -> Decoration {
+    let border = layout.border.unwrap_or_default();
+    // Only a bordered view is titled with its widget's name by default. Without a border the title
+    // takes a line of its own, which views that were sized before titles existed can't spare.
+    let default_title = || match (border, &layout.task_id) {
+        (Border::None, _) | (_, None) => None,
+        (_, Some(task_id)) => widgets.iter().find(|w| &w.id == task_id).map(|w| w.name.clone()),
     };
 
+    Decoration {
+        border,
+        title: layout.title.clone().or_else(default_title).filter(|title| !title.is_empty()),
+        padding: layout.padding.unwrap_or(0),
+        margin: layout.margin.unwrap_or(0),
+    }
+}
+
+fn build_text_view(layout: &Layout, widgets: &[TaskWidget], windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
+    let h_const = constraint(&layout.height);
+    let w_const = constraint(&layout.width);
+
     let task_id = layout.task_id.clone().unwrap_or(String::from("unknown"));
     trace!("Creating text view for {}", task_id);
-    let tv = Widget::new(w_const, h_const, Box::new(Vt100Formatter{}), location);
+    let mut tv = Widget::new(w_const, h_const, Box::new(Vt100Formatter{}), location);
+    tv.set_follow(layout.follow.unwrap_or(false));
+    tv.set_overflow(layout.overflow.unwrap_or_default());
+    tv.set_decoration(decoration(layout, widgets));
     windows.insert(task_id.clone(), tv.id());
 
     Box::new(tv)
 }
 
-fn build_linear_layout(layout: &Layout, windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
+fn build_linear_layout(layout: &Layout, widgets: &[TaskWidget], windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
     let orientation = match layout.orientation.as_ref().unwrap().as_ref() {
-        "vertical" => Orientation::VERTICAL,
-        _ => Orientation::HORIZONTAL
+        "vertical" => Orientation::Vertical,
+        _ => Orientation::Horizontal
     };
 
-    let h_const = match layout.height {
-        Some(h) => DimConstraint::Fixed(h),
-        None => DimConstraint::WrapContent
-    };
-
-    let w_const = match layout.width {
-        Some(w) => DimConstraint::Fixed(w),
-        None => DimConstraint::WrapContent
-    };
+    let h_const = constraint(&layout.height);
+    let w_const = constraint(&layout.width);
 
     let mut ll: LinearLayout = LinearLayout::new(orientation, w_const, h_const, location);
+    ll.set_decoration(decoration(layout, widgets));
 
     let mut next_child_loc = location;
     for child in layout.children.as_ref().unwrap_or(&Vec::new()) {
-        let child= construct_layout(&child, windows, next_child_loc);
+        let child= construct_layout(child, widgets, windows, next_child_loc);
         next_child_loc = match orientation {
-            Orientation::HORIZONTAL => { TermLocation::new(next_child_loc.x + child.width() as u16, next_child_loc.y) }
-            Orientation::VERTICAL => { TermLocation::new(next_child_loc.x, next_child_loc.y + child.height() as u16) }
+            Orientation::Horizontal => { TermLocation::new(next_child_loc.x + child.width() as u16, next_child_loc.y) }
+            Orientation::Vertical => { TermLocation::new(next_child_loc.x, next_child_loc.y + child.height() as u16) }
         };
         ll.add_child(child);
     }
 
     Box::new(ll)
 }
+
+fn build_grid(layout: &Layout, widgets: &[TaskWidget], windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
+    let tracks = |sizes: &Option<Vec<Size>>| sizes.iter().flatten()
+        .map(|s| s.constraint().unwrap_or(DimConstraint::WrapContent))
+        .collect::<Vec<DimConstraint>>();
+    let mut grid = GridLayout::new(tracks(&layout.rows), tracks(&layout.cols), constraint(&layout.width), constraint(&layout.height), location);
+    grid.set_decoration(decoration(layout, widgets));
+
+    for child in layout.children.as_ref().unwrap_or(&Vec::new()) {
+        let cell = GridCell {
+            row: child.row.unwrap_or(0),
+            col: child.col.unwrap_or(0),
+            rowspan: child.rowspan.unwrap_or(1).max(1),
+            colspan: child.colspan.unwrap_or(1).max(1),
+        };
+        grid.add_child(construct_layout(child, widgets, windows, location), cell);
+    }
+
+    Box::new(grid)
+}
+
+fn build_stack(layout: &Layout, widgets: &[TaskWidget], windows: &mut WindowMap, location: TermLocation) -> Box<dyn View> {
+    let mut stack = StackLayout::new(constraint(&layout.width), constraint(&layout.height), location);
+    stack.set_decoration(decoration(layout, widgets));
+    stack.set_tabs(layout.tabs.unwrap_or(false));
+    stack.set_rotation(layout.rotate.as_deref().and_then(parse_period).map(Duration::from_millis));
+
+    for (i, child) in layout.children.as_ref().unwrap_or(&Vec::new()).iter().enumerate() {
+        stack.add_child(construct_layout(child, widgets, windows, location), &page_name(child, widgets, i));
+    }
+
+    Box::new(stack)
+}
+
+// A page's own name, else its title, else its widget's name, else its number.
+fn page_name(page: &Layout, widgets: &[TaskWidget], index: usize) -> String {
+    let widget_name = || page.task_id.as_ref()
+        .and_then(|task_id| widgets.iter().find(|w| &w.id == task_id))
+        .map(|w| w.name.clone());
+
+    page.name.clone()
+        .or_else(|| page.title.clone().filter(|title| !title.is_empty()))
+        .or_else(widget_name)
+        .unwrap_or_else(|| (index + 1).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::views::Overflow;
+
+    #[test]
+    fn hidden_pages_dont_need_waking_for() {
+        let mut marquee = Widget::new(DimConstraint::Fixed(5), DimConstraint::Fixed(1), Box::new(Vt100Formatter{}), TermLocation::new(1, 1));
+        marquee.set_overflow(Overflow::Marquee);
+        marquee.update_content("far too long to fit".to_string());
+        // The marquee's the only page of an inner stack, which is itself the outer one's second page.
+        let mut inner = StackLayout::new(DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1));
+        inner.add_child(Box::new(marquee), "Ticker");
+        let mut outer = StackLayout::new(DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1));
+        outer.add_child(Box::new(Widget::new(DimConstraint::WrapContent, DimConstraint::WrapContent, Box::new(Vt100Formatter{}), TermLocation::new(1, 1))), "Quiet");
+        outer.add_child(Box::new(inner), "Busy");
+        let mut outer: Box<dyn View> = Box::new(outer);
+        outer.inflate(&CharDims::new(20, 5), &TermLocation::new(1, 1));
+
+        let later = Instant::now() + Duration::from_secs(1);
+        assert_eq!(next_turn(&mut outer), None);
+        assert!(!tick(&mut outer, later));
+
+        outer.turn_page(&Page::Next);
+        outer.inflate(&CharDims::new(20, 5), &TermLocation::new(1, 1));
+        assert!(next_turn(&mut outer).is_some());
+        assert!(tick(&mut outer, later));
+    }
+}
@@ -0,0 +1,717 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+use crate::hexterm::commands::BUILTINS;
+use crate::hexterm::keys::parse_key;
+use termion::event::Key;
+use crate::executable_command::{parse_cron, parse_period};
+use crate::tasks::{CommandLine, Config, Layout, Widget, WidgetMode};
+
+/***
+ConfigError: A single problem with the config, and where in the TOML it lives.
+    `path` is a TOML-ish path to the offending table or key, e.g. `layout.children[1].children[0]`
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(path: &str, message: String) -> ConfigError {
+        ConfigError { path: path.to_string(), message }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+const LAYOUT_KINDS: [&str; 4] = ["linearlayout", "textview", "grid", "stack"];
+const ORIENTATIONS: [&str; 2] = ["horizontal", "vertical"];
+
+/***
+ * Check a parsed Config for everything that would otherwise blow up (or silently misbehave)
+ * once the HUD is running. Collects every problem rather than stopping at the first.
+ ***/
+pub fn validate(config: &Config) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let mut ids = HashSet::new();
+
+    for (i, widget) in config.widgets.iter().enumerate() {
+        let path = format!("widgets[{}]", i);
+        validate_id(&widget.id, &format!("{}.id", path), &mut ids, &mut errors);
+
+        match (&widget.command, widget.mode) {
+            (Some(_), WidgetMode::Push) => {
+                errors.push(ConfigError::new(&format!("{}.command", path), "push widgets are set over the control socket - command doesn't apply".to_string()));
+            }
+            (None, WidgetMode::Push) => {},
+            (None, _) => errors.push(ConfigError::new(&path, "needs a command".to_string())),
+            (Some(CommandLine::Argv(argv)), _) if argv.is_empty() => {
+                errors.push(ConfigError::new(&format!("{}.command", path), "command array is empty".to_string()));
+            }
+            (Some(_), _) => {},
+        }
+
+        if let Some(shell) = &widget.shell {
+            match shell_words::split(shell) {
+                Ok(argv) if !argv.is_empty() => {},
+                Ok(_) => errors.push(ConfigError::new(&format!("{}.shell", path), "shell is empty".to_string())),
+                Err(e) => errors.push(ConfigError::new(&format!("{}.shell", path), format!("can't split shell '{}': {}", shell, e))),
+            }
+        }
+
+        match widget.mode {
+            WidgetMode::Poll => validate_poll_widget(widget, &path, &mut errors),
+            WidgetMode::Stream => validate_unscheduled_widget(widget, &path, "stream widgets run continuously", &mut errors),
+            WidgetMode::Push => validate_unscheduled_widget(widget, &path, "push widgets are set over the control socket", &mut errors),
+        }
+    }
+
+    for (i, app) in config.apps.iter().enumerate() {
+        validate_id(&app.id, &format!("apps[{}].id", i), &mut ids, &mut errors);
+    }
+
+    let keys = [("escape", &config.keys.escape), ("next_page", &config.keys.next_page), ("prev_page", &config.keys.prev_page)];
+    for (key, chord) in keys {
+        match parse_key(chord) {
+            Err(e) => errors.push(ConfigError::new(&format!("keys.{}", key), e)),
+            // It'd never get typed - at the prompt, or into an app.
+            Ok(Key::Char(c)) if !c.is_control() => errors.push(ConfigError::new(&format!("keys.{}", key),
+                format!("'{}' types a character - pick a key that doesn't, e.g. \"alt-{}\"", chord, c))),
+            Ok(_) => {},
+        }
+    }
+
+    let widget_ids = config.widgets.iter().map(|w| w.id.as_str()).collect::<HashSet<&str>>();
+    validate_layout(&config.layout, "layout", &widget_ids, &mut errors);
+
+    match how_many_mains(&config.layout) {
+        0 => errors.push(ConfigError::new("layout", "no 'main' view - mark one of your textviews with main = true".to_string())),
+        1 => {}, // perfect!
+        n => errors.push(ConfigError::new("layout", format!("{} views are marked 'main' - only one may be", n)))
+    }
+
+    errors
+}
+
+// Ids are typed at the prompt, so they have to be unique and mustn't be taken for one of its commands.
+fn validate_id(id: &str, path: &str, ids: &mut HashSet<String>, errors: &mut Vec<ConfigError>) {
+    if !ids.insert(id.to_string()) {
+        errors.push(ConfigError::new(path, format!("duplicate id '{}'", id)));
+    }
+    if BUILTINS.contains(&id) {
+        errors.push(ConfigError::new(path, format!("'{}' is a prompt command - pick another id", id)));
+    }
+}
+
+fn validate_poll_widget(widget: &Widget, path: &str, errors: &mut Vec<ConfigError>) {
+    match (&widget.period, &widget.schedule) {
+        (None, None) => errors.push(ConfigError::new(path, "needs either a period or a schedule".to_string())),
+        (Some(_), Some(_)) => errors.push(ConfigError::new(path, "has both a period and a schedule - pick one".to_string())),
+        (Some(period), None) => {
+            if parse_period(period).is_none() {
+                errors.push(ConfigError::new(&format!("{}.period", path),
+                                             format!("can't understand period '{}' - expected e.g. \"10s\", \"5m\" or \"1h30m\"", period)));
+            }
+        }
+        (None, Some(schedule)) => {
+            if let Err(e) = parse_cron(schedule) {
+                errors.push(ConfigError::new(&format!("{}.schedule", path), format!("can't understand schedule '{}': {}", schedule, e)));
+            }
+            if widget.align.is_some() {
+                errors.push(ConfigError::new(&format!("{}.align", path), "align only applies to a period, not a schedule".to_string()));
+            }
+        }
+    }
+
+    if let Some(timeout) = &widget.timeout {
+        if parse_period(timeout).is_none() {
+            errors.push(ConfigError::new(&format!("{}.timeout", path),
+                                         format!("can't understand timeout '{}' - expected e.g. \"10s\", \"5m\" or \"1h30m\"", timeout)));
+        }
+    }
+
+    if widget.max_lines.is_some() {
+        errors.push(ConfigError::new(&format!("{}.max_lines", path), "max_lines only applies to stream and push widgets".to_string()));
+    }
+
+    if widget.scrollback == Some(0) {
+        errors.push(ConfigError::new(&format!("{}.scrollback", path), "scrollback must be at least 1".to_string()));
+    }
+}
+
+/// Streams and push widgets aren't run on a schedule, so nothing about when (or how long) they run applies.
+fn validate_unscheduled_widget(widget: &Widget, path: &str, why: &str, errors: &mut Vec<ConfigError>) {
+    let not_applicable = [("period", widget.period.is_some()),
+                          ("schedule", widget.schedule.is_some()),
+                          ("align", widget.align.is_some()),
+                          ("timeout", widget.timeout.is_some()),
+                          ("scrollback", widget.scrollback.is_some())];
+    for (key, _) in not_applicable.iter().filter(|(_, set)| *set) {
+        errors.push(ConfigError::new(&format!("{}.{}", path, key), format!("{} - {} doesn't apply", why, key)));
+    }
+
+    if widget.max_lines == Some(0) {
+        errors.push(ConfigError::new(&format!("{}.max_lines", path), "max_lines must be at least 1".to_string()));
+    }
+}
+
+fn validate_layout(layout: &Layout, path: &str, widget_ids: &HashSet<&str>, errors: &mut Vec<ConfigError>) {
+    if !LAYOUT_KINDS.contains(&layout.kind.as_str()) {
+        errors.push(ConfigError::new(&format!("{}.kind", path),
+                                     format!("unknown kind '{}' - expected one of {}", layout.kind, LAYOUT_KINDS.join(", "))));
+    }
+
+    if layout.kind == "linearlayout" {
+        match &layout.orientation {
+            None => errors.push(ConfigError::new(path, "linearlayout is missing an orientation".to_string())),
+            Some(o) if !ORIENTATIONS.contains(&o.as_str()) => {
+                errors.push(ConfigError::new(&format!("{}.orientation", path),
+                                             format!("unknown orientation '{}' - expected one of {}", o, ORIENTATIONS.join(", "))));
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(task_id) = &layout.task_id {
+        if !widget_ids.contains(task_id.as_str()) {
+            errors.push(ConfigError::new(&format!("{}.task_id", path), format!("no widget has the id '{}'", task_id)));
+        }
+    }
+
+    if layout.main.unwrap_or(false) && layout.kind != "textview" {
+        errors.push(ConfigError::new(&format!("{}.main", path), format!("only textviews can be 'main', not {}", layout.kind)));
+    }
+
+    if layout.follow.is_some() && layout.kind != "textview" {
+        errors.push(ConfigError::new(&format!("{}.follow", path), format!("only textviews can follow their output, not {}", layout.kind)));
+    }
+
+    if layout.overflow.is_some() && layout.kind != "textview" {
+        errors.push(ConfigError::new(&format!("{}.overflow", path), format!("only textviews have text to overflow, not {}", layout.kind)));
+    }
+
+    for (key, size) in [("width", &layout.width), ("height", &layout.height)] {
+        if let Some(Err(e)) = size.as_ref().map(|s| s.constraint()) {
+            errors.push(ConfigError::new(&format!("{}.{}", path, key), e));
+        }
+    }
+
+    for (key, tracks) in [("rows", &layout.rows), ("cols", &layout.cols)] {
+        if tracks.is_some() && layout.kind != "grid" {
+            errors.push(ConfigError::new(&format!("{}.{}", path, key), format!("only a grid has {}, not {}", key, layout.kind)));
+        }
+        for (i, size) in tracks.iter().flatten().enumerate() {
+            if let Err(e) = size.constraint() {
+                errors.push(ConfigError::new(&format!("{}.{}[{}]", path, key, i), e));
+            }
+        }
+    }
+
+    for (key, set) in [("tabs", layout.tabs.is_some()), ("rotate", layout.rotate.is_some())] {
+        if set && layout.kind != "stack" {
+            errors.push(ConfigError::new(&format!("{}.{}", path, key), format!("only a stack has {}, not {}", key, layout.kind)));
+        }
+    }
+    if let Some(rotate) = layout.rotate.as_ref().filter(|rotate| parse_period(rotate).is_none()) {
+        errors.push(ConfigError::new(&format!("{}.rotate", path),
+                                     format!("can't understand rotate '{}' - expected e.g. \"10s\", \"5m\" or \"1h30m\"", rotate)));
+    }
+
+    for (i, child) in layout.children.as_ref().unwrap_or(&Vec::new()).iter().enumerate() {
+        let child_path = format!("{}.children[{}]", path, i);
+        validate_grid_placement(layout, child, &child_path, errors);
+        if child.name.is_some() && layout.kind != "stack" {
+            errors.push(ConfigError::new(&format!("{}.name", child_path), "only a stack's children have a name".to_string()));
+        }
+        validate_layout(child, &child_path, widget_ids, errors);
+    }
+
+    if layout.kind == "grid" {
+        validate_grid_overlaps(layout, path, errors);
+    }
+}
+
+/// A grid's children say which cells they cover. Nothing else's children can.
+fn validate_grid_placement(parent: &Layout, child: &Layout, path: &str, errors: &mut Vec<ConfigError>) {
+    let placement = [("row", child.row), ("col", child.col), ("rowspan", child.rowspan), ("colspan", child.colspan)];
+    if parent.kind != "grid" {
+        for (key, _) in placement.iter().filter(|(_, value)| value.is_some()) {
+            errors.push(ConfigError::new(&format!("{}.{}", path, key), format!("only a grid's children have a {}", key)));
+        }
+        return;
+    }
+
+    for (key, span) in [("rowspan", child.rowspan), ("colspan", child.colspan)] {
+        if span == Some(0) {
+            errors.push(ConfigError::new(&format!("{}.{}", path, key), format!("{} must be at least 1", key)));
+        }
+    }
+
+    // Tracks that aren't defined fit their content, so only a grid with them all spelled out can be overrun.
+    let reaches = |start: Option<usize>, span: Option<usize>| start.unwrap_or(0) + span.unwrap_or(1).max(1);
+    for (key, reach, tracks) in [("row", reaches(child.row, child.rowspan), &parent.rows), ("col", reaches(child.col, child.colspan), &parent.cols)] {
+        if let Some(tracks) = tracks.as_ref().filter(|tracks| reach > tracks.len()) {
+            errors.push(ConfigError::new(&format!("{}.{}", path, key),
+                                         format!("goes past the grid's {} {}s", tracks.len(), key)));
+        }
+    }
+}
+
+/// Each of a grid's cells holds one child at most - one drawn over another would be hidden.
+fn validate_grid_overlaps(grid: &Layout, path: &str, errors: &mut Vec<ConfigError>) {
+    let children = grid.children.as_deref().unwrap_or_default();
+    let span = |start: Option<usize>, span: Option<usize>| start.unwrap_or(0)..start.unwrap_or(0) + span.unwrap_or(1).max(1);
+    let cells = children.iter().map(|c| (span(c.row, c.rowspan), span(c.col, c.colspan))).collect::<Vec<_>>();
+
+    for (i, (rows, cols)) in cells.iter().enumerate() {
+        let overlaps = |(other_rows, other_cols): &(Range<usize>, Range<usize>)|
+            rows.start < other_rows.end && other_rows.start < rows.end && cols.start < other_cols.end && other_cols.start < cols.end;
+        if let Some(earlier) = cells[..i].iter().position(overlaps) {
+            errors.push(ConfigError::new(&format!("{}.children[{}]", path, i),
+                                         format!("covers some of the same cells as children[{}]", earlier)));
+        }
+    }
+}
+
+pub fn how_many_mains(l: &Layout) -> usize {
+    let main_children: usize = match &l.children {
+        Some(children) => { children.iter().map(how_many_mains).sum() },
+        None => 0
+    };
+
+    let this_main = if l.main.unwrap_or(false) { 1 } else { 0 };
+    this_main + main_children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::views::DimConstraint;
+
+    const WIDGETS: &str = r#"
+        [[widgets]]
+            id = "time"
+            name = "Date and Time"
+            description = "See the current date and time"
+            path = "/bin"
+            command = "./date"
+            period = "1s"
+    "#;
+
+    fn errors_for(toml: &str) -> Vec<String> {
+        let config: Config = toml::from_str(toml).unwrap();
+        validate(&config).iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_errors() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [layout]
+                kind = "linearlayout"
+                orientation = "vertical"
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    main = true
+        "#));
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn ids_cant_be_prompt_commands() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [[widgets]]
+                id = "status"
+                name = "Build status"
+                description = "Shadowed by the prompt's status command"
+                path = "/bin"
+                command = "./true"
+                period = "1m"
+
+            [[apps]]
+                id = "show"
+                name = "Slides"
+                description = "Shadowed by the prompt's show command"
+                path = "/usr/bin"
+                command = "./less"
+
+            [layout]
+                kind = "textview"
+                task_id = "time"
+                main = true
+        "#));
+
+        assert_eq!(errors, vec![
+            "widgets[1].id: 'status' is a prompt command - pick another id",
+            "apps[0].id: 'show' is a prompt command - pick another id",
+        ]);
+    }
+
+    #[test]
+    fn reports_every_problem_with_its_path() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [[widgets]]
+                id = "time"
+                name = "Another clock"
+                description = "Same id as the first"
+                path = "/bin"
+                command = []
+                period = "often"
+                timeout = "never"
+                shell = "'/bin/sh -c"
+
+            [layout]
+                kind = "linearlayout"
+                orientation = "vertical"
+                [[layout.children]]
+                    kind = "linearlayout"
+                [[layout.children]]
+                    kind = "linearlayout"
+                    orientation = "horizontal"
+                    [[layout.children.children]]
+                        kind = "textview"
+                        task_id = "weather"
+                    [[layout.children.children]]
+                        kind = "spreadsheet"
+        "#));
+
+        assert_eq!(errors, vec![
+            "widgets[1].id: duplicate id 'time'",
+            "widgets[1].command: command array is empty",
+            "widgets[1].shell: can't split shell ''/bin/sh -c': missing closing quote",
+            "widgets[1].period: can't understand period 'often' - expected e.g. \"10s\", \"5m\" or \"1h30m\"",
+            "widgets[1].timeout: can't understand timeout 'never' - expected e.g. \"10s\", \"5m\" or \"1h30m\"",
+            "layout.children[0]: linearlayout is missing an orientation",
+            "layout.children[1].children[0].task_id: no widget has the id 'weather'",
+            "layout.children[1].children[1].kind: unknown kind 'spreadsheet' - expected one of linearlayout, textview, grid, stack",
+            "layout: no 'main' view - mark one of your textviews with main = true",
+        ]);
+    }
+
+    #[test]
+    fn widgets_need_exactly_one_schedule() {
+        let errors = errors_for(r#"
+            [[widgets]]
+                id = "no_schedule"
+                name = "Never runs"
+                description = "Has neither"
+                path = "/bin"
+                command = "./date"
+            [[widgets]]
+                id = "both"
+                name = "Runs when?"
+                description = "Has both"
+                path = "/bin"
+                command = "./date"
+                period = "1m"
+                schedule = "* * * * *"
+            [[widgets]]
+                id = "bad_cron"
+                name = "Never runs"
+                description = "Unparseable schedule"
+                path = "/bin"
+                command = "./date"
+                schedule = "at teatime"
+                align = true
+
+            [layout]
+                kind = "textview"
+                task_id = "both"
+                main = true
+        "#);
+
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert_eq!(errors[0], "widgets[0]: needs either a period or a schedule");
+        assert_eq!(errors[1], "widgets[1]: has both a period and a schedule - pick one");
+        assert!(errors[2].starts_with("widgets[2].schedule: can't understand schedule 'at teatime'"), "{}", errors[2]);
+        assert_eq!(errors[3], "widgets[2].align: align only applies to a period, not a schedule");
+    }
+
+    #[test]
+    fn stream_widgets_dont_take_a_schedule() {
+        let errors = errors_for(r#"
+            [[widgets]]
+                id = "log"
+                name = "Syslog"
+                description = "Follows the system log"
+                path = "/"
+                command = "journalctl -f"
+                mode = "stream"
+                max_lines = 5
+            [[widgets]]
+                id = "scheduled_log"
+                name = "Syslog"
+                description = "Confused about what a stream is"
+                path = "/"
+                command = "journalctl -f"
+                mode = "stream"
+                period = "1m"
+                max_lines = 0
+                scrollback = 20
+            [[widgets]]
+                id = "time"
+                name = "Time"
+                description = "Polls with max_lines"
+                path = "/bin"
+                command = "./date"
+                period = "1s"
+                max_lines = 5
+                scrollback = 0
+
+            [layout]
+                kind = "textview"
+                task_id = "log"
+                main = true
+        "#);
+
+        assert_eq!(errors, vec!["widgets[1].period: stream widgets run continuously - period doesn't apply",
+                                "widgets[1].scrollback: stream widgets run continuously - scrollback doesn't apply",
+                                "widgets[1].max_lines: max_lines must be at least 1",
+                                "widgets[2].max_lines: max_lines only applies to stream and push widgets",
+                                "widgets[2].scrollback: scrollback must be at least 1"]);
+    }
+
+    #[test]
+    fn only_push_widgets_go_without_a_command() {
+        let errors = errors_for(r#"
+            [[widgets]]
+                id = "notifications"
+                name = "Notifications"
+                description = "Set by the notification forwarder"
+                mode = "push"
+                max_lines = 5
+            [[widgets]]
+                id = "phone"
+                name = "Phone"
+                description = "Pushed, but with a command anyway"
+                command = "./phone"
+                mode = "push"
+            [[widgets]]
+                id = "time"
+                name = "Time"
+                description = "Polls nothing"
+                period = "1s"
+
+            [layout]
+                kind = "textview"
+                task_id = "notifications"
+                main = true
+        "#);
+
+        assert_eq!(errors, vec!["widgets[1].command: push widgets are set over the control socket - command doesn't apply",
+                                "widgets[2]: needs a command"]);
+    }
+
+    #[test]
+    fn only_one_textview_may_be_main() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [layout]
+                kind = "linearlayout"
+                orientation = "vertical"
+                main = true
+                follow = true
+                overflow = "wrap"
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    main = true
+        "#));
+
+        assert_eq!(errors, vec![
+            "layout.main: only textviews can be 'main', not linearlayout",
+            "layout.follow: only textviews can follow their output, not linearlayout",
+            "layout.overflow: only textviews have text to overflow, not linearlayout",
+            "layout: 2 views are marked 'main' - only one may be",
+        ]);
+    }
+
+    #[test]
+    fn escape_key_must_be_a_key() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [keys]
+                escape = "ctrl-1"
+
+            [layout]
+                kind = "textview"
+                task_id = "time"
+                main = true
+        "#));
+
+        assert_eq!(errors, vec!["keys.escape: can't use 'ctrl-1' - Ctrl only goes with a letter or one of \\ ] ^ _ space"]);
+    }
+
+    #[test]
+    fn sizes_can_be_percentages_weights_or_ranges() {
+        let config: Config = toml::from_str(&(WIDGETS.to_string() + r#"
+            [layout]
+                kind = "linearlayout"
+                orientation = "horizontal"
+                height = 10
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    main = true
+                    width = "30%"
+                    height = "2..5"
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    width = "2*"
+        "#)).unwrap();
+
+        let children = config.layout.children.as_ref().unwrap();
+        assert_eq!(config.layout.height.unwrap().constraint(), Ok(DimConstraint::Fixed(10)));
+        assert_eq!(children[0].width.as_ref().unwrap().constraint(), Ok(DimConstraint::Percent(30)));
+        assert_eq!(children[0].height.as_ref().unwrap().constraint(), Ok(DimConstraint::Between(2, 5)));
+        assert_eq!(children[1].width.as_ref().unwrap().constraint(), Ok(DimConstraint::Weight(2)));
+    }
+
+    #[test]
+    fn reports_sizes_it_cant_use() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [layout]
+                kind = "linearlayout"
+                orientation = "horizontal"
+                width = "150%"
+                height = "0*"
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    main = true
+                    width = "wide"
+                    height = "9..3"
+        "#));
+
+        assert_eq!(errors, vec![
+            "layout.width: '150%' is more than 100%",
+            "layout.height: '0*' has no weight - use at least \"1*\"",
+            "layout.children[0].width: can't understand size 'wide' - expected e.g. 20, \"30%\", \"2*\" or \"5..20\"",
+            "layout.children[0].height: '9..3' has its limits the wrong way round",
+        ]);
+    }
+
+    #[test]
+    fn grid_children_must_fit_the_grid() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [layout]
+                kind = "grid"
+                rows = ["1*", 3]
+                cols = ["25%"]
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    main = true
+                    row = 1
+                    rowspan = 2
+                [[layout.children]]
+                    kind = "linearlayout"
+                    orientation = "vertical"
+                    colspan = 0
+                    rows = [1]
+                    [[layout.children.children]]
+                        kind = "textview"
+                        task_id = "time"
+                        col = 2
+        "#));
+
+        assert_eq!(errors, vec![
+            "layout.children[0].row: goes past the grid's 2 rows",
+            "layout.children[1].colspan: colspan must be at least 1",
+            "layout.children[1].rows: only a grid has rows, not linearlayout",
+            "layout.children[1].children[0].col: only a grid's children have a col",
+        ]);
+    }
+
+    #[test]
+    fn grid_children_cant_overlap() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [layout]
+                kind = "grid"
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    main = true
+                    colspan = 2
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    row = 1
+                    rowspan = 2
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    col = 1
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    row = 2
+                    col = 1
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    row = 2
+        "#));
+
+        assert_eq!(errors, vec![
+            "layout.children[2]: covers some of the same cells as children[0]",
+            "layout.children[4]: covers some of the same cells as children[1]",
+        ]);
+    }
+
+    #[test]
+    fn keys_cant_be_ones_that_type() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [keys]
+                next_page = "n"
+                prev_page = "?"
+                escape = "tab"
+            [layout]
+                kind = "textview"
+                task_id = "time"
+                main = true
+        "#));
+
+        assert_eq!(errors, vec![
+            "keys.next_page: 'n' types a character - pick a key that doesn't, e.g. \"alt-n\"",
+            "keys.prev_page: '?' types a character - pick a key that doesn't, e.g. \"alt-?\"",
+        ]);
+    }
+
+    #[test]
+    fn only_stacks_have_pages() {
+        let errors = errors_for(&(WIDGETS.to_string() + r#"
+            [keys]
+                next_page = "hyper-n"
+            [layout]
+                kind = "stack"
+                tabs = true
+                rotate = "sometimes"
+                [[layout.children]]
+                    kind = "textview"
+                    task_id = "time"
+                    main = true
+                    name = "Clock"
+                [[layout.children]]
+                    kind = "linearlayout"
+                    orientation = "vertical"
+                    tabs = false
+                    [[layout.children.children]]
+                        kind = "textview"
+                        task_id = "time"
+                        name = "Clock"
+        "#));
+
+        assert_eq!(errors, vec![
+            "keys.next_page: unknown key 'hyper-n' - expected e.g. \"ctrl-]\", \"alt-x\" or \"f12\"",
+            "layout.rotate: can't understand rotate 'sometimes' - expected e.g. \"10s\", \"5m\" or \"1h30m\"",
+            "layout.children[1].tabs: only a stack has tabs, not linearlayout",
+            "layout.children[1].children[0].name: only a stack's children have a name",
+        ]);
+    }
+}
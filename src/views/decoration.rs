@@ -0,0 +1,160 @@
+use serde::Deserialize;
+use crate::views::{CharDims, DimConstraint, TermLocation};
+use crate::views::screen::{Cell, Screen, Style};
+use crate::hexterm::formatting::take_columns;
+
+/***
+Border: The line drawn around a decorated View.
+ */
+#[derive(Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Border {
+    #[default]
+    None,
+    Single,
+    Double,
+    Rounded,
+    Ascii,
+}
+
+impl Border {
+    // The corners (top-left, top-right, bottom-left, bottom-right), then the horizontal and vertical lines.
+    fn chars(&self) -> Option<[char; 6]> {
+        match self {
+            Border::None => None,
+            Border::Single => Some(['┌', '┐', '└', '┘', '─', '│']),
+            Border::Double => Some(['╔', '╗', '╚', '╝', '═', '║']),
+            Border::Rounded => Some(['╭', '╮', '╰', '╯', '─', '│']),
+            Border::Ascii => Some(['+', '+', '+', '+', '-', '|']),
+        }
+    }
+}
+
+/***
+Decoration: What goes around a View's content, from the outside in.
+    margin  -> Blank space between the View and its neighbours.
+    border  -> A line around the View, with the title set into its top edge.
+    title   -> Shown in the top border - or if there isn't one, on a line of its own.
+    padding -> Blank space between the border and the content.
+    All of it is taken out of the View's own size, so a decorated View takes no more room than a bare one.
+ */
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Decoration {
+    pub border: Border,
+    pub title: Option<String>,
+    pub padding: usize,
+    pub margin: usize,
+}
+
+impl Decoration {
+    fn side(&self) -> usize {
+        self.margin + self.border.chars().map_or(0, |_| 1) + self.padding
+    }
+
+    fn top(&self) -> usize {
+        let title_or_border = self.border.chars().is_some() || self.title.is_some();
+        self.margin + title_or_border as usize + self.padding
+    }
+
+    /// The room the decoration takes up around the content.
+    pub fn extra(&self) -> CharDims {
+        CharDims::new(self.side() * 2, self.top() + self.side())
+    }
+
+    /// The room left for content inside `outer`.
+    pub fn shrink(&self, outer: &CharDims) -> CharDims {
+        let extra = self.extra();
+        CharDims::new(outer.width.saturating_sub(extra.width), outer.height.saturating_sub(extra.height))
+    }
+
+    /// The outer size for content of `inner`. Nothing's decorated if there's no content.
+    pub fn grow(&self, inner: &CharDims) -> CharDims {
+        if inner.width == 0 || inner.height == 0 { return CharDims::new(0, 0); }
+        let extra = self.extra();
+        CharDims::new(inner.width + extra.width, inner.height + extra.height)
+    }
+
+    /// Constraints on the whole View, as constraints on its content.
+    pub fn shrink_constraints(&self, (width, height): (DimConstraint, DimConstraint)) -> (DimConstraint, DimConstraint) {
+        let extra = self.extra();
+        (width.less(extra.width), height.less(extra.height))
+    }
+
+    /// Where the content starts, for a View at `location`.
+    pub fn content_location(&self, location: &TermLocation) -> TermLocation {
+        TermLocation::new(location.x + self.side() as u16, location.y + self.top() as u16)
+    }
+
+    /// Draw the border and title for a View at `location` of `size`. The rest's left for its content.
+    pub fn draw(&self, screen: &mut Screen, location: &TermLocation, size: &CharDims) {
+        let (x, y) = (location.x as usize - 1 + self.margin, location.y as usize - 1 + self.margin);
+        let (width, height) = (size.width.saturating_sub(self.margin * 2), size.height.saturating_sub(self.margin * 2));
+        if width == 0 || height == 0 { return; }
+
+        let title_at = match self.border.chars() {
+            Some([top_left, top_right, bottom_left, bottom_right, horizontal, vertical]) if width >= 2 && height >= 2 => {
+                let (right, bottom) = (x + width - 1, y + height - 1);
+                for i in x + 1..right {
+                    screen.put(i, y, Cell::new(horizontal, Style::default()));
+                    screen.put(i, bottom, Cell::new(horizontal, Style::default()));
+                }
+                for j in y + 1..bottom {
+                    screen.put(x, j, Cell::new(vertical, Style::default()));
+                    screen.put(right, j, Cell::new(vertical, Style::default()));
+                }
+                screen.put(x, y, Cell::new(top_left, Style::default()));
+                screen.put(right, y, Cell::new(top_right, Style::default()));
+                screen.put(x, bottom, Cell::new(bottom_left, Style::default()));
+                screen.put(right, bottom, Cell::new(bottom_right, Style::default()));
+                self.title.as_ref().map(|title| (format!(" {} ", title), x + 1, width - 2))
+            },
+            Some(_) => None,
+            None => self.title.as_ref().map(|title| (title.clone(), x, width)),
+        };
+
+        if let Some((title, left, room)) = title_at {
+            let (title, _) = take_columns(&title, room);
+            screen.draw(title, &TermLocation::new(left as u16 + 1, y as u16 + 1), &CharDims::new(room, 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(screen: &Screen) -> Vec<String> {
+        (0..screen.size().height).map(|y| screen.row_text(y)).collect()
+    }
+
+    #[test]
+    fn takes_its_room_from_the_content() {
+        let decoration = Decoration { border: Border::Single, title: None, padding: 1, margin: 2 };
+        assert_eq!(decoration.extra(), CharDims::new(8, 8));
+        assert_eq!(decoration.shrink(&CharDims::new(20, 10)), CharDims::new(12, 2));
+        assert_eq!(decoration.grow(&CharDims::new(12, 2)), CharDims::new(20, 10));
+        assert_eq!(decoration.grow(&CharDims::new(0, 2)), CharDims::new(0, 0));
+        assert_eq!(decoration.content_location(&TermLocation::new(1, 1)), TermLocation::new(5, 5));
+        assert_eq!(decoration.shrink_constraints((DimConstraint::Fixed(10), DimConstraint::WrapContent)),
+                   (DimConstraint::Fixed(2), DimConstraint::WrapContent));
+    }
+
+    #[test]
+    fn draws_a_border_with_the_title_in_it() {
+        let mut screen = Screen::new(CharDims::new(9, 4));
+        let decoration = Decoration { border: Border::Rounded, title: Some("a long title".to_string()), padding: 0, margin: 0 };
+        decoration.draw(&mut screen, &TermLocation::new(1, 1), &CharDims::new(9, 3));
+
+        assert_eq!(rows(&screen), vec!["╭ a long╮", "│       │", "╰───────╯", "         "]);
+    }
+
+    #[test]
+    fn a_title_without_a_border_gets_its_own_line() {
+        let mut screen = Screen::new(CharDims::new(6, 3));
+        let decoration = Decoration { border: Border::None, title: Some("CPU".to_string()), padding: 0, margin: 1 };
+        assert_eq!(decoration.extra(), CharDims::new(2, 3));
+        decoration.draw(&mut screen, &TermLocation::new(1, 1), &CharDims::new(6, 3));
+
+        assert_eq!(rows(&screen), vec!["      ", " CPU  ", "      "]);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::views::{GridLayout, GridCell, View, DimConstraint, Dimensions, desired_size, container_size, share, CharDims, ViewId, TermLocation};
+use std::cmp::min;
+use uuid::Uuid;
+use std::slice::IterMut;
+use crate::views::screen::Screen;
+use crate::views::decoration::Decoration;
+
+impl GridLayout {
+    pub fn new(rows: Vec<DimConstraint>, cols: Vec<DimConstraint>, width: DimConstraint, height: DimConstraint, location: TermLocation) -> GridLayout {
+        GridLayout {
+            id: Uuid::new_v4().to_string(),
+            rows,
+            cols,
+            children: vec![],
+            cells: vec![],
+            dims: Dimensions::new(width, height),
+            location,
+            visible: true,
+            dirty: true,
+            decoration: Decoration::default(),
+        }
+    }
+
+    pub fn set_decoration(&mut self, decoration: Decoration) {
+        self.decoration = decoration;
+    }
+
+    pub fn add_child(&mut self, child: Box<dyn View>, cell: GridCell) {
+        self.children.push(child);
+        self.cells.push(cell);
+    }
+
+    // The row or column constraints, with WrapContent for any a child reaches but that weren't defined.
+    fn tracks(defined: &[DimConstraint], needed: usize) -> Vec<DimConstraint> {
+        let mut tracks = defined.to_vec();
+        if needed > tracks.len() {
+            tracks.resize(needed, DimConstraint::WrapContent);
+        }
+        tracks
+    }
+}
+
+/***
+ * Share `total` out between a row's (or column's) worth of tracks. Fixed and Percent tracks
+ * take their size, ones sized by content take what their biggest child wants (`content`),
+ * each in turn while there's room. Then weighted tracks share whatever's left.
+ ***/
+fn track_sizes(tracks: &[DimConstraint], content: &[usize], total: usize) -> Vec<usize> {
+    let mut remaining = total;
+    let mut sizes = tracks.iter().zip(content).map(|(track, wanted)| {
+        let size = match *track {
+            DimConstraint::Fixed(n) => n,
+            DimConstraint::Percent(p) => total * p / 100,
+            DimConstraint::UpTo(n) => min(*wanted, n),
+            DimConstraint::Between(least, most) => (*wanted).clamp(least, most),
+            DimConstraint::WrapContent => *wanted,
+            DimConstraint::Weight(_) => return 0,
+        };
+        let size = min(size, remaining);
+        remaining -= size;
+        size
+    }).collect::<Vec<usize>>();
+
+    let weights = tracks.iter().map(|t| match t { DimConstraint::Weight(w) => *w, _ => 0 }).collect::<Vec<usize>>();
+    sizes.iter_mut().zip(share(remaining, &weights)).for_each(|(size, share)| *size += share);
+    sizes
+}
+
+// Where each track starts, relative to the first, and how much room a span of them covers.
+fn offset(sizes: &[usize], start: usize) -> usize {
+    sizes.iter().take(start).sum()
+}
+
+fn span(sizes: &[usize], start: usize, count: usize) -> usize {
+    sizes.iter().skip(start).take(count).sum()
+}
+
+impl View for GridLayout {
+    fn id(&self) -> ViewId {
+        self.id.clone()
+    }
+
+    fn dirty(&self) -> bool {
+        self.dirty || self.children.iter().any(|c| c.dirty())
+    }
+
+    fn wash(&mut self) {
+        self.dirty = false;
+        self.children.iter_mut().for_each(|c| c.wash());
+    }
+
+    fn inflate(&mut self, parent_dimensions: &CharDims, location: &TermLocation) -> CharDims {
+        self.location = *location;
+        if !self.visible {
+            self.dims.size = CharDims::new(0, 0);
+            return self.dims.size;
+        }
+
+        let most_restrictive_width = min(self.dims.width_constraint.resolve(parent_dimensions.width), DimConstraint::Fixed(parent_dimensions.width));
+        let most_restrictive_height = min(self.dims.height_constraint.resolve(parent_dimensions.height), DimConstraint::Fixed(parent_dimensions.height));
+        self.dims.size = CharDims::new(desired_size(&most_restrictive_width), desired_size(&most_restrictive_height));
+        self.dims.available = self.dims.size;
+
+        let full = self.decoration.shrink(&self.dims.size);
+        let origin = self.decoration.content_location(location);
+        let rows = GridLayout::tracks(&self.rows, self.cells.iter().map(|c| c.row + c.rowspan).max().unwrap_or(0));
+        let cols = GridLayout::tracks(&self.cols, self.cells.iter().map(|c| c.col + c.colspan).max().unwrap_or(0));
+
+        // First, see how big each child would like to be with the whole grid to itself.
+        // Only children in a single row or column count towards fitting that one to its content.
+        let mut row_content = vec![0; rows.len()];
+        let mut col_content = vec![0; cols.len()];
+        for (child, cell) in self.children.iter_mut().zip(&self.cells) {
+            let wanted = child.inflate(&full, &origin);
+            if cell.rowspan == 1 { row_content[cell.row] = row_content[cell.row].max(wanted.height); }
+            if cell.colspan == 1 { col_content[cell.col] = col_content[cell.col].max(wanted.width); }
+        }
+
+        let row_sizes = track_sizes(&rows, &row_content, full.height);
+        let col_sizes = track_sizes(&cols, &col_content, full.width);
+
+        // Then put each child in its cells.
+        for (child, cell) in self.children.iter_mut().zip(&self.cells) {
+            let child_location = TermLocation::new(origin.x + offset(&col_sizes, cell.col) as u16, origin.y + offset(&row_sizes, cell.row) as u16);
+            let room = CharDims::new(span(&col_sizes, cell.col, cell.colspan), span(&row_sizes, cell.row, cell.rowspan));
+            child.inflate(&room, &child_location);
+        }
+
+        let tracks_size = self.decoration.grow(&CharDims::new(col_sizes.iter().sum(), row_sizes.iter().sum()));
+        self.dims.size = CharDims::new(container_size(self.dims.width_constraint, parent_dimensions.width, tracks_size.width),
+                                       container_size(self.dims.height_constraint, parent_dimensions.height, tracks_size.height));
+        self.dims.size
+    }
+
+    fn constraints(&self) -> (DimConstraint, DimConstraint) { (self.dims.width_constraint, self.dims.height_constraint) }
+
+    fn width(&self) -> usize { self.dims.size.width }
+
+    fn height(&self) -> usize { self.dims.size.height }
+
+    fn location(&self) -> TermLocation { self.location }
+
+    fn bounds(&self) -> CharDims { self.dims.available }
+
+    fn content_area(&self) -> (TermLocation, CharDims) {
+        (self.decoration.content_location(&self.location), self.decoration.shrink(&self.dims.available))
+    }
+
+    fn render(&self, screen: &mut Screen) {
+        if !self.visible { return }
+        self.decoration.draw(screen, &self.location, &self.dims.size);
+        self.children.iter().for_each(|c| c.render(screen));
+    }
+
+    fn children(&mut self) -> IterMut<'_, Box<dyn View>> {
+        self.children.iter_mut()
+    }
+
+    fn update_content(&mut self, _: String) {
+        // Like a LinearLayout, a grid has no text of its own.
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if visible != self.visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::views::Widget;
+    use crate::hexterm::formatting::Vt100Formatter;
+    use crate::views::rendered_rows;
+
+    fn text_widget(width: DimConstraint, height: DimConstraint, text: &str) -> Box<dyn View> {
+        let mut tw = Widget::new(width, height, Box::new(Vt100Formatter{}), TermLocation::new(1, 1));
+        tw.update_content(text.to_owned());
+        Box::new(tw)
+    }
+
+    fn wrap_content_text_widget(text: &str) -> Box<dyn View> {
+        text_widget(DimConstraint::WrapContent, DimConstraint::WrapContent, text)
+    }
+
+    fn cell(row: usize, col: usize) -> GridCell {
+        GridCell { row, col, rowspan: 1, colspan: 1 }
+    }
+
+    fn wrap_content_grid(rows: Vec<DimConstraint>, cols: Vec<DimConstraint>) -> GridLayout {
+        GridLayout::new(rows, cols, DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1))
+    }
+
+    fn places(grid: &mut GridLayout) -> Vec<(u16, u16, usize, usize)> {
+        grid.children().map(|c| (c.location().x, c.location().y, c.width(), c.height())).collect()
+    }
+
+    #[test]
+    fn retrieves_constraints() {
+        let grid = GridLayout::new(vec![], vec![], DimConstraint::Fixed(5), DimConstraint::Fixed(2), TermLocation::new(1, 1));
+        assert_eq!(grid.constraints(), (DimConstraint::Fixed(5), DimConstraint::Fixed(2)));
+    }
+
+    #[test]
+    fn when_wrapping_content_takes_size_from_children() {
+        let mut grid = wrap_content_grid(vec![], vec![]);
+        grid.add_child(wrap_content_text_widget("top\nleft"), cell(0, 0));
+        grid.add_child(wrap_content_text_widget("top right"), cell(0, 1));
+        grid.add_child(wrap_content_text_widget("bottom"), cell(1, 1));
+        grid.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        assert_eq!((grid.width(), grid.height()), (13, 3));
+        assert_eq!(places(&mut grid), vec![(1, 1, 4, 2), (5, 1, 9, 1), (5, 3, 6, 1)]);
+    }
+
+    #[test]
+    fn fixed_and_weighted_tracks_share_the_room() {
+        let mut grid = GridLayout::new(vec![DimConstraint::Fixed(1), DimConstraint::Weight(1)],
+                                       vec![DimConstraint::Percent(25), DimConstraint::Weight(2), DimConstraint::Weight(1)],
+                                       DimConstraint::Fixed(40), DimConstraint::Fixed(10), TermLocation::new(1, 1));
+        grid.add_child(text_widget(DimConstraint::Weight(1), DimConstraint::Weight(1), "a"), cell(0, 0));
+        grid.add_child(text_widget(DimConstraint::Weight(1), DimConstraint::Weight(1), "b"), cell(1, 1));
+        grid.add_child(text_widget(DimConstraint::Weight(1), DimConstraint::Weight(1), "c"), cell(1, 2));
+        grid.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        assert_eq!((grid.width(), grid.height()), (40, 10));
+        assert_eq!(places(&mut grid), vec![(1, 1, 10, 1), (11, 2, 20, 9), (31, 2, 10, 9)]);
+    }
+
+    #[test]
+    fn children_can_span_rows_and_columns() {
+        let mut grid = wrap_content_grid(vec![DimConstraint::Fixed(2), DimConstraint::Fixed(3)], vec![DimConstraint::Fixed(4), DimConstraint::Fixed(5)]);
+        grid.add_child(text_widget(DimConstraint::Weight(1), DimConstraint::Weight(1), "wide"), GridCell { row: 0, col: 0, rowspan: 1, colspan: 2 });
+        grid.add_child(text_widget(DimConstraint::Weight(1), DimConstraint::Weight(1), "tall"), GridCell { row: 0, col: 1, rowspan: 2, colspan: 1 });
+        grid.inflate(&CharDims::new(100, 100), &TermLocation::new(3, 2));
+
+        assert_eq!(places(&mut grid), vec![(3, 2, 9, 2), (7, 2, 5, 5)]);
+    }
+
+    #[test]
+    fn shrinks_tracks_to_fit_the_parent() {
+        let mut grid = wrap_content_grid(vec![], vec![DimConstraint::Fixed(6), DimConstraint::Fixed(6)]);
+        grid.add_child(wrap_content_text_widget("left side"), cell(0, 0));
+        grid.add_child(wrap_content_text_widget("right side"), cell(0, 1));
+        grid.inflate(&CharDims::new(8, 100), &TermLocation::new(1, 1));
+
+        assert_eq!(grid.width(), 8);
+        assert_eq!(places(&mut grid), vec![(1, 1, 6, 1), (7, 1, 2, 1)]);
+    }
+
+    #[test]
+    fn rendering_works_with_multiple_children() {
+        let mut grid = wrap_content_grid(vec![], vec![DimConstraint::Fixed(3), DimConstraint::Fixed(3)]);
+        grid.add_child(wrap_content_text_widget("ab"), cell(0, 0));
+        grid.add_child(wrap_content_text_widget("cd"), cell(0, 1));
+        grid.add_child(wrap_content_text_widget("ef"), cell(1, 1));
+        grid.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        assert_eq!(rendered_rows(&grid, CharDims::new(6, 3)), vec!["ab cd", "   ef", ""]);
+    }
+
+    #[test]
+    fn when_invisible_renders_nothing() {
+        let mut grid = wrap_content_grid(vec![], vec![]);
+        grid.add_child(wrap_content_text_widget("hidden"), cell(0, 0));
+        grid.visible = false;
+        grid.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(rendered_rows(&grid, CharDims::new(3, 1)), vec![""]);
+    }
+
+    #[test]
+    fn when_invisible_dims_are_0() {
+        let mut grid = wrap_content_grid(vec![], vec![]);
+        grid.add_child(wrap_content_text_widget("hidden"), cell(0, 0));
+        grid.visible = false;
+        grid.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(grid.dims.size, CharDims::new(0, 0));
+    }
+}
@@ -1,73 +1,266 @@
-use std::io;
-
-use regex::Regex;
-use termion::cursor::Goto;
-use crate::views::{DimConstraint, TermLocation, CharDims};
-use regex::internal::Char;
-use std::borrow::Borrow;
+use crate::views::CharDims;
+use crate::views::screen::{parse_params, Cell, Screen, Style};
+use unicode_width::UnicodeWidthChar;
 
 const ESC: char = '\u{1B}';
-const CSI: &str = r"\u{1B}\[";
-const VT100_PATTERN: &'static str = r"((\u001b\[|\u009b)[\u0030-\u003f]*[\u0020-\u002f]*[\u0040-\u007e])+";
+const BEL: char = '\u{7}';
+const TAB_WIDTH: usize = 8;
+
+/***
+Cursor: Where the next character goes, and the attributes it'll be drawn with.
+    `wrap_pending` is set after writing in the last column - the line only wraps once
+    another character arrives, like a real VT100.
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Cursor {
+    x: usize,
+    y: usize,
+    style: Style,
+    wrap_pending: bool,
+}
 
+/***
+InputProcessor: A VT100/xterm emulator for an app's output.
+    Text and escape sequences are applied to a Screen the size of the app's Window, which
+    is then copied into the Window's rectangle. Nothing the app prints reaches the real
+    terminal directly, so it can't draw outside its Window.
+ */
 pub struct InputProcessor {
     esc_seq: String,
-    buffer: String,
+    screen: Screen,
+    // The main screen, kept here while an app (like vim) uses the alternate one.
+    primary: Option<Screen>,
+    cursor: Cursor,
+    saved_cursor: Cursor,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    autowrap: bool,
+    origin_mode: bool,
+    insert_mode: bool,
+    cursor_visible: bool,
+    replies: String,
 }
 
 impl InputProcessor {
-    pub fn new() -> InputProcessor {
-        InputProcessor { esc_seq: String::new(), buffer: String::new() }
-    }
-
-    pub fn push(&mut self, s: String, location: &TermLocation, dimensions: &CharDims) {
-        let mut ms = s.clone();
-        while let Some(c) = pop(&mut ms) {
-            match c {
-                ESC => {
-                    self.esc_seq.push(c);
-                    self.handle_esc_seq(dimensions, location);
-                },
-                _ => match self.esc_seq.len() {
-                    0 => self.buffer.push(c),
-                    1 => {
-                        if c == '[' {
-                            self.esc_seq.push(c);
-                        } else {
-                            // Not an escape sequence!
-                            self.buffer.push_str(self.esc_seq.as_str());
-                            self.esc_seq.truncate(0);
-                        }
-                    },
-                    _ => {
-                        self.esc_seq.push(c);
-                        self.handle_esc_seq(dimensions, location);
-                    }
+    pub fn new(size: CharDims) -> InputProcessor {
+        InputProcessor {
+            esc_seq: String::new(),
+            screen: Screen::new(size),
+            primary: None,
+            cursor: Cursor::default(),
+            saved_cursor: Cursor::default(),
+            scroll_top: 0,
+            scroll_bottom: size.height.saturating_sub(1),
+            autowrap: true,
+            origin_mode: false,
+            insert_mode: false,
+            cursor_visible: true,
+            replies: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, s: String) {
+        if self.screen.size().width == 0 || self.screen.size().height == 0 { return; }
+
+        for c in s.chars() {
+            if self.esc_seq.is_empty() {
+                match c {
+                    ESC => self.esc_seq.push(c),
+                    _ if c.is_control() => self.control(c),
+                    _ => self.print_char(c),
+                }
+                continue;
+            }
+
+            if !in_string_sequence(&self.esc_seq) {
+                // A new escape cancels an unfinished one, and control characters still work mid-sequence.
+                if c == ESC {
+                    self.esc_seq.truncate(0);
+                } else if c.is_control() {
+                    self.control(c);
+                    continue;
                 }
             }
+
+            self.esc_seq.push(c);
+            if sequence_complete(&self.esc_seq) {
+                let sequence = std::mem::take(&mut self.esc_seq);
+                self.handle_esc_seq(&sequence);
+            }
+        }
+    }
+
+    pub fn screen(&self) -> &Screen { &self.screen }
+
+    /***
+     * The terminal's changed size. Like xterm, keep what fits, pull the cursor back onto
+     * the screen and reset the scroll region - the app will redraw once it hears about it.
+     ***/
+    pub fn resize(&mut self, size: CharDims) {
+        self.screen.resize(size);
+        if let Some(primary) = self.primary.as_mut() {
+            primary.resize(size);
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = size.height.saturating_sub(1);
+        for cursor in [&mut self.cursor, &mut self.saved_cursor] {
+            cursor.x = cursor.x.min(size.width.saturating_sub(1));
+            cursor.y = cursor.y.min(size.height.saturating_sub(1));
+            cursor.wrap_pending = false;
+        }
+    }
+
+    /// Where the app's cursor is on its screen, unless it's hidden it.
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        if self.cursor_visible { Some((self.cursor.x, self.cursor.y)) } else { None }
+    }
+
+    /// Answers to the app's questions (where's the cursor? what are you?), to be sent back to it.
+    pub fn take_replies(&mut self) -> String {
+        std::mem::take(&mut self.replies)
+    }
+
+    fn width(&self) -> usize { self.screen.size().width }
+
+    fn height(&self) -> usize { self.screen.size().height }
+
+    fn blank(&self) -> Cell { Cell::blank(self.cursor.style) }
+
+    fn print_char(&mut self, c: char) {
+        let width = c.width().unwrap_or(0).min(2);
+        if width == 0 {
+            // It goes with the last character printed - which the cursor's still on, if it's waiting to wrap.
+            let x = self.cursor.x + self.cursor.wrap_pending as usize;
+            self.screen.put_char(x, self.cursor.y, c, self.cursor.style);
+            return;
+        }
+
+        // A wide character that won't fit on what's left of the line starts the next one.
+        let wraps_early = self.cursor.x + width > self.width() && self.autowrap;
+        if self.cursor.wrap_pending || wraps_early {
+            self.cursor.wrap_pending = false;
+            if self.autowrap {
+                self.cursor.x = 0;
+                self.line_feed();
+            }
+        }
+
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        if x + width > self.width() { return; }
+        if self.insert_mode {
+            self.screen.insert_blanks(x, y, width, self.blank());
+        }
+        self.screen.put_char(x, y, c, self.cursor.style);
+
+        if x + width < self.width() {
+            self.cursor.x += width;
+        } else {
+            self.cursor.x = self.width() - 1;
+            self.cursor.wrap_pending = true;
         }
     }
 
-    pub fn print(&mut self, out: &mut io::Write) {
-        write!(out, "{}", self.buffer);
-        self.buffer.clear();
+    fn control(&mut self, c: char) {
+        match c {
+            '\r' => self.move_to_column(0),
+            '\n' | '\u{b}' | '\u{c}' => self.line_feed(),
+            '\u{8}' => self.move_to_column(self.cursor.x.saturating_sub(1)),
+            '\t' => self.move_to_column((self.cursor.x / TAB_WIDTH + 1) * TAB_WIDTH),
+            _ => {} // BEL, shift in/out and friends don't change the screen.
+        }
     }
 
-    fn handle_esc_seq(&mut self, dimensions: &CharDims, location: &TermLocation) {
-        // TODO: building regex is slow. Might need to make this a static value.
-        let vt100_regex = Regex::new(VT100_PATTERN).unwrap();
+    fn move_to_column(&mut self, x: usize) {
+        self.cursor.x = x.min(self.width() - 1);
+        self.cursor.wrap_pending = false;
+    }
 
-        if vt100_regex.is_match(self.esc_seq.as_str()) {
-            self.buffer.push_str(self.translated_escape_sequence(self.esc_seq.clone(), dimensions, location).as_str());
+    // Rows can be given relative to the scroll region (origin mode) - this is where row 0 is.
+    fn origin(&self) -> usize {
+        if self.origin_mode { self.scroll_top } else { 0 }
+    }
 
-            // Sequence handled, now clear the esc seq
-            self.esc_seq.truncate(0);
+    fn move_to(&mut self, x: usize, y: usize) {
+        let (top, bottom) = if self.origin_mode { (self.scroll_top, self.scroll_bottom) } else { (0, self.height() - 1) };
+        self.cursor.y = (self.origin() + y).clamp(top, bottom);
+        self.move_to_column(x);
+    }
+
+    // Moving up or down stops at the scroll region's edge, if the cursor started inside it.
+    fn move_up(&mut self, n: usize) {
+        let limit = if self.cursor.y >= self.scroll_top { self.scroll_top } else { 0 };
+        self.cursor.y = self.cursor.y.saturating_sub(n).max(limit);
+        self.cursor.wrap_pending = false;
+    }
+
+    fn move_down(&mut self, n: usize) {
+        let limit = if self.cursor.y <= self.scroll_bottom { self.scroll_bottom } else { self.height() - 1 };
+        self.cursor.y = (self.cursor.y + n).min(limit);
+        self.cursor.wrap_pending = false;
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.y == self.scroll_bottom {
+            self.screen.scroll_up(self.scroll_top, self.scroll_bottom, 1, self.blank());
+        } else if self.cursor.y + 1 < self.height() {
+            self.cursor.y += 1;
+        }
+        self.cursor.wrap_pending = false;
+    }
+
+    fn reverse_index(&mut self) {
+        if self.cursor.y == self.scroll_top {
+            self.screen.scroll_down(self.scroll_top, self.scroll_bottom, 1, self.blank());
+        } else if self.cursor.y > 0 {
+            self.cursor.y -= 1;
         }
+        self.cursor.wrap_pending = false;
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = self.cursor;
+    }
+
+    fn restore_cursor(&mut self) {
+        self.cursor = self.saved_cursor;
+        self.cursor.x = self.cursor.x.min(self.width() - 1);
+        self.cursor.y = self.cursor.y.min(self.height() - 1);
     }
 
-    fn translated_escape_sequence(&self, sequence: String, dimensions: &CharDims, location: &TermLocation) -> String {
-        // TODO: Handle translating important CSI sequences
-        //       I guess we'll see which ones end up important.
+    fn use_alternate_screen(&mut self, alternate: bool) {
+        match (alternate, self.primary.take()) {
+            (true, None) => {
+                let alternate = Screen::new(self.screen.size());
+                self.primary = Some(std::mem::replace(&mut self.screen, alternate));
+            },
+            (false, Some(primary)) => self.screen = primary,
+            (_, primary) => self.primary = primary,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = InputProcessor::new(self.screen.size());
+    }
+
+    fn handle_esc_seq(&mut self, sequence: &str) {
+        let mut chars = sequence.chars().skip(1);
+        match chars.next() {
+            Some('[') => self.handle_csi(&sequence[2..]),
+            Some('7') => self.save_cursor(),
+            Some('8') => self.restore_cursor(),
+            Some('D') => self.line_feed(),
+            Some('E') => {
+                self.move_to_column(0);
+                self.line_feed();
+            },
+            Some('M') => self.reverse_index(),
+            Some('c') => self.reset(),
+            // Titles (OSC), character sets, keypad modes and the like don't affect what's drawn.
+            _ => {}
+        }
+    }
+
+    fn handle_csi(&mut self, body: &str) {
         /***
             @	ICH	Insert the indicated # of blank characters.
             A	CUU	Move cursor up the indicated # of rows.
@@ -89,193 +282,383 @@ impl InputProcessor {
             L	IL	Insert the indicated # of blank lines.
             M	DL	Delete the indicated # of lines.
             P	DCH	Delete the indicated # of characters on current line.
+            S	SU	Scroll up the indicated # of lines.
+            T	SD	Scroll down the indicated # of lines.
             X	ECH	Erase the indicated # of characters on current line.
             a	HPR	Move cursor right the indicated # of columns.
             c	DA	Answer ESC [ ? 6 c: "I am a VT102".
             d	VPA	Move cursor to the indicated row, current column.
             e	VPR	Move cursor down the indicated # of rows.
             f	HVP	Move cursor to the indicated row, column.
-            g	TBC	Without parameter: clear tab stop at current position.
-                    ESC [ 3 g: delete all tab stops.
-            h	SM	Set Mode (see below).
-            l	RM	Reset Mode (see below).
-            m	SGR	Set attributes (see below).
-            n	DSR	Status report (see below).
-            q	DECLL	Set keyboard LEDs.
-                    ESC [ 0 q: clear all LEDs
-                    ESC [ 1 q: set Scroll Lock LED
-                    ESC [ 2 q: set Num Lock LED
-                    ESC [ 3 q: set Caps Lock LED
+            h	SM	Set Mode (see set_mode).
+            l	RM	Reset Mode (see set_mode).
+            m	SGR	Set attributes (see Style::apply_sgr).
+            n	DSR	Status report: 5 asks "are you OK?", 6 asks for the cursor position.
             r	DECSTBM	Set scrolling region; parameters are top and bottom row.
             s	?	Save cursor location.
             u	?	Restore cursor location.
             `	HPA	Move cursor to indicated column in current row
-         */
-
-        let cls = Regex::new((CSI.to_string()+"2J").as_str()).unwrap();
-        let goto = Regex::new((CSI.to_string()+r"\d+;\d+H").as_str()).unwrap();
 
-        if cls.is_match(sequence.as_str()) {
-            self.clear_screen(dimensions, location)
-        } else if goto.is_match(sequence.as_str()) {
-            self.rewite_goto(sequence.as_str(), location)
-        } else {
-            sequence
+            Tab stops (g), keyboard LEDs (q) and anything else are ignored.
+         */
+        let final_char = match body.chars().last() {
+            Some(c) => c,
+            None => return,
+        };
+        let body = &body[..body.len() - final_char.len_utf8()];
+        let private = body.chars().next().filter(|c| "<=>?".contains(*c));
+        let body = &body[private.map_or(0, |c| c.len_utf8())..];
+        // Intermediate characters (e.g. `CSI ! p`) pick a different function - none of which we support.
+        if body.chars().any(|c| (' '..='/').contains(&c)) { return; }
+
+        let params = parse_params(body);
+        // Most parameters are counts or positions, where a missing or zero value means 1.
+        let n = |i: usize| params.get(i).copied().filter(|p| *p > 0).unwrap_or(1) as usize;
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        let blank = self.blank();
+
+        match (private, final_char) {
+            (None, '@') => self.screen.insert_blanks(x, y, n(0), blank),
+            (None, 'A') => self.move_up(n(0)),
+            (None, 'B') | (None, 'e') => self.move_down(n(0)),
+            (None, 'C') | (None, 'a') => self.move_to_column(x + n(0)),
+            (None, 'D') => self.move_to_column(x.saturating_sub(n(0))),
+            (None, 'E') => {
+                self.move_down(n(0));
+                self.move_to_column(0);
+            },
+            (None, 'F') => {
+                self.move_up(n(0));
+                self.move_to_column(0);
+            },
+            (None, 'G') | (None, '`') => self.move_to_column(n(0) - 1),
+            (None, 'H') | (None, 'f') => self.move_to(n(1) - 1, n(0) - 1),
+            (None, 'd') => self.move_to(x, n(0) - 1),
+            (_, 'J') => self.erase_display(params.first().copied().unwrap_or(0)),
+            (_, 'K') => self.erase_line(params.first().copied().unwrap_or(0)),
+            (None, 'L') if (self.scroll_top..=self.scroll_bottom).contains(&y) => {
+                self.screen.scroll_down(y, self.scroll_bottom, n(0), blank);
+                self.move_to_column(0);
+            },
+            (None, 'M') if (self.scroll_top..=self.scroll_bottom).contains(&y) => {
+                self.screen.scroll_up(y, self.scroll_bottom, n(0), blank);
+                self.move_to_column(0);
+            },
+            (None, 'P') => self.screen.delete_chars(x, y, n(0), blank),
+            (None, 'S') => self.screen.scroll_up(self.scroll_top, self.scroll_bottom, n(0), blank),
+            (None, 'T') => self.screen.scroll_down(self.scroll_top, self.scroll_bottom, n(0), blank),
+            (None, 'X') => self.screen.erase(y, x, x + n(0), blank),
+            (None, 'c') if params.first().copied().unwrap_or(0) == 0 => self.replies.push_str("\u{1b}[?6c"),
+            (None, 'n') => match params.first() {
+                Some(5) => self.replies.push_str("\u{1b}[0n"),
+                Some(6) => self.replies.push_str(&format!("\u{1b}[{};{}R", y - self.origin() + 1, x + 1)),
+                _ => {}
+            },
+            (_, 'h') => params.iter().for_each(|mode| self.set_mode(private, *mode, true)),
+            (_, 'l') => params.iter().for_each(|mode| self.set_mode(private, *mode, false)),
+            (None, 'm') => self.cursor.style.apply_sgr(&params),
+            (None, 'r') => {
+                let top = n(0) - 1;
+                let bottom = params.get(1).copied().filter(|p| *p > 0).map_or(self.height(), |p| p as usize).min(self.height()) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            },
+            (None, 's') => self.save_cursor(),
+            (None, 'u') => self.restore_cursor(),
+            _ => {}
         }
     }
 
-    fn rewite_goto(&self, sequence: &str, location: &TermLocation) -> String {
-        let goto = Regex::new((CSI.to_string()+r"(\d+);(\d+)H").as_str()).unwrap();
-        let captures = goto.captures(sequence).unwrap();
-        let mut y = captures.get(1).unwrap().as_str().parse::<u16>().unwrap();
-        let mut x = captures.get(2).unwrap().as_str().parse::<u16>().unwrap();
-
-        x += location.x - 1;
-        y += location.y - 1;
-
-        format!("\u{1B}[{};{}H", y, x)
+    /***
+     * SM/RM. The modes that change what's drawn:
+     *   4      IRM     Insert characters rather than overwriting them.
+     *   ?6     DECOM   Cursor positions are relative to the scroll region.
+     *   ?7     DECAWM  Wrap at the end of a line.
+     *   ?25    DECTCEM Show the cursor.
+     *   ?47, ?1047     Use the alternate screen.
+     *   ?1048          Save (set) or restore (reset) the cursor.
+     *   ?1049          Both: save the cursor and switch to a cleared alternate screen.
+     ***/
+    fn set_mode(&mut self, private: Option<char>, mode: u16, on: bool) {
+        match (private, mode) {
+            (None, 4) => self.insert_mode = on,
+            (Some('?'), 6) => {
+                self.origin_mode = on;
+                self.move_to(0, 0);
+            },
+            (Some('?'), 7) => self.autowrap = on,
+            (Some('?'), 25) => self.cursor_visible = on,
+            (Some('?'), 47) | (Some('?'), 1047) => self.use_alternate_screen(on),
+            (Some('?'), 1048) if on => self.save_cursor(),
+            (Some('?'), 1048) => self.restore_cursor(),
+            (Some('?'), 1049) if on => {
+                self.save_cursor();
+                self.use_alternate_screen(true);
+            },
+            (Some('?'), 1049) => {
+                self.use_alternate_screen(false);
+                self.restore_cursor();
+            },
+            _ => {}
+        }
     }
 
-    fn clear_screen(&self, dimensions: &CharDims, location: &TermLocation) -> String{
-        let mut clear = String::new();
-        for y in 0..dimensions.height {
-            // Go to each line in the window, then print a bunch of spaces to clear the region.
-            clear.push_str(format!("{}{:width$}",
-                                   Goto(location.x, location.y + y as u16),
-                                   " ", width=dimensions.width).as_str());
+    fn erase_display(&mut self, how: u16) {
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        let (width, blank) = (self.width(), self.blank());
+        match how {
+            0 => {
+                self.screen.erase(y, x, width, blank);
+                (y + 1..self.height()).for_each(|row| self.screen.erase(row, 0, width, blank));
+            },
+            1 => {
+                (0..y).for_each(|row| self.screen.erase(row, 0, width, blank));
+                self.screen.erase(y, 0, x + 1, blank);
+            },
+            2 | 3 => (0..self.height()).for_each(|row| self.screen.erase(row, 0, width, blank)),
+            _ => {}
         }
+    }
 
-        clear
+    fn erase_line(&mut self, how: u16) {
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        let (width, blank) = (self.width(), self.blank());
+        match how {
+            0 => self.screen.erase(y, x, width, blank),
+            1 => self.screen.erase(y, 0, x + 1, blank),
+            2 => self.screen.erase(y, 0, width, blank),
+            _ => {}
+        }
     }
 }
 
+// OSC, DCS and friends carry arbitrary text (including ESC) until their terminator.
+fn in_string_sequence(seq: &str) -> bool {
+    matches!(seq.chars().nth(1), Some(']') | Some('P') | Some('_') | Some('^'))
+}
 
-fn pop(s: &mut String) -> Option<char> {
-    match s.len() {
-        0 => None,
-        _ => Some(s.remove(0))
+fn sequence_complete(seq: &str) -> bool {
+    match seq.chars().nth(1) {
+        None => false,
+        Some('[') => seq.len() > 2 && seq.chars().last().is_some_and(|c| ('@'..='~').contains(&c)),
+        Some(_) if in_string_sequence(seq) => seq.ends_with(BEL) || seq.ends_with("\u{1b}\\"),
+        // Character set selection and the like: ESC, an intermediate, then one more character.
+        Some(c) if (' '..='/').contains(&c) => seq.chars().count() >= 3,
+        Some(_) => true,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::mpsc;
-    use std::sync::mpsc::Sender;
-
-    use crate::views::DimConstraint;
+    use crate::views::screen::Color;
 
     use super::*;
 
     fn subject() -> InputProcessor {
-        InputProcessor::new()
+        InputProcessor::new(CharDims::new(5, 3))
+    }
+
+    fn rows(iproc: &InputProcessor) -> Vec<String> {
+        (0..iproc.height()).map(|y| iproc.screen.row_text(y)).collect()
     }
 
     #[test]
-    fn sends_plain_text_to_output() {
+    fn sends_plain_text_to_the_screen() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(5, 2);
-        iproc.push("abcd".to_string(), &location, &dimensions);
+        iproc.push("abcd".to_string());
 
-        assert_eq!(iproc.buffer, "abcd".to_string())
+        assert_eq!(rows(&iproc), vec!["abcd ", "     ", "     "]);
+        assert_eq!((iproc.cursor.x, iproc.cursor.y), (4, 0));
     }
 
     #[test]
-    fn clears_plain_text_after_print() {
+    fn wide_characters_take_two_cells_and_wrap_whole() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(5, 2);
-        iproc.push("abcd".to_string(), &location, &dimensions);
+        iproc.push("a日本e\u{301}".to_string());
 
-        let mut output = Vec::new();
-        iproc.print(&mut output);
+        assert_eq!(rows(&iproc), vec!["a日本", "e\u{301}    ", "     "]);
+        assert!(iproc.screen.cell(2, 0).is_wide_tail());
+        assert_eq!((iproc.cursor.x, iproc.cursor.y), (1, 1));
+    }
 
-        assert_eq!(output, b"abcd")
+    #[test]
+    fn combining_characters_join_the_last_one_printed() {
+        let mut iproc = subject();
+        // The accent on the last column's character comes while the cursor's waiting to wrap.
+        iproc.push("abc日\u{308}\r\n\u{301}x".to_string());
+
+        assert_eq!(rows(&iproc), vec!["abc日\u{308}", "x    ", "     "]);
+        assert_eq!((iproc.cursor.x, iproc.cursor.y), (1, 1));
+    }
+
+    #[test]
+    fn reports_the_cursor_unless_its_hidden() {
+        let mut iproc = subject();
+        iproc.push("ab\r\nc".to_string());
+        assert_eq!(iproc.cursor(), Some((1, 1)));
+
+        iproc.push("\u{1b}[?25l".to_string());
+        assert_eq!(iproc.cursor(), None);
     }
 
     #[test]
     fn captures_partial_esc_sequence() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(5, 2);
-        iproc.push("\u{1B}[3".to_string(), &location, &dimensions);
+        iproc.push("\u{1B}[3".to_string());
 
-        assert_eq!(iproc.buffer, "".to_string());
         assert_eq!(iproc.esc_seq, "\u{1B}[3".to_string());
+        assert_eq!(rows(&iproc)[0], "     ");
     }
 
     #[test]
-    fn prints_complete_esc_sequence() {
+    fn applies_esc_sequence_across_strings() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(5, 2);
-        iproc.push("\u{1B}[30m".to_string(), &location, &dimensions);
+        iproc.push("ab\u{1B}[3".to_string());
+        iproc.push("1mc".to_string());
 
-        assert_eq!(iproc.buffer, "\u{1B}[30m".to_string());
-        assert_eq!(iproc.esc_seq, "".to_string());
+        assert_eq!(iproc.esc_seq, "");
+        assert_eq!(iproc.screen.cell(1, 0).style, Style::default());
+        assert_eq!(iproc.screen.cell(2, 0).style.fg, Color::Indexed(1));
     }
 
     #[test]
-    fn prints_complete_esc_sequence_in_mid_string() {
+    fn ignores_titles_and_character_sets() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(5, 2);
-        iproc.push("some text \u{1B}[30m and some more text".to_string(), &location, &dimensions);
+        iproc.push("\u{1b}]0;title\u{7}a\u{1b}]2;x\u{1b}\\\u{1b}(Bb".to_string());
 
-        assert_eq!(iproc.buffer, "some text \u{1B}[30m and some more text".to_string());
-        assert_eq!(iproc.esc_seq, "".to_string());
+        assert_eq!(rows(&iproc)[0], "ab   ");
     }
 
     #[test]
-    fn hold_partial_esc_sequence_in_mid_string() {
+    fn wraps_and_scrolls_at_the_bottom() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(5, 2);
-        iproc.push("some text \u{1B}[3".to_string(), &location, &dimensions);
+        iproc.push("12345".to_string());
+        // The cursor waits at the end of the line until there's something to wrap.
+        assert_eq!((iproc.cursor.x, iproc.cursor.y), (4, 0));
 
-        assert_eq!(iproc.buffer, "some text ".to_string());
-        assert_eq!(iproc.esc_seq, "\u{1B}[3".to_string());
+        iproc.push("6\r\nline3\r\nend".to_string());
+        assert_eq!(rows(&iproc), vec!["6    ", "line3", "end  "]);
+    }
+
+    #[test]
+    fn clears_the_screen() {
+        let mut iproc = subject();
+        iproc.push("some text \u{1B}[2J".to_string());
+
+        assert_eq!(rows(&iproc), vec!["     ", "     ", "     "]);
+    }
+
+    #[test]
+    fn erases_parts_of_lines() {
+        let mut iproc = subject();
+        iproc.push("abcde\u{1b}[3G\u{1b}[K\r\nabcde\u{1b}[3G\u{1b}[1K\r\nabcde\u{1b}[2G\u{1b}[2X".to_string());
+
+        assert_eq!(rows(&iproc), vec!["ab   ", "   de", "a  de"]);
+    }
+
+    #[test]
+    fn follows_goto_sequences() {
+        let mut iproc = subject();
+        iproc.push("\u{1b}[2;3Hx\u{1b}[1;1Hy".to_string());
+
+        assert_eq!(rows(&iproc), vec!["y    ", "  x  ", "     "]);
+    }
+
+    #[test]
+    fn follows_goto_sequences_with_missing_positions() {
+        let mut iproc = subject();
+        iproc.push("\u{1b}[3Ha\u{1b}[Hb\u{1b}[;4Hc".to_string());
+
+        assert_eq!(rows(&iproc), vec!["b  c ", "     ", "a    "]);
     }
 
     #[test]
-    fn prints_esc_sequence_across_strings() {
+    fn keeps_the_cursor_on_the_screen() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(5, 2);
-        // put some text and a partial esc sequence in the buffer
-        iproc.push("some text \u{1B}[3".to_string(), &location, &dimensions);
+        iproc.push("\u{1b}[99;99Hx\u{1b}[9Ay\u{1b}[9Dz".to_string());
 
-        // empty buffer of non-escape sequence text
-        iproc.print(&mut Vec::new());
+        assert_eq!(rows(&iproc), vec!["z   y", "     ", "    x"]);
+    }
 
-        // complete the escape sequence
-        iproc.push("0m".to_string(), &location, &dimensions);
+    #[test]
+    fn inserts_and_deletes_characters() {
+        let mut iproc = subject();
+        iproc.push("abcd\u{1b}[2G\u{1b}[2@\r\n1234\u{1b}[2G\u{1b}[P".to_string());
 
-        // the entire escape sequence prints together
-        assert_eq!(iproc.buffer, "\u{1B}[30m".to_string());
+        assert_eq!(rows(&iproc), vec!["a  bc", "134  ", "     "]);
     }
 
     #[test]
-    fn rewrites_cls_sequences() {
+    fn inserts_and_deletes_lines() {
         let mut iproc = subject();
-        let location= TermLocation::new(1, 1);
-        let dimensions = CharDims::new(3, 2);
-        // put some text and a clear screen esc sequence in the buffer
-        iproc.push("some text \u{1B}[2J".to_string(), &location, &dimensions);
+        iproc.push("a\r\nb\r\nc\u{1b}[2H\u{1b}[L".to_string());
+        assert_eq!(rows(&iproc), vec!["a    ", "     ", "b    "]);
 
-        // should be rewritten to a bunch of empty space - following the preceding text
-        assert_eq!(iproc.buffer, "some text \u{1b}[1;1H   \u{1b}[2;1H   ")
+        iproc.push("\u{1b}[1H\u{1b}[2M".to_string());
+        assert_eq!(rows(&iproc), vec!["b    ", "     ", "     "]);
     }
 
     #[test]
-    fn rewrites_goto_sequences() {
+    fn scrolls_within_the_scroll_region() {
         let mut iproc = subject();
-        let location= TermLocation::new(5, 2);
-        let dimensions = CharDims::new(3, 2);
-        let goto_topleft = "\u{1b}[1;1H";
-        iproc.push(goto_topleft.to_string(), &location, &dimensions);
+        iproc.push("top\u{1b}[2;3r\u{1b}[2Ha\r\nb\r\nc".to_string());
+        assert_eq!(rows(&iproc), vec!["top  ", "b    ", "c    "]);
 
-        // should be rewritten to a new location based on the widget's location (5, 2)
-        assert_eq!(iproc.buffer, "\u{1b}[2;5H")
+        // Reverse index at the top of the region scrolls it back down.
+        iproc.push("\u{1b}[2H\u{1b}M".to_string());
+        assert_eq!(rows(&iproc), vec!["top  ", "     ", "b    "]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn styles_text_with_sgr() {
+        let mut iproc = subject();
+        iproc.push("\u{1b}[1;32ma\u{1b}[0mb".to_string());
+
+        assert_eq!(iproc.screen.cell(0, 0).style, Style { bold: true, fg: Color::Indexed(2), ..Style::default() });
+        assert_eq!(iproc.screen.cell(1, 0).style, Style::default());
+    }
+
+    #[test]
+    fn saves_and_restores_the_cursor() {
+        let mut iproc = subject();
+        iproc.push("ab\u{1b}7\u{1b}[31m\u{1b}[3;1Hc\u{1b}8d\u{1b}[s\u{1b}[2Hx\u{1b}[ue".to_string());
+
+        assert_eq!(rows(&iproc), vec!["abde ", "x    ", "c    "]);
+        assert_eq!(iproc.screen.cell(2, 0).style, Style::default());
+    }
+
+    #[test]
+    fn alternate_screen_leaves_the_main_one_alone() {
+        let mut iproc = subject();
+        iproc.push("shell\u{1b}[2;1H\u{1b}[?1049hvim".to_string());
+        assert_eq!(rows(&iproc), vec!["     ", "vim  ", "     "]);
+
+        iproc.push("\u{1b}[?1049l$".to_string());
+        assert_eq!(rows(&iproc), vec!["shell", "$    ", "     "]);
+    }
+
+    #[test]
+    fn answers_status_reports() {
+        let mut iproc = subject();
+        iproc.push("\u{1b}[2;3H\u{1b}[6n\u{1b}[c".to_string());
+
+        assert_eq!(iproc.take_replies(), "\u{1b}[2;3R\u{1b}[?6c");
+        assert_eq!(iproc.take_replies(), "");
+    }
+
+    #[test]
+    fn resizing_keeps_the_cursor_on_screen() {
+        let mut iproc = subject();
+        iproc.push("one\r\ntwo\r\nthree".to_string());
+        iproc.resize(CharDims::new(3, 2));
+
+        assert_eq!(rows(&iproc), vec!["one", "two"]);
+        assert_eq!(iproc.cursor(), Some((2, 1)));
+
+        iproc.push("\r\nx".to_string());
+        assert_eq!(rows(&iproc), vec!["two", "x  "]);
+    }
+}
@@ -1,22 +1,30 @@
-use crate::views::{LinearLayout, Orientation, View, DimConstraint, Dimensions, desired_size, CharDims, ViewId, TermLocation};
+use crate::views::{LinearLayout, Orientation, View, DimConstraint, Dimensions, desired_size, container_size, share, CharDims, ViewId, TermLocation};
 use std::cmp::{min, max};
 use log::info;
 use uuid::Uuid;
 use std::slice::IterMut;
+use crate::views::screen::Screen;
+use crate::views::decoration::Decoration;
 
 
 impl LinearLayout {
     pub fn new(orientation: Orientation, width: DimConstraint, height: DimConstraint, location: TermLocation) -> LinearLayout {
         LinearLayout {
             id: Uuid::new_v4().to_string(),
-            orientation: orientation,
+            orientation,
             dims: Dimensions::new(width, height),
-            location: location,
+            location,
             children: vec![],
             visible: true,
+            dirty: true,
+            decoration: Decoration::default(),
         }
     }
 
+    pub fn set_decoration(&mut self, decoration: Decoration) {
+        self.decoration = decoration;
+    }
+
     pub fn add_child(&mut self, child: Box<dyn View>) {
         self.children.push(child);
     }
@@ -27,11 +35,11 @@ impl LinearLayout {
         // e.g. for Vertical, we stack by height, so sum those.
         //      ...then stretch sideways to the max child width.
         match orientation {
-            Orientation::HORIZONTAL => {
+            Orientation::Horizontal => {
                 CharDims::new(childrens_desired_dims.width + child_dims.width,
                  max(childrens_desired_dims.height, child_dims.height))
             }
-            Orientation::VERTICAL => {
+            Orientation::Vertical => {
                 CharDims::new(max(childrens_desired_dims.width, child_dims.width),
                  childrens_desired_dims.height + child_dims.height)
             }
@@ -43,80 +51,125 @@ impl LinearLayout {
         // Ignore in the direction we are stretching.
         // e.g. for Vertical, we stack by height, so subtract each child from that.
         match orientation {
-            Orientation::VERTICAL => {
+            Orientation::Vertical => {
                 CharDims::new(remaining_parent_dims.width,
-                 if remaining_parent_dims.height >= child_dims.height { remaining_parent_dims.height - child_dims.height } else { 0 })
+                 remaining_parent_dims.height.saturating_sub(child_dims.height))
             }
-            Orientation::HORIZONTAL => {
-                CharDims::new (if remaining_parent_dims.width >= child_dims.width { remaining_parent_dims.width - child_dims.width } else { 0 },
+            Orientation::Horizontal => {
+                CharDims::new (remaining_parent_dims.width.saturating_sub(child_dims.width),
                  remaining_parent_dims.height)
             }
         }
     }
 }
 
+impl Orientation {
+    // A size's extent in the direction children are stacked.
+    fn along(&self, dims: &CharDims) -> usize {
+        match self {
+            Orientation::Horizontal => dims.width,
+            Orientation::Vertical => dims.height,
+        }
+    }
+
+    // A child's weight, if it's weighted in the direction children are stacked.
+    fn weight(&self, (width, height): (DimConstraint, DimConstraint)) -> Option<usize> {
+        match (self, width, height) {
+            (Orientation::Horizontal, DimConstraint::Weight(w), _) => Some(w),
+            (Orientation::Vertical, _, DimConstraint::Weight(w)) => Some(w),
+            _ => None,
+        }
+    }
+
+    /***
+     * The room to offer a child inside a layout of size `full`, with `left` still free in the
+     * direction children are stacked. Percentages are of the whole layout, but can't take more than's left.
+     ***/
+    fn room(&self, (width, height): (DimConstraint, DimConstraint), full: &CharDims, left: usize) -> CharDims {
+        let percent_of = |constraint: DimConstraint, whole: usize| match constraint {
+            DimConstraint::Percent(p) => whole * p / 100,
+            _ => whole,
+        };
+        match self {
+            Orientation::Horizontal => CharDims::new(min(percent_of(width, full.width), left), percent_of(height, full.height)),
+            Orientation::Vertical => CharDims::new(percent_of(width, full.width), min(percent_of(height, full.height), left)),
+        }
+    }
+}
+
 impl View for LinearLayout {
     fn id(&self) -> ViewId {
         self.id.clone()
     }
 
     fn dirty(&self) -> bool {
-        self.children.iter().any(|c| c.dirty())
+        self.dirty || self.children.iter().any(|c| c.dirty())
     }
 
     fn wash(&mut self) {
+        self.dirty = false;
         self.children.iter_mut().for_each(|c| c.wash());
     }
 
     fn inflate(&mut self, parent_dimensions: &CharDims, location: &TermLocation) -> CharDims {
-        self.location = location.clone();
+        self.location = *location;
         if !self.visible {
             self.dims.size = CharDims::new(0, 0);
             return self.dims.size;
         }
 
         let mut childrens_desired_dims = CharDims::new(0, 0);
-        let most_restrictive_width = min(self.dims.width_constraint, DimConstraint::Fixed(parent_dimensions.width));
-        let most_restrictive_height = min(self.dims.height_constraint, DimConstraint::Fixed(parent_dimensions.height));
+        let most_restrictive_width = min(self.dims.width_constraint.resolve(parent_dimensions.width), DimConstraint::Fixed(parent_dimensions.width));
+        let most_restrictive_height = min(self.dims.height_constraint.resolve(parent_dimensions.height), DimConstraint::Fixed(parent_dimensions.height));
 
         self.dims.size = CharDims::new(desired_size(&most_restrictive_width),
                           desired_size(&most_restrictive_height));
+        self.dims.available = self.dims.size;
 
-        let mut remaining_parent_dims = self.dims.size.clone();
-
-        let mut next_child_loc = location.clone();
+        // First pass: everything but the weighted children, to see how much room they leave.
+        let full = self.decoration.shrink(&self.dims.size);
+        let mut remaining_parent_dims = full;
+        let mut rooms = Vec::with_capacity(self.children.len());
         for child in &mut self.children {
-            let child_dims = child.inflate(&remaining_parent_dims, &next_child_loc);
-            childrens_desired_dims = LinearLayout::update_child_dims(self.orientation, childrens_desired_dims, child_dims);
+            if self.orientation.weight(child.constraints()).is_some() {
+                rooms.push(None);
+                continue;
+            }
+            let room = self.orientation.room(child.constraints(), &full, self.orientation.along(&remaining_parent_dims));
+            let child_dims = child.inflate(&room, &self.decoration.content_location(location));
             remaining_parent_dims = LinearLayout::update_parent_dims(self.orientation, remaining_parent_dims, child_dims);
+            rooms.push(Some(room));
+        }
+
+        // The weighted children share what's left between them.
+        let weights = self.children.iter().map(|c| self.orientation.weight(c.constraints()).unwrap_or(0)).collect::<Vec<usize>>();
+        let mut shares = share(self.orientation.along(&remaining_parent_dims), &weights).into_iter();
+
+        // Second pass: place each child, now everyone's room is known.
+        let mut next_child_loc = self.decoration.content_location(location);
+        for (child, room) in self.children.iter_mut().zip(rooms) {
+            let share = shares.next().unwrap_or(0);
+            let orientation = self.orientation;
+            let room = room.unwrap_or_else(|| orientation.room(child.constraints(), &full, share));
+            let child_dims = child.inflate(&room, &next_child_loc);
+            childrens_desired_dims = LinearLayout::update_child_dims(self.orientation, childrens_desired_dims, child_dims);
 
             next_child_loc = match self.orientation {
-                Orientation::HORIZONTAL =>  { TermLocation::new(next_child_loc.x + child.width() as u16, next_child_loc.y) },
-                Orientation::VERTICAL =>    { TermLocation::new(next_child_loc.x, next_child_loc.y + child.height() as u16) }
+                Orientation::Horizontal =>  { TermLocation::new(next_child_loc.x + child.width() as u16, next_child_loc.y) },
+                Orientation::Vertical =>    { TermLocation::new(next_child_loc.x, next_child_loc.y + child.height() as u16) }
             };
         }
 
-        let new_most_restrictive_width = min(DimConstraint::Fixed(childrens_desired_dims.width), most_restrictive_width);
-        let new_most_restrictive_height = min(DimConstraint::Fixed(childrens_desired_dims.height), most_restrictive_height);
-
-        self.dims.size.width = match new_most_restrictive_width {
-            DimConstraint::Fixed(n) => n,
-            DimConstraint::UpTo(n) => n,
-            DimConstraint::WrapContent => 0 // Only happens if we're a "WrapContent" and have 0 or empty children
-        };
-
-        self.dims.size.height = match new_most_restrictive_height {
-            DimConstraint::Fixed(n) => n,
-            DimConstraint::UpTo(n) => n,
-            DimConstraint::WrapContent => 0 // Only happens if we're a "WrapContent" and have 0 or empty children
-        };
+        let childrens_desired_dims = self.decoration.grow(&childrens_desired_dims);
+        self.dims.size = CharDims::new(container_size(self.dims.width_constraint, parent_dimensions.width, childrens_desired_dims.width),
+                                       container_size(self.dims.height_constraint, parent_dimensions.height, childrens_desired_dims.height));
 
         if self.height() == 0 {
             info!("LL {:?} Dimensions: {}x{}", self.orientation, self.width(), self.height());
             info!("LL zero height child dims height: {}; constraint: {:?}", childrens_desired_dims.height, self.dims.height_constraint);
         }
 
-        self.dims.size.clone()
+        self.dims.size
     }
 
     fn constraints(&self) -> (DimConstraint, DimConstraint) { (self.dims.width_constraint, self.dims.height_constraint) }
@@ -125,19 +178,38 @@ impl View for LinearLayout {
 
     fn height(&self) -> usize { self.dims.size.height }
 
-    fn render(&self) -> String {
-        if !self.visible || !self.dirty() { return String::new() }
-        self.children.iter().map(|c| c.render()).collect::<Vec<String>>().join("")
+    fn location(&self) -> TermLocation { self.location }
+
+    fn bounds(&self) -> CharDims { self.dims.available }
+
+    fn content_area(&self) -> (TermLocation, CharDims) {
+        (self.decoration.content_location(&self.location), self.decoration.shrink(&self.dims.available))
     }
 
-    fn children(&mut self) -> IterMut<Box<dyn View>> {
+    fn render(&self, screen: &mut Screen) {
+        if !self.visible { return }
+        self.decoration.draw(screen, &self.location, &self.dims.size);
+        self.children.iter().for_each(|c| c.render(screen));
+    }
+
+    fn children(&mut self) -> IterMut<'_, Box<dyn View>> {
         self.children.iter_mut()
     }
 
     fn update_content(&mut self, _: String) {
         // No-op - you can't replace text in a LL.
         // I know this breaks Liscov substitution and I'm not much happier about it.
-        return;
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if visible != self.visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
     }
 }
 
@@ -147,6 +219,7 @@ mod tests {
     use super::*;
     use crate::views::Widget;
     use crate::hexterm::formatting::Vt100Formatter;
+    use crate::views::rendered_rows;
 
     fn fixed_size_text_widget() -> Widget {
         let mut tw = Widget::new(DimConstraint::Fixed(10), DimConstraint::Fixed(2), Box::new(Vt100Formatter{}), TermLocation::new(1, 1));
@@ -161,15 +234,15 @@ mod tests {
     }
 
     fn vert_ll_with_wrap_content() -> LinearLayout {
-        LinearLayout::new(Orientation::VERTICAL, DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1))
+        LinearLayout::new(Orientation::Vertical, DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1))
     }
 
     fn vert_ll_with_fixed_size() -> LinearLayout {
-        LinearLayout::new(Orientation::VERTICAL, DimConstraint::Fixed(5), DimConstraint::Fixed(2), TermLocation::new(1, 1))
+        LinearLayout::new(Orientation::Vertical, DimConstraint::Fixed(5), DimConstraint::Fixed(2), TermLocation::new(1, 1))
     }
 
     fn horz_ll_with_wrap_content() -> LinearLayout {
-        LinearLayout::new(Orientation::HORIZONTAL, DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1))
+        LinearLayout::new(Orientation::Horizontal, DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1))
     }
 
     #[test]
@@ -222,13 +295,56 @@ mod tests {
         assert_eq!(ll.height(), 3);
     }
 
+    fn text_widget(width: DimConstraint, height: DimConstraint) -> Box<dyn View> {
+        let mut tw = Widget::new(width, height, Box::new(Vt100Formatter{}), TermLocation::new(1, 1));
+        tw.text = "text".to_owned();
+        Box::new(tw)
+    }
+
+    #[test]
+    fn weighted_children_share_whats_left_over() {
+        let mut ll = horz_ll_with_wrap_content();
+        ll.add_child(text_widget(DimConstraint::Weight(2), DimConstraint::WrapContent));
+        ll.add_child(text_widget(DimConstraint::Fixed(4), DimConstraint::WrapContent));
+        ll.add_child(text_widget(DimConstraint::Weight(1), DimConstraint::WrapContent));
+        ll.inflate(&CharDims::new(24, 10), &TermLocation::new(1, 1));
+
+        let widths = ll.children().map(|c| (c.location().x, c.width())).collect::<Vec<(u16, usize)>>();
+        assert_eq!(widths, vec![(1, 14), (15, 4), (19, 6)]);
+        assert_eq!(ll.width(), 24);
+    }
+
+    #[test]
+    fn percentages_are_of_the_whole_layout() {
+        let mut ll = LinearLayout::new(Orientation::Vertical, DimConstraint::Fixed(20), DimConstraint::Fixed(10), TermLocation::new(1, 1));
+        ll.add_child(text_widget(DimConstraint::Percent(50), DimConstraint::Percent(30)));
+        ll.add_child(text_widget(DimConstraint::WrapContent, DimConstraint::Percent(100)));
+        ll.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        let sizes = ll.children().map(|c| (c.width(), c.height())).collect::<Vec<(usize, usize)>>();
+        // The second child can't have 100% - only 7 rows are left.
+        assert_eq!(sizes, vec![(10, 3), (4, 7)]);
+    }
+
+    #[test]
+    fn children_sit_inside_the_padding() {
+        let mut ll = vert_ll_with_wrap_content();
+        ll.set_decoration(Decoration { padding: 1, ..Decoration::default() });
+        ll.add_child(text_widget(DimConstraint::WrapContent, DimConstraint::WrapContent));
+        ll.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        assert_eq!((ll.width(), ll.height()), (6, 3));
+        assert_eq!(ll.children().next().unwrap().location(), TermLocation::new(2, 2));
+    }
+
     #[test]
     fn vert_rendering_works() {
         let mut ll = vert_ll_with_wrap_content();
         ll.add_child(Box::new(fixed_size_text_widget()));
         ll.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
 
-        assert_eq!("\u{1b}[1;1HThis is so\u{1b}[2;1Hwith multi".to_string(), ll.render());
+        // The text's taller than the widget, so it shows there's more below.
+        assert_eq!(rendered_rows(&ll, CharDims::new(12, 3)), vec!["This is so", "with mult↓", ""]);
     }
 
     #[test]
@@ -238,7 +354,7 @@ mod tests {
         ll.add_child(Box::new(fixed_size_text_widget()));
         ll.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
 
-        assert_eq!("\u{1b}[1;1HThis is so\u{1b}[2;1Hwith multi\u{1b}[3;1HThis is so\u{1b}[4;1Hwith multi".to_string(), ll.render());
+        assert_eq!(rendered_rows(&ll, CharDims::new(12, 4)), vec!["This is so", "with mult↓", "This is so", "with mult↓"]);
     }
 
     #[test]
@@ -248,7 +364,7 @@ mod tests {
         ll.add_child(Box::new(fixed_size_text_widget()));
         ll.add_child(Box::new(fixed_size_text_widget()));
         ll.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
-        assert_eq!("\u{1b}[1;1HThis is so\u{1b}[2;1Hwith multi\u{1b}[1;11HThis is so\u{1b}[2;11Hwith multi\u{1b}[1;21HThis is so\u{1b}[2;21Hwith multi".to_string(), ll.render());
+        assert_eq!(rendered_rows(&ll, CharDims::new(30, 2)), vec!["This is soThis is soThis is so", "with mult↓with mult↓with mult↓"]);
     }
 
     #[test]
@@ -257,7 +373,7 @@ mod tests {
         ll.add_child(Box::new(fixed_size_text_widget()));
         ll.visible = false;
         ll.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
-        assert_eq!(String::from(""), ll.render());
+        assert_eq!(rendered_rows(&ll, CharDims::new(3, 1)), vec![""]);
     }
 
     #[test]
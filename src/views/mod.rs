@@ -1,11 +1,18 @@
-use std::cmp::Ordering;
+use std::cmp::{min, Ordering};
 use crate::hexterm::formatting::TextFormatter;
 use std::slice::IterMut;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use crate::views::screen::Screen;
+use crate::views::decoration::Decoration;
 
 mod linear_layout;
+mod grid_layout;
+mod stack_layout;
 mod widget;
-mod window;
-mod interactive_widget;
+pub mod window;
+pub mod screen;
+pub mod decoration;
 mod input_processor;
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
@@ -22,28 +29,65 @@ DimConstraint: Represents a constraint on layout.
     WrapContent -> Takes its size from the size of its children.
     Fixed(n)    -> Always 'n' characters, until the limits of the container or terminal get in the way.
     UpTo(n)     -> Resizes based on content between 0 and n characters.
+    Between(m, n) -> Resizes based on content, but never less than m or more than n characters.
+    Percent(n)  -> n% of the containing LinearLayout, in either direction.
+    Weight(n)   -> A share of whatever a LinearLayout has left once everything else is placed,
+                   in proportion to the other weighted children. Across the layout, it fills.
+    The containing layout works out Percent and Weight, and offers the View exactly that much room.
  */
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum DimConstraint {
     WrapContent,
     Fixed(usize),
     UpTo(usize),
-    // Between(usize, usize),
+    Between(usize, usize),
+    Percent(usize),
+    Weight(usize),
 }
 
 impl DimConstraint {
-    fn to_ord(&self) -> usize {
+    fn to_ord(self) -> usize {
         match self {
-            DimConstraint::UpTo(x) => *x,
-            DimConstraint::Fixed(x) => *x,
-            DimConstraint::WrapContent => 1_000_000_000,
+            DimConstraint::UpTo(x) => x,
+            DimConstraint::Fixed(x) => x,
+            DimConstraint::Between(_, x) => x,
+            // Unresolved, these take all the room they're given - see `resolve`.
+            DimConstraint::WrapContent | DimConstraint::Percent(_) | DimConstraint::Weight(_) => 1_000_000_000,
+        }
+    }
+
+    /// Percent and Weight are sized by the containing layout - the View itself fills whatever room it's given.
+    pub fn fills(&self) -> bool {
+        matches!(self, DimConstraint::Percent(_) | DimConstraint::Weight(_))
+    }
+
+    /// A View's own view of its constraint, given `room` by its parent.
+    pub fn resolve(self, room: usize) -> DimConstraint {
+        if self.fills() { DimConstraint::Fixed(room) } else { self }
+    }
+
+    /// The same constraint, on something `n` characters smaller.
+    pub fn less(self, n: usize) -> DimConstraint {
+        match self {
+            DimConstraint::Fixed(x) => DimConstraint::Fixed(x.saturating_sub(n)),
+            DimConstraint::UpTo(x) => DimConstraint::UpTo(x.saturating_sub(n)),
+            DimConstraint::Between(x, y) => DimConstraint::Between(x.saturating_sub(n), y.saturating_sub(n)),
+            other => other,
+        }
+    }
+
+    /// The least a View with this constraint shrinks to, room permitting.
+    pub fn least(&self) -> usize {
+        match self {
+            DimConstraint::Between(x, _) => *x,
+            _ => 0,
         }
     }
 }
 
 impl PartialOrd for DimConstraint {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.to_ord().cmp(&other.to_ord()))
+        Some(self.cmp(other))
     }
 }
 
@@ -52,6 +96,52 @@ impl Ord for DimConstraint {
         self.to_ord().cmp(&other.to_ord())
     }
 }
+/***
+Scroll: Moves a View's content around inside it.
+    Lines(n) -> n lines further down (or up, if n is negative).
+    Pages(n) -> n screenfuls further down (or up).
+    Top      -> Back to the first line.
+    Bottom   -> To the last line. A 'follow' Widget stays there as more output arrives.
+ */
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Scroll {
+    Lines(isize),
+    Pages(isize),
+    Top,
+    Bottom,
+}
+
+/***
+Overflow: What a Widget does with lines too wide for it.
+    Truncate -> Cuts them off at the edge.
+    Ellipsis -> Cuts them off with a '…', so it's clear there's more.
+    Wrap     -> Carries them on over the next lines, breaking between words where it can.
+    Marquee  -> Scrolls them sideways a column at a time, coming round again after the end.
+ */
+#[derive(Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    #[default]
+    Truncate,
+    Ellipsis,
+    Wrap,
+    Marquee,
+}
+
+/***
+Page: Which of a StackLayout's children it shows.
+    Next, Previous -> The one after (or before) the one showing, going round at the ends.
+    Named(s)       -> The child called s, or the s'th one, counting from 1.
+    Rotate(now)    -> The next one, if the stack rotates and it's been long enough since it last turned.
+ */
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Page {
+    Next,
+    Previous,
+    Named(String),
+    Rotate(Instant),
+}
+
 /***
 View: A trait representing a render-able text widget.
  */
@@ -63,9 +153,28 @@ pub trait View {
     fn constraints(&self) -> (DimConstraint, DimConstraint);
     fn width(&self) -> usize;
     fn height(&self) -> usize;
-    fn render(&self) -> String;
-    fn children(&mut self) -> IterMut<Box<dyn View>>;
+    fn location(&self) -> TermLocation;
+    // The most room this View could have taken up at the last inflate - its constraints, limited by its parent.
+    fn bounds(&self) -> CharDims;
+    // Where its content goes, and the most room that could have - `bounds` less any decoration.
+    fn content_area(&self) -> (TermLocation, CharDims) { (self.location(), self.bounds()) }
+    // Draw onto `screen`, which is the size of the whole terminal.
+    fn render(&self, screen: &mut Screen);
+    fn children(&mut self) -> IterMut<'_, Box<dyn View>>;
     fn update_content(&mut self, text: String);
+    // Only Views with content of their own can scroll - the rest ignore it.
+    fn scroll(&mut self, _scroll: Scroll) {}
+    // A hidden View takes up no room and draws nothing.
+    fn set_visible(&mut self, visible: bool);
+    fn visible(&self) -> bool;
+    // Only stacks have pages - the rest ignore it. True if this View now shows a different page.
+    fn turn_page(&mut self, _page: &Page) -> bool { false }
+    // When this View's next due to change by itself - a rotating stack turning, or a marquee moving.
+    fn next_turn(&self) -> Option<Instant> { None }
+    // Move a marquee along if it's due by `now` - the rest ignore it. True if anything moved.
+    fn tick(&mut self, _now: Instant) -> bool { false }
+    // A title that's the name of what the View shows changes along with it. Others are left alone.
+    fn retitle(&mut self, _from: &str, _to: &str) {}
 }
 
 /***
@@ -80,40 +189,86 @@ pub struct Widget {
     text: String,
     formatter: Box<dyn TextFormatter>,
     dirty: bool,
+    scroll: usize,  // The first line of text that's shown
+    follow: bool,   // Keep showing the end of the text as it changes, like `tail -f`
+    at_end: bool,   // Whether the last line's on screen - 'follow' widgets only follow from here
+    overflow: Overflow,
+    marquee: usize, // How many columns a marquee's lines have scrolled along
+    stepped: Instant, // When the marquee last moved
+    decoration: Decoration,
     empty_children: Vec<Box<dyn View>> //Just for an empty list we can return.
 }
 
 /***
-InteractiveWidget: An even simpler text container. The thing that prints interactive stuff.
+Orientation: For a LinearLayout. You know what this does.
+ */
+#[derive(Copy, Clone, Debug)]
+pub enum Orientation {
+    Horizontal,
+    Vertical
+}
+
+/***
+LinearLayout: Prints child View views' contents, stacked horizontally or vertically.
  */
-pub struct InteractiveWidget {
+pub struct LinearLayout {
     id: ViewId,
-    location: TermLocation,
+    orientation: Orientation,
+    children: Vec<Box<dyn View>>,
     dims: Dimensions,
+    location: TermLocation,
     visible: bool,
-    dirty: bool,
-    empty_children: Vec<Box<dyn View>> //Just for an empty list we can return.
+    dirty: bool,    // Changed itself (e.g. been hidden) since it was drawn. Its children keep their own.
+    decoration: Decoration,
 }
 
 /***
-Orientation: For a LinearLayout. You know what this does.
+GridLayout: Places child Views in a grid of rows and columns, each child covering one or more cells.
+    Rows and columns take the same constraints as a View, with WrapContent fitting the children
+    that sit in just that row (or column).
  */
-#[derive(Copy, Clone, Debug)]
-pub enum Orientation {
-    HORIZONTAL,
-    VERTICAL
+pub struct GridLayout {
+    id: ViewId,
+    rows: Vec<DimConstraint>,
+    cols: Vec<DimConstraint>,
+    children: Vec<Box<dyn View>>,
+    cells: Vec<GridCell>,
+    dims: Dimensions,
+    location: TermLocation,
+    visible: bool,
+    dirty: bool,
+    decoration: Decoration,
 }
 
 /***
-LinearLayout: Prints child View views' contents, stacked horizontally or vertically.
+StackLayout: Shows one of its child Views at a time - its pages - with the rest hidden.
+    An optional tab bar across the top names every page, and picks out the one showing.
+    It can also turn to the next page by itself, every `rotate`.
  */
-pub struct LinearLayout {
+pub struct StackLayout {
     id: ViewId,
-    orientation: Orientation,
     children: Vec<Box<dyn View>>,
+    names: Vec<String>,
+    current: usize,
+    tabs: bool,
+    rotate: Option<Duration>,
+    turned: Instant,  // When the page last changed, for rotating
     dims: Dimensions,
     location: TermLocation,
-    visible: bool
+    visible: bool,
+    dirty: bool,
+    decoration: Decoration,
+}
+
+/***
+GridCell: Where a GridLayout's child goes - the top-left row and column, and how many of each it spans.
+ */
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GridCell {
+    pub row: usize,
+    pub col: usize,
+    pub rowspan: usize,
+    pub colspan: usize,
 }
 
 /***
@@ -123,7 +278,8 @@ Dimensions: An internal struct used to track the constraints and actual size of
 pub struct Dimensions {
     width_constraint: DimConstraint,
     height_constraint: DimConstraint,
-    size: CharDims  // Actual size in character glyphs
+    size: CharDims,  // Actual size in character glyphs
+    available: CharDims  // The most size we could've had - see View::bounds
 }
 
 impl Dimensions {
@@ -132,15 +288,62 @@ impl Dimensions {
             width_constraint: width,
             height_constraint: height,
             size: CharDims::new(0,  0), // Will be updated during 'inflate' later.
+            available: CharDims::new(0, 0),
         }
     }
 }
 
+/// The space a View with these constraints gets inside `parent`, before its content has a say.
+pub fn available_size(width: DimConstraint, height: DimConstraint, parent: &CharDims) -> CharDims {
+    CharDims::new(desired_size(&min(width.resolve(parent.width), DimConstraint::Fixed(parent.width))),
+                  desired_size(&min(height.resolve(parent.height), DimConstraint::Fixed(parent.height))))
+}
+
+/***
+ * How big a container with `constraint` ends up, given `parent` room and
+ * children that need `content`. Like a Widget, it shrinks to fit what's inside.
+ ***/
+pub fn container_size(constraint: DimConstraint, parent: usize, content: usize) -> usize {
+    let most_restrictive = min(constraint.resolve(parent), DimConstraint::Fixed(parent));
+    let size = match min(DimConstraint::Fixed(content), most_restrictive) {
+        // Percent and Weight take all the room they're given, whatever the children need.
+        _ if constraint.fills() => desired_size(&most_restrictive),
+        DimConstraint::Fixed(n) | DimConstraint::UpTo(n) | DimConstraint::Between(_, n) => n,
+        _ => 0 // Only happens if we're a "WrapContent" and have 0 or empty children
+    };
+    size.max(constraint.least()).min(parent)
+}
+
+/// Split `total` in proportion to `weights`. What doesn't divide evenly goes to the first few.
+pub fn share(total: usize, weights: &[usize]) -> Vec<usize> {
+    let sum = weights.iter().sum::<usize>();
+    if sum == 0 { return vec![0; weights.len()]; }
+
+    let mut shares = weights.iter().map(|w| total * w / sum).collect::<Vec<usize>>();
+    let mut spare = total - shares.iter().sum::<usize>();
+    for (share, _) in shares.iter_mut().zip(weights).filter(|(_, w)| **w > 0) {
+        if spare == 0 { break; }
+        *share += 1;
+        spare -= 1;
+    }
+    shares
+}
+
+/// What a View draws on a Screen of `size`, one (right-trimmed) row per line. For tests.
+#[cfg(test)]
+pub fn rendered_rows(view: &dyn View, size: CharDims) -> Vec<String> {
+    let mut screen = Screen::new(size);
+    view.render(&mut screen);
+    (0..size.height).map(|y| screen.row_text(y).trim_end().to_string()).collect()
+}
+
 pub fn desired_size(constraint: &DimConstraint) -> usize {
     match constraint {
         DimConstraint::WrapContent => 0, // If the constraint at this point is wrap content, we have to inflate children to see
         DimConstraint::Fixed(x) => *x,
-        DimConstraint::UpTo(x) => *x
+        DimConstraint::UpTo(x) => *x,
+        DimConstraint::Between(_, x) => *x,
+        DimConstraint::Percent(_) | DimConstraint::Weight(_) => 0, // Not until they're resolved
     }
 }
 
@@ -153,5 +356,14 @@ mod tests {
         assert!(DimConstraint::Fixed(1).to_ord() == DimConstraint::UpTo(1).to_ord());
         assert!(DimConstraint::Fixed(1) < DimConstraint::UpTo(2));
         assert!(DimConstraint::Fixed(1000) < DimConstraint::WrapContent);
+        assert!(DimConstraint::Between(5, 10) < DimConstraint::UpTo(11));
+    }
+
+    #[test]
+    fn percent_and_weight_fill_the_room_theyre_given() {
+        assert_eq!(DimConstraint::Percent(30).resolve(12), DimConstraint::Fixed(12));
+        assert_eq!(DimConstraint::Weight(2).resolve(7), DimConstraint::Fixed(7));
+        assert_eq!(DimConstraint::UpTo(3).resolve(7), DimConstraint::UpTo(3));
+        assert_eq!(available_size(DimConstraint::Percent(50), DimConstraint::Between(2, 4), &CharDims::new(20, 10)), CharDims::new(20, 4));
     }
 }
\ No newline at end of file
@@ -0,0 +1,528 @@
+use termion::cursor::Goto;
+use unicode_width::UnicodeWidthChar;
+
+use crate::views::{CharDims, TermLocation};
+
+/***
+Color: A foreground or background colour, as set by SGR.
+    Default    -> Whatever the real terminal uses.
+    Indexed(n) -> One of the 256 palette colours (0-15 are the classic and bright ones).
+    Rgb(r,g,b) -> 24-bit colour.
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Color {
+    #[default]
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/***
+Style: The text attributes a Cell is drawn with.
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub hidden: bool,
+    pub strike: bool,
+}
+
+impl Style {
+    /// Update the attributes from the parameters of an SGR (`CSI ... m`) sequence.
+    pub fn apply_sgr(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = Style::default();
+            return;
+        }
+
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                5 | 6 => self.blink = true,
+                7 => self.inverse = true,
+                8 => self.hidden = true,
+                9 => self.strike = true,
+                21 => self.underline = true,
+                22 => { self.bold = false; self.dim = false; },
+                23 => self.italic = false,
+                24 => self.underline = false,
+                25 => self.blink = false,
+                27 => self.inverse = false,
+                28 => self.hidden = false,
+                29 => self.strike = false,
+                n @ 30..=37 => self.fg = Color::Indexed((n - 30) as u8),
+                38 => self.fg = extended_color(params, &mut i),
+                39 => self.fg = Color::Default,
+                n @ 40..=47 => self.bg = Color::Indexed((n - 40) as u8),
+                48 => self.bg = extended_color(params, &mut i),
+                49 => self.bg = Color::Default,
+                n @ 90..=97 => self.fg = Color::Indexed((n - 90 + 8) as u8),
+                n @ 100..=107 => self.bg = Color::Indexed((n - 100 + 8) as u8),
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    /// The SGR sequence that switches a terminal from any state to this Style.
+    pub fn sgr(&self) -> String {
+        let mut codes = vec!["0".to_string()];
+        let flags = [(self.bold, "1"), (self.dim, "2"), (self.italic, "3"), (self.underline, "4"),
+                     (self.blink, "5"), (self.inverse, "7"), (self.hidden, "8"), (self.strike, "9")];
+        codes.extend(flags.iter().filter(|(on, _)| *on).map(|(_, code)| code.to_string()));
+
+        match self.fg {
+            Color::Default => {},
+            Color::Indexed(n) if n < 8 => codes.push((30 + n).to_string()),
+            Color::Indexed(n) if n < 16 => codes.push((90 + n - 8).to_string()),
+            Color::Indexed(n) => codes.push(format!("38;5;{}", n)),
+            Color::Rgb(r, g, b) => codes.push(format!("38;2;{};{};{}", r, g, b)),
+        }
+        match self.bg {
+            Color::Default => {},
+            Color::Indexed(n) if n < 8 => codes.push((40 + n).to_string()),
+            Color::Indexed(n) if n < 16 => codes.push((100 + n - 8).to_string()),
+            Color::Indexed(n) => codes.push(format!("48;5;{}", n)),
+            Color::Rgb(r, g, b) => codes.push(format!("48;2;{};{};{}", r, g, b)),
+        }
+
+        format!("\u{1b}[{}m", codes.join(";"))
+    }
+}
+
+// `38;5;n` or `38;2;r;g;b` (and the same for 48), leaving `i` on the last parameter used.
+fn extended_color(params: &[u16], i: &mut usize) -> Color {
+    let param = |n: usize| params.get(n).copied().unwrap_or(0).min(255) as u8;
+    match params.get(*i + 1) {
+        Some(5) => {
+            *i += 2;
+            Color::Indexed(param(*i))
+        },
+        Some(2) => {
+            *i += 4;
+            Color::Rgb(param(*i - 2), param(*i - 1), param(*i))
+        },
+        _ => Color::Default,
+    }
+}
+
+// How many combining characters a Cell keeps. Any more than that are dropped.
+const MAX_MARKS: usize = 2;
+
+/***
+Cell: One character on a Screen, and how to draw it.
+    `marks` are the combining characters (accents and the like) drawn over it. Unused ones are '\0'.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub marks: [char; MAX_MARKS],
+    pub style: Style,
+}
+
+impl Cell {
+    pub fn new(ch: char, style: Style) -> Cell { Cell { ch, marks: ['\0'; MAX_MARKS], style } }
+
+    /// What erasing leaves behind: a space, keeping only the background colour.
+    pub fn blank(style: Style) -> Cell {
+        Cell::new(' ', Style { bg: style.bg, ..Style::default() })
+    }
+
+    /// The right half of a wide character. The terminal draws it along with the left half.
+    pub fn wide_tail(style: Style) -> Cell { Cell::new('\0', style) }
+
+    pub fn is_wide_tail(&self) -> bool { self.ch == '\0' }
+
+    fn add_mark(&mut self, mark: char) {
+        if let Some(unused) = self.marks.iter_mut().find(|m| **m == '\0') {
+            *unused = mark;
+        }
+    }
+
+    // The character, with any combining characters after it.
+    fn text(&self) -> impl Iterator<Item = char> + '_ {
+        std::iter::once(self.ch).chain(self.marks.iter().copied().filter(|m| *m != '\0'))
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Cell { Cell::blank(Style::default()) }
+}
+
+/***
+Screen: A grid of Cells. The contents of an emulated terminal, or of the real one - Views draw
+    into a Screen, and `diff` against what's already showing works out the least to send.
+ */
+#[derive(Clone)]
+pub struct Screen {
+    size: CharDims,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Screen {
+    pub fn new(size: CharDims) -> Screen {
+        Screen {
+            size,
+            rows: vec![vec![Cell::default(); size.width]; size.height],
+        }
+    }
+
+    pub fn size(&self) -> CharDims { self.size }
+
+    /// Change size, keeping whatever's in the top-left corner that fits. New cells are blank.
+    pub fn resize(&mut self, size: CharDims) {
+        self.rows.resize(size.height, Vec::new());
+        self.rows.iter_mut().for_each(|row| row.resize(size.width, Cell::default()));
+        self.size = size;
+    }
+
+    #[cfg(test)]
+    pub fn cell(&self, x: usize, y: usize) -> &Cell { &self.rows[y][x] }
+
+    /// A row's characters, without any styling.
+    #[cfg(test)]
+    pub fn row_text(&self, y: usize) -> String {
+        self.rows[y].iter().filter(|cell| !cell.is_wide_tail()).flat_map(Cell::text).collect()
+    }
+
+    /// Set one cell. Overwriting either half of a wide character blanks the other half.
+    pub fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        if y >= self.size.height || x >= self.size.width { return; }
+
+        let row = &mut self.rows[y];
+        if x > 0 && row[x].is_wide_tail() && !cell.is_wide_tail() {
+            row[x - 1] = Cell::new(' ', row[x - 1].style);
+        }
+        if x + 1 < row.len() && row[x + 1].is_wide_tail() {
+            row[x + 1] = Cell::new(' ', row[x + 1].style);
+        }
+        row[x] = cell;
+    }
+
+    /***
+     * Put `c` at `x`, `y`, taking two cells if it's a wide character, and return how many columns
+     * it took. Zero-width characters (combining accents and the like) take none - they're added
+     * to the character just before `x`, whichever cell that starts in.
+     ***/
+    pub fn put_char(&mut self, x: usize, y: usize, c: char, style: Style) -> usize {
+        let width = c.width().unwrap_or(0).min(2);
+        match width {
+            0 => if y < self.size.height && (1..=self.size.width).contains(&x) {
+                let row = &mut self.rows[y];
+                let before = if row[x - 1].is_wide_tail() { x.saturating_sub(2) } else { x - 1 };
+                row[before].add_mark(c);
+            },
+            1 => self.put(x, y, Cell::new(c, style)),
+            _ => {
+                self.put(x, y, Cell::new(c, style));
+                self.put(x + 1, y, Cell::wide_tail(style));
+            }
+        }
+        width
+    }
+
+    /// Blank the columns `from..to` of row `y`. Wide characters only partly in them are blanked too.
+    pub fn erase(&mut self, y: usize, from: usize, to: usize, blank: Cell) {
+        let to = to.min(self.size.width);
+        if y < self.size.height && from < to {
+            self.unsplit(from, y, blank);
+            self.unsplit(to, y, blank);
+            self.rows[y][from..to].iter_mut().for_each(|cell| *cell = blank);
+        }
+    }
+
+    // Blank both halves of a wide character that straddles the left edge of column `x`,
+    // before something splits it in two.
+    fn unsplit(&mut self, x: usize, y: usize, blank: Cell) {
+        if x > 0 && x < self.size.width && self.rows[y][x].is_wide_tail() {
+            self.rows[y][x - 1] = blank;
+            self.rows[y][x] = blank;
+        }
+    }
+
+    /// Move rows `top..=bottom` up by `n`, blanking the rows that open up at the bottom.
+    pub fn scroll_up(&mut self, top: usize, bottom: usize, n: usize, blank: Cell) {
+        let bottom = bottom.min(self.size.height - 1);
+        if top > bottom { return; }
+
+        let n = n.min(bottom - top + 1);
+        self.rows[top..=bottom].rotate_left(n);
+        for y in bottom + 1 - n..=bottom {
+            self.erase(y, 0, self.size.width, blank);
+        }
+    }
+
+    /// Move rows `top..=bottom` down by `n`, blanking the rows that open up at the top.
+    pub fn scroll_down(&mut self, top: usize, bottom: usize, n: usize, blank: Cell) {
+        let bottom = bottom.min(self.size.height - 1);
+        if top > bottom { return; }
+
+        let n = n.min(bottom - top + 1);
+        self.rows[top..=bottom].rotate_right(n);
+        for y in top..top + n {
+            self.erase(y, 0, self.size.width, blank);
+        }
+    }
+
+    /***
+     * Shift the rest of row `y` right by `n` from column `x`. Cells pushed past the edge are lost,
+     * as are wide characters split at `x` or by the edge.
+     ***/
+    pub fn insert_blanks(&mut self, x: usize, y: usize, n: usize, blank: Cell) {
+        if y >= self.size.height || x >= self.size.width { return; }
+        let n = n.min(self.size.width - x);
+        self.unsplit(x, y, blank);
+        self.unsplit(self.size.width - n, y, blank);
+        let row = &mut self.rows[y][x..];
+        row.rotate_right(n);
+        row[..n].iter_mut().for_each(|cell| *cell = blank);
+    }
+
+    /***
+     * Remove `n` cells from row `y` at column `x`, pulling the rest of the row left.
+     * Wide characters only partly removed are blanked.
+     ***/
+    pub fn delete_chars(&mut self, x: usize, y: usize, n: usize, blank: Cell) {
+        if y >= self.size.height || x >= self.size.width { return; }
+        let n = n.min(self.size.width - x);
+        self.unsplit(x, y, blank);
+        self.unsplit(x + n, y, blank);
+        let row = &mut self.rows[y][x..];
+        row.rotate_left(n);
+        let len = row.len();
+        row[len - n..].iter_mut().for_each(|cell| *cell = blank);
+    }
+
+    /***
+     * Draw text, as produced by a TextFormatter, with its top-left corner at `location`.
+     * Goto sequences are in terminal coordinates and SGR sequences style what follows;
+     * other escape sequences are dropped. Nothing lands outside `location`/`size`,
+     * so whatever's drawing can't spill onto its neighbours - a wide character with only
+     * one column left for it is drawn as a space.
+     ***/
+    pub fn draw(&mut self, text: &str, location: &TermLocation, size: &CharDims) {
+        let (left, top) = (location.x as usize - 1, location.y as usize - 1);
+        let (right, bottom) = (left + size.width, top + size.height);
+        let (mut x, mut y) = (left, top);
+        let mut style = Style::default();
+
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                // Two-character sequences, e.g. ESC 7, have nothing to draw.
+                '\u{1b}' => if chars.next() == Some('[') {
+                    let sequence = chars.by_ref().take_while(|c| !('@'..='~').contains(c)).collect::<String>();
+                    let final_char = text[..text.len() - chars.as_str().len()].chars().last();
+                    let params = parse_params(&sequence);
+                    match final_char {
+                        Some('H') | Some('f') => {
+                            let position = |i: usize| params.get(i).copied().filter(|p| *p > 0).unwrap_or(1) as usize;
+                            y = position(0) - 1;
+                            x = position(1) - 1;
+                        },
+                        Some('m') => style.apply_sgr(&params),
+                        _ => {},
+                    }
+                },
+                '\n' => {
+                    x = left;
+                    y += 1;
+                },
+                '\r' => x = left,
+                c if c.is_control() => {},
+                c => {
+                    let width = c.width().unwrap_or(0).min(2);
+                    // A combining character goes with the one before it, so needs that to be inside.
+                    let inside = if width == 0 { (left + 1..=right).contains(&x) } else { (left..right).contains(&x) };
+                    if inside && (top..bottom).contains(&y) {
+                        if x + width <= right {
+                            self.put_char(x, y, c, style);
+                        } else {
+                            self.put(x, y, Cell::new(' ', style));
+                        }
+                    }
+                    x += width;
+                }
+            }
+        }
+    }
+
+    /// Copy all of `other` onto this Screen, with its top-left corner at `location`.
+    pub fn blit(&mut self, other: &Screen, location: &TermLocation) {
+        let (left, top) = (location.x as usize - 1, location.y as usize - 1);
+        for (y, row) in other.rows.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                self.put(left + x, top + y, *cell);
+            }
+        }
+    }
+
+    /***
+     * What to send a terminal that's showing `front` to make it show this Screen instead.
+     * Only the cells that differ are drawn. If the sizes don't match, everything is.
+     ***/
+    pub fn diff(&self, front: &Screen) -> String {
+        let redraw_all = front.size != self.size;
+        let mut out = String::new();
+        let mut style = None;
+        let mut cursor = None;
+
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if cell.is_wide_tail() || (!redraw_all && front.rows[y][x] == *cell) { continue; }
+
+                if cursor != Some((x, y)) {
+                    out.push_str(&Goto(x as u16 + 1, y as u16 + 1).to_string());
+                }
+                if style != Some(cell.style) {
+                    out.push_str(&cell.style.sgr());
+                    style = Some(cell.style);
+                }
+                out.extend(cell.text());
+                let width = if row.get(x + 1).is_some_and(|next| next.is_wide_tail()) { 2 } else { 1 };
+                cursor = Some((x + width, y));
+            }
+        }
+
+        if style.is_some() {
+            out.push_str("\u{1b}[0m");
+        }
+        out
+    }
+}
+
+// `1;;3` -> [1, 0, 3]. xterm's colon-separated sub-parameters are read the same way.
+pub fn parse_params(body: &str) -> Vec<u16> {
+    if body.is_empty() { return Vec::new(); }
+    body.split([';', ':']).map(|p| p.parse::<u16>().unwrap_or(0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resizing_keeps_what_still_fits() {
+        let mut screen = Screen::new(CharDims::new(3, 2));
+        screen.draw("abc\ndef", &TermLocation::new(1, 1), &CharDims::new(3, 2));
+
+        screen.resize(CharDims::new(2, 3));
+        assert_eq!((0..3).map(|y| screen.row_text(y)).collect::<Vec<String>>(), vec!["ab", "de", "  "]);
+
+        screen.resize(CharDims::new(4, 1));
+        assert_eq!(screen.row_text(0), "ab  ");
+        assert_eq!(screen.size(), CharDims::new(4, 1));
+    }
+
+    #[test]
+    fn sgr_parameters_set_and_clear_attributes() {
+        let mut style = Style::default();
+        style.apply_sgr(&[1, 31, 48, 5, 200]);
+        assert_eq!(style, Style { bold: true, fg: Color::Indexed(1), bg: Color::Indexed(200), ..Style::default() });
+        assert_eq!(style.sgr(), "\u{1b}[0;1;31;48;5;200m");
+
+        style.apply_sgr(&[22, 38, 2, 1, 2, 3, 49]);
+        assert_eq!(style, Style { fg: Color::Rgb(1, 2, 3), ..Style::default() });
+
+        style.apply_sgr(&[]);
+        assert_eq!(style, Style::default());
+    }
+
+    #[test]
+    fn scrolling_only_moves_rows_in_the_region() {
+        let mut screen = Screen::new(CharDims::new(1, 4));
+        "abcd".chars().enumerate().for_each(|(y, c)| screen.put(0, y, Cell::new(c, Style::default())));
+
+        screen.scroll_up(1, 2, 1, Cell::default());
+        assert_eq!((0..4).map(|y| screen.row_text(y)).collect::<String>(), "ac d");
+
+        screen.scroll_down(0, 3, 2, Cell::default());
+        assert_eq!((0..4).map(|y| screen.row_text(y)).collect::<String>(), "  ac");
+    }
+
+    #[test]
+    fn drawing_stays_inside_its_area() {
+        let mut screen = Screen::new(CharDims::new(6, 3));
+        screen.draw("\u{1b}[2;2Hhello\u{1b}[3;2H\u{1b}[31mx\u{1b}[4;2Hgone", &TermLocation::new(2, 2), &CharDims::new(3, 2));
+
+        assert_eq!((0..3).map(|y| screen.row_text(y)).collect::<Vec<String>>(), vec!["      ", " hel  ", " x    "]);
+        assert_eq!(screen.cell(1, 2).style.fg, Color::Indexed(1));
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        let mut screen = Screen::new(CharDims::new(5, 2));
+        screen.draw("日本語\nx°y", &TermLocation::new(1, 1), &CharDims::new(5, 2));
+        assert_eq!((0..2).map(|y| screen.row_text(y)).collect::<Vec<String>>(), vec!["日本 ", "x°y  "]);
+        assert!(screen.cell(1, 0).is_wide_tail());
+
+        // Writing over half of one blanks the other half.
+        screen.put(1, 0, Cell::new('a', Style::default()));
+        assert_eq!(screen.row_text(0), " a本 ");
+    }
+
+    #[test]
+    fn combining_characters_join_the_one_before() {
+        let mut screen = Screen::new(CharDims::new(6, 1));
+        // The first accent has nothing before it in the area, so it's dropped.
+        screen.draw("\u{301}e\u{301}\u{302}\u{303}本\u{308}x", &TermLocation::new(2, 1), &CharDims::new(4, 1));
+
+        assert_eq!(screen.row_text(0), " e\u{301}\u{302}本\u{308}x ");
+        assert_eq!(screen.diff(&Screen::new(CharDims::new(6, 1))), "\u{1b}[1;2H\u{1b}[0me\u{301}\u{302}本\u{308}x\u{1b}[0m");
+    }
+
+    #[test]
+    fn wide_characters_split_in_two_are_blanked() {
+        let wide = || {
+            let mut screen = Screen::new(CharDims::new(6, 1));
+            screen.draw("a本日b", &TermLocation::new(1, 1), &CharDims::new(6, 1));
+            screen
+        };
+        let blank = Cell::new('.', Style::default());
+
+        let mut screen = wide();
+        screen.erase(0, 2, 4, blank);
+        assert_eq!(screen.row_text(0), "a....b");
+
+        let mut screen = wide();
+        screen.insert_blanks(2, 0, 1, blank);
+        assert_eq!(screen.row_text(0), "a...日");
+
+        let mut screen = wide();
+        screen.delete_chars(2, 0, 2, blank);
+        assert_eq!(screen.row_text(0), "a..b..");
+        assert!((0..6).all(|x| !screen.cell(x, 0).is_wide_tail()));
+    }
+
+    #[test]
+    fn diff_skips_over_the_right_half_of_wide_characters() {
+        let front = Screen::new(CharDims::new(4, 1));
+        let mut back = front.clone();
+        back.draw("本ab", &TermLocation::new(1, 1), &CharDims::new(4, 1));
+        assert_eq!(back.diff(&front), "\u{1b}[1;1H\u{1b}[0m本ab\u{1b}[0m");
+    }
+
+    #[test]
+    fn diff_only_draws_what_changed() {
+        let front = Screen::new(CharDims::new(4, 2));
+        let mut back = front.clone();
+        assert_eq!(back.diff(&front), "");
+
+        back.put(1, 0, Cell::new('a', Style::default()));
+        back.put(2, 0, Cell::new('b', Style { bold: true, ..Style::default() }));
+        back.put(3, 1, Cell::new('c', Style::default()));
+        assert_eq!(back.diff(&front), "\u{1b}[1;2H\u{1b}[0ma\u{1b}[0;1mb\u{1b}[2;4H\u{1b}[0mc\u{1b}[0m");
+    }
+}
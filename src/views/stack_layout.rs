@@ -0,0 +1,330 @@
+use crate::views::{StackLayout, Page, View, DimConstraint, Dimensions, desired_size, container_size, CharDims, ViewId, TermLocation};
+use std::cmp::{min, max};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use std::slice::IterMut;
+use crate::views::screen::Screen;
+use crate::views::decoration::Decoration;
+use crate::hexterm::formatting::display_width;
+
+impl StackLayout {
+    pub fn new(width: DimConstraint, height: DimConstraint, location: TermLocation) -> StackLayout {
+        StackLayout {
+            id: Uuid::new_v4().to_string(),
+            children: vec![],
+            names: vec![],
+            current: 0,
+            tabs: false,
+            rotate: None,
+            turned: Instant::now(),
+            dims: Dimensions::new(width, height),
+            location,
+            visible: true,
+            dirty: true,
+            decoration: Decoration::default(),
+        }
+    }
+
+    pub fn set_decoration(&mut self, decoration: Decoration) {
+        self.decoration = decoration;
+    }
+
+    pub fn set_tabs(&mut self, tabs: bool) {
+        self.tabs = tabs;
+    }
+
+    pub fn set_rotation(&mut self, rotate: Option<Duration>) {
+        self.rotate = rotate;
+    }
+
+    /// Add a page called `name`. The first page added is the one showing.
+    pub fn add_child(&mut self, mut child: Box<dyn View>, name: &str) {
+        child.set_visible(self.children.is_empty());
+        self.children.push(child);
+        self.names.push(name.to_string());
+    }
+
+    // Which page `page` means, if any - None if it's not one of ours, or it's not time to rotate yet.
+    fn find(&self, page: &Page) -> Option<usize> {
+        let count = self.children.len();
+        if count == 0 { return None; }
+
+        match page {
+            Page::Next => Some((self.current + 1) % count),
+            Page::Previous => Some((self.current + count - 1) % count),
+            Page::Named(name) => self.names.iter().position(|n| n.eq_ignore_ascii_case(name))
+                .or_else(|| name.parse::<usize>().ok().filter(|n| (1..=count).contains(n)).map(|n| n - 1)),
+            Page::Rotate(now) => self.rotate
+                .filter(|every| now.saturating_duration_since(self.turned) >= *every)
+                .map(|_| (self.current + 1) % count),
+        }
+    }
+
+    fn tab_rows(&self) -> usize {
+        if self.tabs && !self.children.is_empty() { 1 } else { 0 }
+    }
+
+    // Every page's name, with the one showing picked out.
+    fn tab_bar(&self) -> String {
+        self.names.iter().enumerate().map(|(i, name)| {
+            if i == self.current { format!("\u{1b}[7m {} \u{1b}[27m", name) } else { format!(" {} ", name) }
+        }).collect::<Vec<String>>().join("|")
+    }
+
+    fn tab_bar_width(&self) -> usize {
+        self.names.iter().map(|name| display_width(name) + 2).sum::<usize>() + self.names.len().saturating_sub(1)
+    }
+}
+
+impl View for StackLayout {
+    fn id(&self) -> ViewId {
+        self.id.clone()
+    }
+
+    fn dirty(&self) -> bool {
+        self.dirty || self.children.iter().any(|c| c.dirty())
+    }
+
+    fn wash(&mut self) {
+        self.dirty = false;
+        self.children.iter_mut().for_each(|c| c.wash());
+    }
+
+    fn inflate(&mut self, parent_dimensions: &CharDims, location: &TermLocation) -> CharDims {
+        self.location = *location;
+        if !self.visible {
+            self.dims.size = CharDims::new(0, 0);
+            return self.dims.size;
+        }
+
+        let most_restrictive_width = min(self.dims.width_constraint.resolve(parent_dimensions.width), DimConstraint::Fixed(parent_dimensions.width));
+        let most_restrictive_height = min(self.dims.height_constraint.resolve(parent_dimensions.height), DimConstraint::Fixed(parent_dimensions.height));
+        self.dims.size = CharDims::new(desired_size(&most_restrictive_width), desired_size(&most_restrictive_height));
+        self.dims.available = self.dims.size;
+
+        // Every page gets the same room, under the tab bar. Hidden ones come back empty.
+        let full = self.decoration.shrink(&self.dims.size);
+        let origin = self.decoration.content_location(location);
+        let tab_rows = self.tab_rows();
+        let page_room = CharDims::new(full.width, full.height.saturating_sub(tab_rows));
+        let page_location = TermLocation::new(origin.x, origin.y + tab_rows as u16);
+
+        let mut content = CharDims::new(0, 0);
+        for child in self.children.iter_mut() {
+            let size = child.inflate(&page_room, &page_location);
+            content = CharDims::new(max(content.width, size.width), max(content.height, size.height));
+        }
+        if tab_rows > 0 {
+            content = CharDims::new(max(content.width, self.tab_bar_width()), content.height + tab_rows);
+        }
+
+        let content_size = self.decoration.grow(&content);
+        self.dims.size = CharDims::new(container_size(self.dims.width_constraint, parent_dimensions.width, content_size.width),
+                                       container_size(self.dims.height_constraint, parent_dimensions.height, content_size.height));
+        self.dims.size
+    }
+
+    fn constraints(&self) -> (DimConstraint, DimConstraint) { (self.dims.width_constraint, self.dims.height_constraint) }
+
+    fn width(&self) -> usize { self.dims.size.width }
+
+    fn height(&self) -> usize { self.dims.size.height }
+
+    fn location(&self) -> TermLocation { self.location }
+
+    fn bounds(&self) -> CharDims { self.dims.available }
+
+    fn content_area(&self) -> (TermLocation, CharDims) {
+        (self.decoration.content_location(&self.location), self.decoration.shrink(&self.dims.available))
+    }
+
+    fn render(&self, screen: &mut Screen) {
+        if !self.visible || self.width() == 0 || self.height() == 0 { return }
+        self.decoration.draw(screen, &self.location, &self.dims.size);
+        if self.tab_rows() > 0 {
+            let width = self.decoration.shrink(&self.dims.size).width;
+            screen.draw(&self.tab_bar(), &self.decoration.content_location(&self.location), &CharDims::new(width, 1));
+        }
+        self.children.iter().for_each(|c| c.render(screen));
+    }
+
+    fn children(&mut self) -> IterMut<'_, Box<dyn View>> {
+        self.children.iter_mut()
+    }
+
+    fn update_content(&mut self, _: String) {
+        // Like the other layouts, a stack has no text of its own.
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if visible != self.visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn next_turn(&self) -> Option<Instant> {
+        self.rotate.filter(|_| self.children.len() > 1).map(|every| self.turned + every)
+    }
+
+    fn turn_page(&mut self, page: &Page) -> bool {
+        let next = match self.find(page) {
+            Some(next) => next,
+            None => return false,
+        };
+
+        self.turned = match page {
+            Page::Rotate(now) => *now,
+            _ => Instant::now(),
+        };
+        if next == self.current { return false; }
+
+        self.children[self.current].set_visible(false);
+        self.children[next].set_visible(true);
+        self.current = next;
+        self.dirty = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::views::Widget;
+    use crate::hexterm::formatting::Vt100Formatter;
+    use crate::views::rendered_rows;
+
+    fn wrap_content_text_widget(text: &str) -> Box<dyn View> {
+        let mut tw = Widget::new(DimConstraint::WrapContent, DimConstraint::WrapContent, Box::new(Vt100Formatter{}), TermLocation::new(1, 1));
+        tw.update_content(text.to_owned());
+        Box::new(tw)
+    }
+
+    fn three_pages() -> StackLayout {
+        let mut stack = StackLayout::new(DimConstraint::WrapContent, DimConstraint::WrapContent, TermLocation::new(1, 1));
+        stack.add_child(wrap_content_text_widget("one"), "Dashboard");
+        stack.add_child(wrap_content_text_widget("two\nlines"), "Calendar");
+        stack.add_child(wrap_content_text_widget("three"), "System");
+        stack
+    }
+
+    fn showing(stack: &StackLayout) -> &str {
+        &stack.names[stack.current]
+    }
+
+    fn sizes(stack: &mut StackLayout) -> Vec<(usize, usize)> {
+        stack.children().map(|c| (c.width(), c.height())).collect()
+    }
+
+    #[test]
+    fn retrieves_constraints() {
+        let stack = StackLayout::new(DimConstraint::Fixed(5), DimConstraint::UpTo(2), TermLocation::new(1, 1));
+        assert_eq!(stack.constraints(), (DimConstraint::Fixed(5), DimConstraint::UpTo(2)));
+    }
+
+    #[test]
+    fn shows_only_the_first_page_to_begin_with() {
+        let mut stack = three_pages();
+        stack.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        assert_eq!(showing(&stack), "Dashboard");
+        assert_eq!(sizes(&mut stack), vec![(3, 1), (0, 0), (0, 0)]);
+        assert_eq!((stack.width(), stack.height()), (3, 1));
+        assert_eq!(rendered_rows(&stack, CharDims::new(6, 2)), vec!["one", ""]);
+    }
+
+    #[test]
+    fn turns_to_pages_by_name_number_or_direction() {
+        let mut stack = three_pages();
+        assert!(stack.turn_page(&Page::Named("calendar".to_string())));
+        stack.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(sizes(&mut stack), vec![(0, 0), (5, 2), (0, 0)]);
+        assert_eq!((stack.width(), stack.height()), (5, 2));
+
+        assert!(stack.turn_page(&Page::Next));
+        assert_eq!(showing(&stack), "System");
+        assert!(stack.turn_page(&Page::Next));
+        assert_eq!(showing(&stack), "Dashboard");
+        assert!(stack.turn_page(&Page::Previous));
+        assert_eq!(showing(&stack), "System");
+        assert!(stack.turn_page(&Page::Named("2".to_string())));
+        assert_eq!(showing(&stack), "Calendar");
+
+        assert!(!stack.turn_page(&Page::Named("Calendar".to_string())));
+        assert!(!stack.turn_page(&Page::Named("Weather".to_string())));
+        assert!(!stack.turn_page(&Page::Named("4".to_string())));
+    }
+
+    #[test]
+    fn turning_the_page_needs_a_redraw() {
+        let mut stack = three_pages();
+        stack.wash();
+        assert!(!stack.dirty());
+
+        assert!(!stack.turn_page(&Page::Named("Weather".to_string())));
+        assert!(!stack.dirty());
+        assert!(stack.turn_page(&Page::Next));
+        assert!(stack.dirty());
+    }
+
+    #[test]
+    fn rotates_only_once_its_interval_has_passed() {
+        let mut stack = three_pages();
+        let start = Instant::now();
+        assert!(!stack.turn_page(&Page::Rotate(start + Duration::from_secs(60))));
+
+        stack.set_rotation(Some(Duration::from_secs(10)));
+        stack.turned = start;
+        assert!(!stack.turn_page(&Page::Rotate(start + Duration::from_secs(9))));
+        assert!(stack.turn_page(&Page::Rotate(start + Duration::from_secs(10))));
+        assert_eq!(showing(&stack), "Calendar");
+        assert!(!stack.turn_page(&Page::Rotate(start + Duration::from_secs(19))));
+        assert!(stack.turn_page(&Page::Rotate(start + Duration::from_secs(20))));
+        assert_eq!(showing(&stack), "System");
+    }
+
+    #[test]
+    fn the_tab_bar_names_every_page_above_the_one_showing() {
+        let mut stack = three_pages();
+        stack.set_tabs(true);
+        stack.turn_page(&Page::Next);
+        stack.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        assert_eq!((stack.width(), stack.height()), (31, 3));
+        assert_eq!(sizes(&mut stack)[1], (5, 2));
+        assert_eq!(rendered_rows(&stack, CharDims::new(31, 4)), vec![" Dashboard | Calendar | System", "two", "lines", ""]);
+    }
+
+    #[test]
+    fn fills_its_room_whichever_page_is_showing() {
+        let mut stack = StackLayout::new(DimConstraint::Weight(1), DimConstraint::Weight(1), TermLocation::new(1, 1));
+        stack.add_child(wrap_content_text_widget("one"), "1");
+        stack.add_child(wrap_content_text_widget(""), "2");
+        stack.inflate(&CharDims::new(10, 4), &TermLocation::new(1, 1));
+        assert_eq!((stack.width(), stack.height()), (10, 4));
+
+        stack.turn_page(&Page::Next);
+        stack.inflate(&CharDims::new(10, 4), &TermLocation::new(1, 1));
+        assert_eq!((stack.width(), stack.height()), (10, 4));
+    }
+
+    #[test]
+    fn when_invisible_renders_nothing() {
+        let mut stack = three_pages();
+        stack.visible = false;
+        stack.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(rendered_rows(&stack, CharDims::new(3, 1)), vec![""]);
+    }
+
+    #[test]
+    fn when_invisible_dims_are_0() {
+        let mut stack = three_pages();
+        stack.visible = false;
+        stack.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(stack.dims.size, CharDims::new(0, 0));
+    }
+}
@@ -1,29 +1,52 @@
-use crate::views::{View, Widget, DimConstraint, Dimensions, desired_size, CharDims, ViewId, TermLocation};
+use crate::views::{View, Widget, DimConstraint, Dimensions, desired_size, available_size, CharDims, ViewId, TermLocation, Scroll, Overflow};
 use std::cmp::min;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 use std::slice::IterMut;
-use log::info;
-use crate::hexterm::formatting::TextFormatter;
+use crate::hexterm::formatting::{TextFormatter, display_width, ellipsize, marquee, sgr_prefix, sgr_state, wrap};
 use termion::cursor::Goto;
-use std::io::{stdout, Write};
+use termion::style;
+use crate::views::screen::{Screen, Style};
+use crate::views::decoration::Decoration;
+
+// How often a marquee moves along a column.
+const MARQUEE_STEP: Duration = Duration::from_millis(300);
 
 impl Widget {
     pub fn new(width: DimConstraint, height: DimConstraint, formatter: Box<dyn TextFormatter>, location: TermLocation) -> Widget {
         Widget {
             id: Uuid::new_v4().to_string(),
-            dims: Dimensions {
-                width_constraint: width,
-                height_constraint: height,
-                size: CharDims::new(0, 0)
-            },
-            location: location,
+            dims: Dimensions::new(width, height),
+            location,
             visible: true,
             text: "".to_string(),
-            formatter: formatter,
+            formatter,
             empty_children: Vec::new(),
-            dirty: true
+            dirty: true,
+            scroll: 0,
+            follow: false,
+            at_end: true,
+            overflow: Overflow::Truncate,
+            marquee: 0,
+            stepped: Instant::now(),
+            decoration: Decoration::default(),
         }
     }
+
+    pub fn set_decoration(&mut self, decoration: Decoration) {
+        self.decoration = decoration;
+        self.dirty = true;
+    }
+
+    /// Keep the end of the text in view as it's updated, unless it's been scrolled back from there.
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow;
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+        self.dirty = true;
+    }
 }
 
 impl View for Widget {
@@ -32,7 +55,7 @@ impl View for Widget {
     }
 
     fn dirty(&self) -> bool {
-        return self.dirty
+        self.dirty
     }
 
     fn wash(&mut self) {
@@ -41,79 +64,212 @@ impl View for Widget {
 
     fn inflate(&mut self, parent_dimensions: &CharDims, location: &TermLocation) -> CharDims {
         if self.location != *location {
-            self.location = location.clone();
+            self.location = *location;
             self.dirty = true;
         }
 
+        self.dims.available = available_size(self.dims.width_constraint, self.dims.height_constraint, parent_dimensions);
+
         if !self.visible || self.text.is_empty() {
             self.dims.size = CharDims::new(0, 0);
             return self.dims.size;
         }
 
-        let new_size = self.update_dims(parent_dimensions);
+        let content_size = self.update_dims(&self.decoration.shrink(parent_dimensions));
+        let grown = self.decoration.grow(&content_size);
+        let new_size = CharDims::new(min(grown.width, parent_dimensions.width), min(grown.height, parent_dimensions.height));
         if new_size != self.dims.size {
-            clear_area(&self.location, &self.dims.size);
             self.dims.size = new_size;
+            self.dirty = true;
         }
 
-        self.dims.size.clone()
+        // The text or our size may have changed - either way, stay within the text (or at its end).
+        let scroll = if self.follow && self.at_end { self.max_scroll() } else { min(self.scroll, self.max_scroll()) };
+        self.set_scroll(scroll);
+
+        self.dims.size
     }
 
     fn constraints(&self) -> (DimConstraint, DimConstraint) {
-        (self.dims.width_constraint.clone(), self.dims.height_constraint.clone())
+        (self.dims.width_constraint, self.dims.height_constraint)
     }
 
     fn width(&self) -> usize { self.dims.size.width }
 
     fn height(&self) -> usize { self.dims.size.height }
 
-    fn render(&self) -> String {
-        if !self.dirty { return String::new() }
-        self.formatter.format(self.text.as_str(), (self.width(), self.height()), (self.location.x, self.location.y))
+    fn location(&self) -> TermLocation { self.location }
+
+    fn bounds(&self) -> CharDims { self.dims.available }
+
+    fn content_area(&self) -> (TermLocation, CharDims) {
+        (self.decoration.content_location(&self.location), self.decoration.shrink(&self.dims.available))
     }
 
-    fn children(&mut self) -> IterMut<Box<dyn View>> {
+    fn render(&self, screen: &mut Screen) {
+        if self.width() == 0 || self.height() == 0 { return }
+        self.decoration.draw(screen, &self.location, &self.dims.size);
+
+        let (location, size) = (self.content_location(), self.content_size());
+        let lines = self.lines(size.width);
+        // Lines scrolled off the top may still have set the colour the first visible one starts in.
+        let scrolled_past = lines[..min(self.scroll, lines.len())].join("\n");
+        let visible = lines.iter().skip(self.scroll).take(size.height).map(|line| match self.overflow {
+            Overflow::Ellipsis => ellipsize(line, size.width),
+            Overflow::Marquee => marquee(line, size.width, self.marquee),
+            Overflow::Truncate | Overflow::Wrap => line.clone(),
+        }).collect::<Vec<String>>().join("\n");
+        let visible = sgr_prefix(sgr_state(&scrolled_past, Style::default())) + &visible;
+        let mut rendered = self.formatter.format(visible.as_str(), (size.width, size.height), (location.x, location.y));
+        rendered.push_str(&self.scroll_indicator());
+        screen.draw(&rendered, &location, &size);
+    }
+
+    fn children(&mut self) -> IterMut<'_, Box<dyn View>> {
         self.empty_children.iter_mut()
     }
 
     fn update_content(&mut self, text: String) {
-        self.text = text;
-        self.dirty = true; // gotta be updated!
+        if text != self.text {
+            self.text = text;
+            self.dirty = true; // gotta be updated!
+            // New text scrolls in from its start.
+            self.marquee = 0;
+            self.stepped = Instant::now();
+        }
+    }
+
+    fn scroll(&mut self, scroll: Scroll) {
+        let page = self.content_size().height.max(1) as isize;
+        let target = match scroll {
+            Scroll::Lines(n) => self.scroll as isize + n,
+            Scroll::Pages(n) => self.scroll as isize + n * page,
+            Scroll::Top => 0,
+            Scroll::Bottom => self.max_scroll() as isize,
+        };
+        self.set_scroll(target.clamp(0, self.max_scroll() as isize) as usize);
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        if visible != self.visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn next_turn(&self) -> Option<Instant> {
+        let moving = self.overflow == Overflow::Marquee && self.visible && self.overflowing();
+        if moving { Some(self.stepped + MARQUEE_STEP) } else { None }
+    }
+
+    fn tick(&mut self, now: Instant) -> bool {
+        if self.next_turn().is_none_or(|due| now < due) { return false; }
+
+        self.marquee += 1;
+        self.stepped = now;
+        self.dirty = true;
+        true
+    }
+
+    fn retitle(&mut self, from: &str, to: &str) {
+        if self.decoration.title.as_deref() == Some(from) {
+            self.decoration.title = Some(to.to_string());
+            self.dirty = true;
+        }
     }
 }
 
 impl Widget {
+    fn content_location(&self) -> TermLocation {
+        self.decoration.content_location(&self.location)
+    }
+
+    fn content_size(&self) -> CharDims {
+        self.decoration.shrink(&self.dims.size)
+    }
+
+    // The lines of text as they're shown `width` columns wide - wrapped, if that's how it overflows.
+    fn lines(&self, width: usize) -> Vec<String> {
+        let lines = self.text.split('\n');
+        match self.overflow {
+            Overflow::Wrap => lines.flat_map(|line| wrap(line, width)).collect(),
+            _ => lines.map(String::from).collect(),
+        }
+    }
+
+    // Whether a line that's showing is too wide to show all of.
+    fn overflowing(&self) -> bool {
+        let size = self.content_size();
+        self.lines(size.width).iter().skip(self.scroll).take(size.height).any(|line| display_width(line) > size.width)
+    }
+
+    fn max_scroll(&self) -> usize {
+        let size = self.content_size();
+        self.lines(size.width).len().saturating_sub(size.height)
+    }
+
+    fn set_scroll(&mut self, scroll: usize) {
+        if scroll != self.scroll {
+            self.scroll = scroll;
+            self.dirty = true;
+        }
+        self.at_end = scroll == self.max_scroll();
+    }
+
+    // Arrows in the right-hand column when there's more text above or below what's shown.
+    fn scroll_indicator(&self) -> String {
+        let (location, size) = (self.content_location(), self.content_size());
+        if size.width == 0 || size.height == 0 { return String::new() }
+
+        let x = location.x + size.width as u16 - 1;
+        let (top, bottom) = (location.y, location.y + size.height as u16 - 1);
+        match (self.scroll > 0, self.scroll < self.max_scroll()) {
+            (true, true) if top == bottom => format!("{}{}↕", Goto(x, top), style::Reset),
+            (true, true) => format!("{}{}↑{}↓", Goto(x, top), style::Reset, Goto(x, bottom)),
+            (true, false) => format!("{}{}↑", Goto(x, top), style::Reset),
+            (false, true) => format!("{}{}↓", Goto(x, bottom), style::Reset),
+            (false, false) => String::new(),
+        }
+    }
+
+    // The size of the content, inside `parent_dimensions` - which the decoration's already been taken from.
     fn update_dims(&mut self, parent_dimensions: &CharDims) -> CharDims {
+        let (width_constraint, height_constraint) = self.decoration.shrink_constraints((self.dims.width_constraint, self.dims.height_constraint));
         match &self.text.len() {
-            0 => { self.dims.size.clone() },
+            0 => { self.dims.size },
             _ => {
                 let lines = self.text.split("\n").collect::<Vec<&str>>();
-                let height = lines.iter().count();
-                let width = lines.iter().map(|l| l.len()).max().unwrap();
+                let width = lines.iter().map(|l| display_width(l)).max().unwrap();
                 let desired_width_constraint = DimConstraint::UpTo(width);
-                let desired_height_constraint = DimConstraint::UpTo(height);
+                let most_restrictive_width = min(desired_width_constraint, min(width_constraint, DimConstraint::Fixed(parent_dimensions.width)));
+
+                // Percent and Weight take all the room they're given, even if the text's smaller.
+                let width = if width_constraint.fills() { parent_dimensions.width } else { desired_size(&most_restrictive_width) };
+                let width = width.max(width_constraint.least()).min(parent_dimensions.width);
 
-                let most_restrictive_width = min(desired_width_constraint, min(self.dims.width_constraint, DimConstraint::Fixed(parent_dimensions.width)));
-                let most_restrictive_height = min(desired_height_constraint, min(self.dims.height_constraint, DimConstraint::Fixed(parent_dimensions.height)));
+                // Wrapped text is as tall as it is once it's wrapped to the width it's getting.
+                let height = self.lines(width).len();
+                let desired_height_constraint = DimConstraint::UpTo(height);
+                let most_restrictive_height = min(desired_height_constraint, min(height_constraint, DimConstraint::Fixed(parent_dimensions.height)));
+                let height = if height_constraint.fills() { parent_dimensions.height } else { desired_size(&most_restrictive_height) };
 
-                CharDims::new(desired_size(&most_restrictive_width), desired_size(&most_restrictive_height))
+                CharDims::new(width, height.max(height_constraint.least()).min(parent_dimensions.height))
             }
         }
     }
 }
 
-fn clear_area(location: &TermLocation, dims: &CharDims) {
-    for i in 0..dims.height as u16 {
-        writeln!(stdout(), "{}{:width$}", Goto(location.x, location.y + i), " ", width=dims.width).unwrap();
-        stdout().flush().unwrap();
-    }
-}
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::hexterm::formatting::Vt100Formatter;
+    use crate::views::rendered_rows;
+    use crate::views::decoration::Border;
+    use crate::views::screen::Color;
 
     fn fixed_size_text_widget() -> Widget {
         Widget::new(DimConstraint::Fixed(10), DimConstraint::Fixed(2), Box::new(Vt100Formatter{}), TermLocation::new(1, 1))
@@ -137,6 +293,16 @@ mod tests {
         assert_eq!(2, tw.height());
     }
 
+    #[test]
+    fn bounds_are_the_room_the_widget_could_have_used() {
+        let mut tw = wrap_content_text_widget();
+        tw.text = "short".to_string();
+        tw.inflate(&CharDims::new(40, 10), &TermLocation::new(3, 4));
+        assert_eq!(tw.width(), 5);
+        assert_eq!(tw.bounds(), CharDims::new(40, 10));
+        assert_eq!(tw.location(), TermLocation::new(3, 4));
+    }
+
     #[test]
     fn inflation_of_fixed_width_works_shrinks_to_fit_parent() {
         let mut tw = fixed_size_text_widget();
@@ -155,6 +321,15 @@ mod tests {
         assert_eq!(2, tw.height());
     }
 
+    #[test]
+    fn wrap_content_width_counts_columns_not_bytes() {
+        let mut tw = wrap_content_text_widget();
+        tw.text = "\u{1b}[33m21°C\u{1b}[39m\n☕ 日本".to_string();
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(7, tw.width());
+        assert_eq!(rendered_rows(&tw, CharDims::new(8, 2)), vec!["21°C", "☕ 日本"]);
+    }
+
     #[test]
     fn inflation_of_wrap_content_width_shrinks_to_fixed_parent_dims() {
         let mut tw = wrap_content_text_widget();
@@ -164,12 +339,52 @@ mod tests {
         assert_eq!(2, tw.height());
     }
 
+    #[test]
+    fn between_grows_and_shrinks_with_the_text_within_its_limits() {
+        let mut tw = Widget::new(DimConstraint::Between(6, 12), DimConstraint::Between(1, 3), Box::new(Vt100Formatter{}), TermLocation::new(1, 1));
+        tw.text = "hi".to_string();
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!((tw.width(), tw.height()), (6, 1));
+
+        tw.text = "a much longer line
+and
+more
+lines".to_string();
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!((tw.width(), tw.height()), (12, 3));
+
+        tw.inflate(&CharDims::new(4, 100), &TermLocation::new(1, 1));
+        assert_eq!(tw.width(), 4);
+    }
+
+    #[test]
+    fn borders_are_drawn_around_the_text_and_take_up_room() {
+        let mut tw = wrap_content_text_widget();
+        tw.set_decoration(Decoration { border: Border::Ascii, title: Some("t".to_string()), padding: 0, margin: 0 });
+        tw.text = "one\ntwo\nthree".to_string();
+        tw.inflate(&CharDims::new(7, 4), &TermLocation::new(1, 1));
+
+        assert_eq!((tw.width(), tw.height()), (7, 4));
+        assert_eq!(tw.content_area(), (TermLocation::new(2, 2), CharDims::new(5, 2)));
+        assert_eq!(rendered_rows(&tw, CharDims::new(8, 5)), vec!["+ t --+", "|one  |", "|two ↓|", "+-----+", ""]);
+    }
+
+    #[test]
+    fn only_a_title_naming_what_was_shown_is_retitled() {
+        let mut tw = wrap_content_text_widget();
+        tw.set_decoration(Decoration { border: Border::Single, title: Some("Clock".to_string()), padding: 0, margin: 0 });
+        tw.retitle("Weather", "Forecast");
+        assert_eq!(tw.decoration.title.as_deref(), Some("Clock"));
+        tw.retitle("Clock", "Forecast");
+        assert_eq!(tw.decoration.title.as_deref(), Some("Forecast"));
+    }
+
     #[test]
     fn renders_all_text_within_wrap_content() {
         let mut tw = wrap_content_text_widget();
         tw.text = "some\ntext".to_string();
         tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
-        assert_eq!(String::from("\u{1b}[1;1Hsome\u{1b}[2;1Htext"), tw.render());
+        assert_eq!(rendered_rows(&tw, CharDims::new(6, 3)), vec!["some", "text", ""]);
     }
 
     #[test]
@@ -177,7 +392,102 @@ mod tests {
         let mut tw = fixed_size_text_widget();
         tw.text = "some really long text\nand another really long line\nthis line doesn't show up at all".to_string();
         tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
-        assert_eq!(String::from("\u{1b}[1;1Hsome reall\u{1b}[2;1Hand anothe"), tw.render());
+        assert_eq!(rendered_rows(&tw, CharDims::new(12, 3)), vec!["some reall", "and anoth↓", ""]);
+    }
+
+    #[test]
+    fn scrolls_through_text_taller_than_the_widget() {
+        let mut tw = fixed_size_text_widget();
+        tw.text = "one\ntwo\nthree\nfour".to_string();
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        tw.scroll(Scroll::Lines(1));
+        assert_eq!(rendered_rows(&tw, CharDims::new(6, 2)), vec!["two ↑", "thre↓"]);
+
+        tw.scroll(Scroll::Pages(5));
+        assert_eq!(rendered_rows(&tw, CharDims::new(6, 2)), vec!["thre↑", "four"]);
+
+        tw.scroll(Scroll::Top);
+        assert_eq!(tw.scroll, 0);
+    }
+
+    #[test]
+    fn colour_set_above_the_scroll_still_applies_but_stops_at_the_edge() {
+        let mut tw = fixed_size_text_widget();
+        tw.text = "\u{1b}[44mone\ntwo\nthree\u{1b}[0m\nfour".to_string();
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        tw.scroll(Scroll::Lines(1));
+
+        let mut screen = Screen::new(CharDims::new(12, 2));
+        tw.render(&mut screen);
+        assert_eq!(screen.cell(0, 0).style.bg, Color::Indexed(4));
+        assert_eq!(screen.cell(4, 0).style.bg, Color::Default);
+        assert_eq!(screen.cell(4, 1).style.bg, Color::Default);
+    }
+
+    #[test]
+    fn wrapped_text_is_as_tall_as_its_wrapped_lines() {
+        let mut tw = Widget::new(DimConstraint::UpTo(10), DimConstraint::WrapContent, Box::new(Vt100Formatter{}), TermLocation::new(1, 1));
+        tw.set_overflow(Overflow::Wrap);
+        tw.update_content("the quick brown fox\njumps".to_string());
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+
+        assert_eq!((tw.width(), tw.height()), (10, 3));
+        assert_eq!(rendered_rows(&tw, CharDims::new(10, 3)), vec!["the quick", "brown fox", "jumps"]);
+    }
+
+    #[test]
+    fn ellipsis_shows_where_lines_are_cut_off() {
+        let mut tw = fixed_size_text_widget();
+        tw.set_overflow(Overflow::Ellipsis);
+        tw.update_content("some really long text\nshort".to_string());
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(rendered_rows(&tw, CharDims::new(10, 2)), vec!["some real…", "short"]);
+    }
+
+    #[test]
+    fn marquee_moves_along_only_when_its_due_and_needed() {
+        let mut tw = fixed_size_text_widget();
+        tw.update_content("some really long text".to_string());
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(tw.next_turn(), None);
+
+        tw.set_overflow(Overflow::Marquee);
+        let start = tw.stepped;
+        assert_eq!(tw.next_turn(), Some(start + MARQUEE_STEP));
+        assert!(!tw.tick(start));
+        assert!(tw.tick(start + MARQUEE_STEP));
+        assert!(tw.tick(start + MARQUEE_STEP * 2));
+        assert_eq!(rendered_rows(&tw, CharDims::new(10, 1)), vec!["me really"]);
+
+        tw.update_content("other really long text".to_string());
+        assert_eq!(rendered_rows(&tw, CharDims::new(10, 1)), vec!["other real"]);
+
+        tw.update_content("short".to_string());
+        assert_eq!(tw.next_turn(), None);
+    }
+
+    #[test]
+    fn follow_keeps_the_end_in_view_until_scrolled_back() {
+        let mut tw = fixed_size_text_widget();
+        tw.set_follow(true);
+        tw.update_content("one\ntwo\nthree".to_string());
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(tw.scroll, 1);
+
+        tw.update_content("one\ntwo\nthree\nfour".to_string());
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(tw.scroll, 2);
+
+        tw.scroll(Scroll::Lines(-1));
+        tw.update_content("one\ntwo\nthree\nfour\nfive".to_string());
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(tw.scroll, 1);
+
+        tw.scroll(Scroll::Bottom);
+        tw.update_content("one\ntwo\nthree\nfour\nfive\nsix".to_string());
+        tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
+        assert_eq!(tw.scroll, 4);
     }
 
     #[test]
@@ -186,7 +496,7 @@ mod tests {
         tw.text = "some really long text\nand another really long line\nthis line doesn't show up at all".to_string();
         tw.visible = false;
         tw.inflate(&CharDims::new(100, 100), &TermLocation::new(1, 1));
-        assert_eq!(String::from(""), tw.render());
+        assert_eq!(rendered_rows(&tw, CharDims::new(3, 1)), vec![""]);
     }
 
     #[test]
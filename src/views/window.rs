@@ -1,57 +1,65 @@
-use std::sync::mpsc::{Receiver, TryRecvError};
-use std::sync::Arc;
-use crate::views::{InteractiveWidget};
-use std::{thread, io};
-use regex::Regex;
+use crate::views::{CharDims, TermLocation};
 use crate::views::input_processor::InputProcessor;
-use std::io::{stdout, Stdout};
+use crate::views::screen::Screen;
 
+/***
+Window: A fixed area of the screen that an app's raw terminal output is shown in.
+    Output goes through an InputProcessor, which emulates a terminal the size of the Window,
+    so the app's cursor movement, scrolling and clearing can't reach outside it.
+ */
 pub struct Window {
-    stdin_rx: Receiver<String>,
-    view: Option<Arc<InteractiveWidget>>,
-    input_proc: InputProcessor
+    location: TermLocation,
+    input_proc: InputProcessor,
 }
 
 impl Window {
-    pub fn new(stdin_rx: Receiver<String>) -> Window {
-        Window { stdin_rx, input_proc: InputProcessor::new(), view: None }
+    pub fn new(location: TermLocation, size: CharDims) -> Window {
+        Window { location, input_proc: InputProcessor::new(size) }
     }
 
-    pub fn assign_view(&mut self, view: Arc<InteractiveWidget>) {
-        self.view = Some(view.clone());
-        self.input_proc = InputProcessor::new()
+    /// Take in some output from the app. Partial escape sequences are held back until the rest arrives.
+    pub fn push(&mut self, output: String) {
+        self.input_proc.push(output);
     }
 
-    pub fn run(&mut self) {
-        loop {
-            match self.stdin_rx.try_recv() {
-                Ok(input) => {
-                    match self.view.as_ref() {
-                        None => {},
-                        Some(v) => {
-                            self.input_proc.push(input, &v.location, &v.dims.size);
-                        }
-                    }
-                }
-                Err(_) => {}
-            }
-
-            self.input_proc.print(&mut io::stdout());
-        }
+    /// Anything the app asked the terminal that needs answering, e.g. a cursor position report.
+    pub fn replies(&mut self) -> String {
+        self.input_proc.take_replies()
     }
 
-    fn print(&self, input: String) {
-        match self.my_view() {
-            Some(v) => v.print(input),
-            None => {}
-        }
+    /// Move the Window to a new area. False if it was already there.
+    pub fn fit(&mut self, location: TermLocation, size: CharDims) -> bool {
+        if location == self.location && size == self.input_proc.screen().size() { return false; }
+        self.location = location;
+        self.input_proc.resize(size);
+        true
     }
 
-    fn my_view(&self) -> Option<Arc<InteractiveWidget>> {
-        match self.view.clone() {
-            Some(v) => Some(v.clone()),
-            None => None
-        }
+    /// Copy what the app's showing onto `screen`, over the Window's area.
+    pub fn draw(&self, screen: &mut Screen) {
+        screen.blit(self.input_proc.screen(), &self.location);
+    }
+
+    /// Where the app's cursor is, in terminal coordinates - None if it's hidden.
+    pub fn cursor(&self) -> Option<TermLocation> {
+        self.input_proc.cursor().map(|(x, y)| TermLocation::new(self.location.x + x as u16, self.location.y + y as u16))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_drawn_relative_to_the_window() {
+        let mut window = Window::new(TermLocation::new(3, 2), CharDims::new(3, 2));
+        window.push("\u{1b}[2;2Hhi".to_string());
+
+        let mut screen = Screen::new(CharDims::new(6, 4));
+        screen.draw("everywhere\neverywhere\neverywhere\neverywhere", &TermLocation::new(1, 1), &CharDims::new(6, 4));
+        window.draw(&mut screen);
+
+        assert_eq!((0..4).map(|y| screen.row_text(y)).collect::<Vec<String>>(), vec!["everyw", "ev   w", "ev hiw", "everyw"]);
+        assert_eq!(window.cursor(), Some(TermLocation::new(5, 3)));
+    }
+}